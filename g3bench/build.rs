@@ -16,6 +16,7 @@
 
 use std::env;
 
+#[allow(clippy::unusual_byte_groupings)]
 fn main() {
     let rustc = rustc_version::version_meta().unwrap();
     println!(
@@ -56,4 +57,16 @@ fn main() {
     if env::var("CARGO_FEATURE_QUIC").is_ok() {
         println!("cargo:rustc-env=G3_QUIC_FEATURE=quinn");
     }
+
+    if let Ok(version) = env::var("DEP_OPENSSL_VERSION_NUMBER") {
+        // this will require a dependency on openssl-sys crate
+        let version = u64::from_str_radix(&version, 16).unwrap();
+
+        if version >= 0x3_00_00_00_0 {
+            println!("cargo:rustc-cfg=ossl300");
+        }
+        if version >= 0x3_02_00_00_0 {
+            println!("cargo:rustc-cfg=ossl320");
+        }
+    }
 }
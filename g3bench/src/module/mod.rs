@@ -16,6 +16,7 @@
 
 pub(crate) mod http;
 pub(crate) mod openssl;
+pub(crate) mod otlp;
 pub(crate) mod proxy_protocol;
 pub(crate) mod rustls;
 pub(crate) mod ssl;
@@ -18,11 +18,15 @@ use std::io::{stderr, IsTerminal};
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use ahash::AHashMap;
 use anyhow::{anyhow, Context};
+use clap::parser::ValueSource;
 use clap::{value_parser, Arg, ArgAction, ArgMatches, Command, ValueHint};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 use g3_runtime::blended::BlendedRuntimeConfig;
 use g3_runtime::unaided::UnaidedRuntimeConfig;
@@ -46,26 +50,33 @@ const GLOBAL_ARG_RATE_LIMIT: &str = "rate-limit";
 const GLOBAL_ARG_REQUESTS: &str = "requests";
 const GLOBAL_ARG_RESOLVE: &str = "resolve";
 const GLOBAL_ARG_LOG_ERROR: &str = "log-error";
+const GLOBAL_ARG_ERROR_SAMPLE_RATE: &str = "error-sample-rate";
 const GLOBAL_ARG_IGNORE_FATAL_ERROR: &str = "ignore-fatal-error";
 const GLOBAL_ARG_EMIT_METRICS: &str = "emit-metrics";
 const GLOBAL_ARG_STATSD_TARGET_UDP: &str = "statsd-target-udp";
 const GLOBAL_ARG_STATSD_TARGET_UNIX: &str = "statsd-target-unix";
 const GLOBAL_ARG_NO_PROGRESS_BAR: &str = "no-progress-bar";
+const GLOBAL_ARG_QUIET: &str = "quiet";
 
 const GLOBAL_ARG_PEER_PICK_POLICY: &str = "peer-pick-policy";
+const GLOBAL_ARG_SEED: &str = "seed";
 const GLOBAL_ARG_TCP_LIMIT_SHIFT: &str = "tcp-limit-shift";
 const GLOBAL_ARG_TCP_LIMIT_BYTES: &str = "tcp-limit-bytes";
 const GLOBAL_ARG_UDP_LIMIT_SHIFT: &str = "udp-limit-shift";
 const GLOBAL_ARG_UDP_LIMIT_BYTES: &str = "udp-limit-bytes";
 const GLOBAL_ARG_UDP_LIMIT_PACKETS: &str = "udp-limit-packets";
 
+#[derive(Clone)]
 pub struct ProcArgs {
     pub(super) concurrency: usize,
+    /// whether the concurrency is set explicitly in the command line
+    pub(super) concurrency_set: bool,
     pub(super) latency: Option<Duration>,
     pub(super) requests: Option<usize>,
     pub(super) time_limit: Option<Duration>,
     pub(super) rate_limit: Option<RateLimitQuotaConfig>,
     pub(super) log_error_count: usize,
+    pub(super) log_error_sample_rate: Option<f64>,
     pub(super) ignore_fatal_error: bool,
     pub(super) task_unconstrained: bool,
     resolver: AHashMap<UpstreamAddr, IpAddr>,
@@ -77,8 +88,11 @@ pub struct ProcArgs {
 
     statsd_client_config: Option<StatsdClientConfig>,
     no_progress_bar: bool,
+    pub(super) quiet: bool,
 
     peer_pick_policy: SelectivePickPolicy,
+    pub(super) seed: Option<u64>,
+    seeded_rng: Option<Arc<Mutex<StdRng>>>,
     pub(super) tcp_sock_speed_limit: TcpSockSpeedLimitConfig,
     pub(super) udp_sock_speed_limit: UdpSockSpeedLimitConfig,
 }
@@ -87,11 +101,13 @@ impl Default for ProcArgs {
     fn default() -> Self {
         ProcArgs {
             concurrency: 1,
+            concurrency_set: false,
             latency: None,
             requests: None,
             time_limit: None,
             rate_limit: None,
             log_error_count: 0,
+            log_error_sample_rate: None,
             ignore_fatal_error: false,
             task_unconstrained: false,
             resolver: AHashMap::new(),
@@ -102,7 +118,10 @@ impl Default for ProcArgs {
             openssl_async_job_size: 0,
             statsd_client_config: None,
             no_progress_bar: false,
+            quiet: false,
             peer_pick_policy: SelectivePickPolicy::RoundRobin,
+            seed: None,
+            seeded_rng: None,
             tcp_sock_speed_limit: TcpSockSpeedLimitConfig::default(),
             udp_sock_speed_limit: UdpSockSpeedLimitConfig::default(),
         }
@@ -111,10 +130,22 @@ impl Default for ProcArgs {
 
 impl ProcArgs {
     pub fn summary(&self) {
+        if self.quiet {
+            return;
+        }
         println!("Concurrency Level: {}", self.concurrency);
+        println!("Worker Threads: {}", self.worker_thread_number());
         println!();
     }
 
+    fn worker_thread_number(&self) -> usize {
+        self.thread_number.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+    }
+
     pub(super) fn new_progress_bar(&self) -> Option<BenchProgress> {
         if self.no_progress_bar {
             None
@@ -182,7 +213,13 @@ impl ProcArgs {
 
     pub(super) fn select_peer<'a, T>(&'a self, peers: &'a SelectiveVec<WeightedValue<T>>) -> &'a T {
         match self.peer_pick_policy {
-            SelectivePickPolicy::Random => peers.pick_random().inner(),
+            SelectivePickPolicy::Random => match &self.seeded_rng {
+                Some(rng) => {
+                    let key = rng.lock().unwrap().gen::<u64>();
+                    peers.pick_jump(&key).inner()
+                }
+                None => peers.pick_random().inner(),
+            },
             SelectivePickPolicy::Serial => peers.pick_serial().inner(),
             SelectivePickPolicy::RoundRobin => peers.pick_round_robin().inner(),
             _ => unreachable!(),
@@ -300,9 +337,10 @@ pub fn add_global_args(app: Command) -> Command {
     )
     .arg(
         Arg::new(GLOBAL_ARG_THREADS)
-            .help("Number of threads")
+            .help("Number of worker threads, default to the number of available cpus")
             .value_name("THREAD NUMBER")
             .long(GLOBAL_ARG_THREADS)
+            .visible_alias("worker-threads")
             .global(true)
             .num_args(1)
             .value_parser(value_parser!(usize)),
@@ -332,6 +370,18 @@ pub fn add_global_args(app: Command) -> Command {
             .num_args(1)
             .value_parser(value_parser!(usize)),
     )
+    .arg(
+        Arg::new(GLOBAL_ARG_ERROR_SAMPLE_RATE)
+            .help(
+                "Only log this fraction of the error requests, all of them will still be counted. \
+                The sampled ones will all be logged if no log error count set",
+            )
+            .value_name("RATE")
+            .long(GLOBAL_ARG_ERROR_SAMPLE_RATE)
+            .global(true)
+            .num_args(1)
+            .value_parser(value_parser!(f64)),
+    )
     .arg(
         Arg::new(GLOBAL_ARG_IGNORE_FATAL_ERROR)
             .help("Continue even if fatal error occurred")
@@ -372,6 +422,14 @@ pub fn add_global_args(app: Command) -> Command {
             .long(GLOBAL_ARG_NO_PROGRESS_BAR)
             .global(true),
     )
+    .arg(
+        Arg::new(GLOBAL_ARG_QUIET)
+            .help("Print nothing but errors, the exit code will be set if any request failed")
+            .action(ArgAction::SetTrue)
+            .short('q')
+            .long(GLOBAL_ARG_QUIET)
+            .global(true),
+    )
     .arg(
         Arg::new(GLOBAL_ARG_PEER_PICK_POLICY)
             .help("Set the pick policy for selecting peers")
@@ -381,6 +439,19 @@ pub fn add_global_args(app: Command) -> Command {
             .default_value("rr")
             .num_args(1),
     )
+    .arg(
+        Arg::new(GLOBAL_ARG_SEED)
+            .help(
+                "Use a fixed seed for the random peer picking and connection pool assignment, \
+                so the same task will use the same peer / connection across runs.\n\
+                Note that this will reduce the randomness of load distribution",
+            )
+            .value_name("SEED")
+            .long(GLOBAL_ARG_SEED)
+            .global(true)
+            .num_args(1)
+            .value_parser(value_parser!(u64)),
+    )
     .arg(
         Arg::new(GLOBAL_ARG_TCP_LIMIT_SHIFT)
             .help("Shift value for the TCP socket speed limit config")
@@ -436,6 +507,8 @@ pub fn parse_global_args(args: &ArgMatches) -> anyhow::Result<ProcArgs> {
 
     if let Some(n) = args.get_one::<usize>(GLOBAL_ARG_CONCURRENCY) {
         proc_args.concurrency = *n;
+        proc_args.concurrency_set =
+            args.value_source(GLOBAL_ARG_CONCURRENCY) == Some(ValueSource::CommandLine);
     }
 
     if let Some(n) = args.get_one::<usize>(GLOBAL_ARG_LATENCY) {
@@ -487,6 +560,12 @@ pub fn parse_global_args(args: &ArgMatches) -> anyhow::Result<ProcArgs> {
     if let Some(n) = args.get_one::<usize>(GLOBAL_ARG_LOG_ERROR) {
         proc_args.log_error_count = *n;
     }
+    if let Some(rate) = args.get_one::<f64>(GLOBAL_ARG_ERROR_SAMPLE_RATE) {
+        if !(0.0..=1.0).contains(rate) {
+            return Err(anyhow!("the error sample rate should be in range [0, 1]"));
+        }
+        proc_args.log_error_sample_rate = Some(*rate);
+    }
     if args.get_flag(GLOBAL_ARG_IGNORE_FATAL_ERROR) {
         proc_args.ignore_fatal_error = true;
     }
@@ -505,6 +584,10 @@ pub fn parse_global_args(args: &ArgMatches) -> anyhow::Result<ProcArgs> {
         proc_args.statsd_client_config = Some(config);
     }
 
+    if args.get_flag(GLOBAL_ARG_QUIET) {
+        proc_args.quiet = true;
+        proc_args.no_progress_bar = true;
+    }
     if args.get_flag(GLOBAL_ARG_NO_PROGRESS_BAR) || !stderr().is_terminal() {
         proc_args.no_progress_bar = true;
     }
@@ -512,6 +595,10 @@ pub fn parse_global_args(args: &ArgMatches) -> anyhow::Result<ProcArgs> {
     if let Some(s) = args.get_one::<String>(GLOBAL_ARG_PEER_PICK_POLICY) {
         proc_args.peer_pick_policy = SelectivePickPolicy::from_str(s).unwrap();
     }
+    if let Some(seed) = args.get_one::<u64>(GLOBAL_ARG_SEED) {
+        proc_args.seed = Some(*seed);
+        proc_args.seeded_rng = Some(Arc::new(Mutex::new(StdRng::seed_from_u64(*seed))));
+    }
 
     if let Some(bytes) = args.get_one::<usize>(GLOBAL_ARG_TCP_LIMIT_BYTES) {
         let shift = args.get_one::<String>(GLOBAL_ARG_TCP_LIMIT_SHIFT).unwrap();
@@ -0,0 +1,99 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+
+use g3_types::ext::DurationExt;
+
+/// the number of passed requests between two timeout updates
+const UPDATE_INTERVAL: u64 = 100;
+
+/// Percentile adaptive request timeout.
+///
+/// The latency of all passed requests will be recorded. The timeout will be the max one before
+/// `warmup` requests have passed, after that it will be updated every `UPDATE_INTERVAL` passed
+/// requests, to be `factor` times the p99 latency, bounded in range [`min`, `max`].
+pub(super) struct KeylessAdaptiveTimeout {
+    factor: f64,
+    warmup: u64,
+    min: Duration,
+    max: Duration,
+    latency: Mutex<Histogram<u64>>,
+    current_nanos: AtomicU64,
+}
+
+impl KeylessAdaptiveTimeout {
+    pub(super) fn new(factor: f64, warmup: u64, min: Duration, max: Duration) -> Self {
+        KeylessAdaptiveTimeout {
+            factor,
+            warmup,
+            min,
+            max,
+            latency: Mutex::new(Histogram::new(3).unwrap()),
+            current_nanos: AtomicU64::new(max.as_nanos_u64()),
+        }
+    }
+
+    pub(super) fn current(&self) -> Duration {
+        Duration::from_nanos(self.current_nanos.load(Ordering::Relaxed))
+    }
+
+    pub(super) fn record(&self, latency: Duration) {
+        let mut histogram = self.latency.lock().unwrap();
+        let _ = histogram.record(latency.as_nanos_u64());
+        let count = histogram.len();
+        if count < self.warmup || (count - self.warmup) % UPDATE_INTERVAL != 0 {
+            return;
+        }
+        let p99 = Duration::from_nanos(histogram.value_at_quantile(0.99));
+        drop(histogram);
+
+        let timeout = p99.mul_f64(self.factor).clamp(self.min, self.max);
+        self.current_nanos
+            .store(timeout.as_nanos_u64(), Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounded() {
+        let t =
+            KeylessAdaptiveTimeout::new(3.0, 10, Duration::from_millis(10), Duration::from_secs(1));
+        for _ in 0..9 {
+            t.record(Duration::from_millis(20));
+        }
+        assert_eq!(t.current(), Duration::from_secs(1));
+        t.record(Duration::from_millis(20));
+        let current = t.current();
+        assert!(current >= Duration::from_millis(59) && current <= Duration::from_millis(61));
+
+        let t =
+            KeylessAdaptiveTimeout::new(3.0, 1, Duration::from_millis(10), Duration::from_secs(1));
+        t.record(Duration::from_micros(100));
+        assert_eq!(t.current(), Duration::from_millis(10));
+        let t =
+            KeylessAdaptiveTimeout::new(3.0, 1, Duration::from_millis(10), Duration::from_secs(1));
+        t.record(Duration::from_millis(500));
+        assert_eq!(t.current(), Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,336 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use hdrhistogram::serialization::{Deserializer, Serializer, V2DeflateSerializer};
+use hdrhistogram::Histogram;
+use serde_json::{json, Value};
+
+const MANIFEST_FILE: &str = "manifest.json";
+const PLAN_FILE: &str = "plan.txt";
+const SUMMARY_FILE: &str = "summary.json";
+const LATENCY_FILE: &str = "latency.csv";
+const LATENCY_HDR_FILE: &str = "latency.hdr";
+const ERRORS_FILE: &str = "errors.json";
+
+const LATENCY_PERCENTILES: [f64; 9] = [50.0, 66.0, 75.0, 80.0, 90.0, 95.0, 98.0, 99.0, 100.0];
+
+/// all the result files of a single run, which will be saved to the artifact dir
+pub(super) struct KeylessArtifact {
+    pub(super) plan: Vec<u8>,
+    pub(super) summary: Value,
+    pub(super) latency: Option<Histogram<u64>>,
+    pub(super) errors: Value,
+}
+
+impl KeylessArtifact {
+    pub(super) fn save(&self, dir: &Path) -> anyhow::Result<()> {
+        fs::create_dir_all(dir)
+            .map_err(|e| anyhow!("failed to create artifact dir {}: {e}", dir.display()))?;
+
+        write_file(dir, PLAN_FILE, &self.plan)?;
+        write_json_file(dir, SUMMARY_FILE, &self.summary)?;
+        let mut files = json!({
+            "plan": PLAN_FILE,
+            "summary": SUMMARY_FILE,
+            "errors": ERRORS_FILE,
+        });
+        if let Some(h) = &self.latency {
+            write_file(dir, LATENCY_FILE, latency_csv(h).as_bytes())?;
+            files["latency"] = Value::from(LATENCY_FILE);
+            save_hdr_histogram(&dir.join(LATENCY_HDR_FILE), h)?;
+            files["latency_hdr"] = Value::from(LATENCY_HDR_FILE);
+        }
+        write_json_file(dir, ERRORS_FILE, &self.errors)?;
+
+        // write the manifest at last, so a dir without it is an incomplete one
+        let manifest = json!({
+            "tool": crate::build::PKG_NAME,
+            "version": crate::build::VERSION,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "files": files,
+        });
+        write_json_file(dir, MANIFEST_FILE, &manifest)
+    }
+}
+
+fn write_file(dir: &Path, name: &str, data: &[u8]) -> anyhow::Result<()> {
+    let path = dir.join(name);
+    fs::write(&path, data).map_err(|e| anyhow!("failed to write file {}: {e}", path.display()))
+}
+
+fn write_json_file(dir: &Path, name: &str, value: &Value) -> anyhow::Result<()> {
+    let data = serde_json::to_vec_pretty(value)
+        .map_err(|e| anyhow!("failed to encode {name} as json: {e}"))?;
+    write_file(dir, name, &data)
+}
+
+fn latency_csv(h: &Histogram<u64>) -> String {
+    let mut s = String::from("percentile,total_time_ns\n");
+    for pct in LATENCY_PERCENTILES {
+        s.push_str(&format!("{pct},{}\n", h.value_at_percentile(pct)));
+    }
+    s
+}
+
+pub(super) fn latency_json(h: &Histogram<u64>) -> Value {
+    json!({
+        "min_ns": h.min(),
+        "mean_ns": h.mean(),
+        "max_ns": h.max(),
+        "p50_ns": h.value_at_quantile(0.50),
+        "p90_ns": h.value_at_quantile(0.90),
+        "p99_ns": h.value_at_quantile(0.99),
+    })
+}
+
+/// save the histogram in the V2 compressed format, which can be merged with other ones
+pub(super) fn save_hdr_histogram(path: &Path, h: &Histogram<u64>) -> anyhow::Result<()> {
+    let mut data = Vec::new();
+    V2DeflateSerializer::new()
+        .serialize(h, &mut data)
+        .map_err(|e| anyhow!("failed to encode the latency histogram: {e}"))?;
+    fs::write(path, data).map_err(|e| anyhow!("failed to write file {}: {e}", path.display()))
+}
+
+fn load_hdr_histogram(path: &Path) -> anyhow::Result<Histogram<u64>> {
+    let data =
+        fs::read(path).map_err(|e| anyhow!("failed to read file {}: {e}", path.display()))?;
+    Deserializer::new()
+        .deserialize(&mut &data[..])
+        .map_err(|e| anyhow!("invalid hdr histogram file {}: {e}", path.display()))
+}
+
+fn load_json_file(dir: &Path, name: &str) -> anyhow::Result<Value> {
+    let path = dir.join(name);
+    let data =
+        fs::read(&path).map_err(|e| anyhow!("failed to read file {}: {e}", path.display()))?;
+    serde_json::from_slice(&data).map_err(|e| anyhow!("invalid json file {}: {e}", path.display()))
+}
+
+fn check_manifest(dir: &Path) -> anyhow::Result<()> {
+    if !dir.join(MANIFEST_FILE).is_file() {
+        return Err(anyhow!(
+            "{} is not a complete artifact dir, no {MANIFEST_FILE} found",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// load the summary from a previous artifact dir
+pub(super) fn load_baseline(dir: &Path) -> anyhow::Result<Value> {
+    check_manifest(dir)?;
+    load_json_file(dir, SUMMARY_FILE)
+}
+
+/// the combined result of the artifact dirs saved by instances that run at the same time
+struct MergedArtifacts {
+    instances: usize,
+    passed: u64,
+    failed: u64,
+    requests_per_second: f64,
+    max_total_time: f64,
+    latency: Histogram<u64>,
+    missing_latency: usize,
+}
+
+impl MergedArtifacts {
+    fn load(dirs: &[PathBuf]) -> anyhow::Result<Self> {
+        let mut merged = MergedArtifacts {
+            instances: 0,
+            passed: 0,
+            failed: 0,
+            requests_per_second: 0.0,
+            max_total_time: 0.0,
+            latency: Histogram::new(3)
+                .map_err(|e| anyhow!("failed to create latency histogram: {e}"))?,
+            missing_latency: 0,
+        };
+        for dir in dirs {
+            check_manifest(dir)?;
+            let manifest = load_json_file(dir, MANIFEST_FILE)?;
+            let summary = load_json_file(dir, SUMMARY_FILE)?;
+
+            merged.instances += 1;
+            merged.passed += summary["passed"].as_u64().unwrap_or_default();
+            merged.failed += summary["failed"].as_u64().unwrap_or_default();
+            // the instances run in parallel, so the throughput should be summed up
+            merged.requests_per_second +=
+                summary["requests_per_second"].as_f64().unwrap_or_default();
+            let total_time = summary["total_time_secs"].as_f64().unwrap_or_default();
+            merged.max_total_time = merged.max_total_time.max(total_time);
+
+            match manifest["files"]["latency_hdr"].as_str() {
+                Some(name) => {
+                    let h = load_hdr_histogram(&dir.join(name))?;
+                    merged.latency.add(&h).map_err(|e| {
+                        anyhow!("failed to merge latency of {}: {e}", dir.display())
+                    })?;
+                }
+                None => merged.missing_latency += 1,
+            }
+        }
+        Ok(merged)
+    }
+
+    fn summary(&self) {
+        println!("Merged instances:     {}", self.instances);
+        println!("Max time taken:       {:.3}s", self.max_total_time);
+        println!("Complete requests:    {}", self.passed);
+        if self.failed > 0 {
+            println!("Failed requests:      {}", self.failed);
+        }
+        println!(
+            "Requests per second:  {:.3} [#/sec] (sum)",
+            self.requests_per_second
+        );
+        if self.missing_latency > 0 {
+            println!(
+                "WARN: {} instances have no {LATENCY_HDR_FILE} saved, skip their latency",
+                self.missing_latency
+            );
+        }
+        if self.latency.is_empty() {
+            return;
+        }
+        println!("Percentage of the requests served within a certain time");
+        for pct in LATENCY_PERCENTILES {
+            let v = Duration::from_nanos(self.latency.value_at_percentile(pct));
+            println!("{pct:4}% {v:8.3?}");
+        }
+    }
+}
+
+/// merge the artifact dirs saved by multiple instances and print the combined report
+pub(super) fn merge_artifacts(dirs: &[PathBuf]) -> anyhow::Result<()> {
+    let merged = MergedArtifacts::load(dirs)?;
+    merged.summary();
+    Ok(())
+}
+
+pub(super) fn compare_with_baseline(baseline: &Value, current: &Value) {
+    println!("# Baseline Comparison");
+    println!(
+        "{:<20} {:>12} {:>12} {:>9}",
+        "", "Baseline", "Current", "Change"
+    );
+    if let (Some(base), Some(curr)) = (
+        baseline["requests_per_second"].as_f64(),
+        current["requests_per_second"].as_f64(),
+    ) {
+        println!(
+            "{:<20} {base:>12.3} {curr:>12.3} {:>9}",
+            "Requests/s:",
+            change_ratio(base, curr)
+        );
+    }
+    for (name, key) in [("P50:", "p50_ns"), ("P90:", "p90_ns"), ("P99:", "p99_ns")] {
+        if let (Some(base), Some(curr)) = (
+            baseline["latency"][key].as_u64(),
+            current["latency"][key].as_u64(),
+        ) {
+            let base_d = Duration::from_nanos(base);
+            let curr_d = Duration::from_nanos(curr);
+            println!(
+                "{name:<20} {base_d:>12.3?} {curr_d:>12.3?} {:>9}",
+                change_ratio(base as f64, curr as f64)
+            );
+        }
+    }
+}
+
+fn change_ratio(base: f64, curr: f64) -> String {
+    if base == 0.0 {
+        return "-".to_string();
+    }
+    format!("{:+.2}%", (curr - base) / base * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_percentiles() {
+        let mut h = Histogram::<u64>::new(3).unwrap();
+        for v in 1..=100 {
+            h.record(v * 1000).unwrap();
+        }
+        let csv = latency_csv(&h);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("percentile,total_time_ns"));
+        assert_eq!(lines.next(), Some("50,50015"));
+        assert_eq!(lines.last(), Some("100,100031"));
+
+        assert_eq!(change_ratio(100.0, 110.0), "+10.00%");
+        assert_eq!(change_ratio(0.0, 1.0), "-");
+    }
+
+    #[test]
+    fn hdr_roundtrip() {
+        let mut h = Histogram::<u64>::new(3).unwrap();
+        for v in 1..=100 {
+            h.record(v * 1000).unwrap();
+        }
+        let path = std::env::temp_dir().join(format!("g3bench-hdr-{}", std::process::id()));
+        save_hdr_histogram(&path, &h).unwrap();
+        let data = fs::read(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let decoded: Histogram<u64> = Deserializer::new().deserialize(&mut &data[..]).unwrap();
+        assert_eq!(decoded, h);
+    }
+
+    #[test]
+    fn merge_dirs() {
+        let base = std::env::temp_dir().join(format!("g3bench-merge-{}", std::process::id()));
+        let mut dirs = Vec::new();
+        for i in 1..=2u64 {
+            let mut h = Histogram::<u64>::new(3).unwrap();
+            h.record(i * 1000).unwrap();
+            let artifact = KeylessArtifact {
+                plan: Vec::new(),
+                summary: json!({
+                    "total_time_secs": i as f64,
+                    "passed": 10 * i,
+                    "failed": i,
+                    "requests_per_second": 10.0,
+                }),
+                latency: Some(h),
+                errors: json!({}),
+            };
+            let dir = base.join(i.to_string());
+            artifact.save(&dir).unwrap();
+            dirs.push(dir);
+        }
+        let merged = MergedArtifacts::load(&dirs);
+        let _ = fs::remove_dir_all(&base);
+
+        let merged = merged.unwrap();
+        assert_eq!(merged.instances, 2);
+        assert_eq!(merged.passed, 30);
+        assert_eq!(merged.failed, 3);
+        assert_eq!(merged.requests_per_second, 20.0);
+        assert_eq!(merged.max_total_time, 2.0);
+        assert_eq!(merged.latency.len(), 2);
+        assert_eq!(merged.missing_latency, 0);
+    }
+}
@@ -0,0 +1,109 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use hdrhistogram::Histogram;
+use tokio::time::Instant;
+
+use g3_types::ext::DurationExt;
+
+use super::{KeylessCloudflareArgs, KeylessRequest, ProcArgs, SimplexTransfer};
+
+/// send one cold request and then `warm_count` warm requests for each of the keys,
+/// and compare the latency of them to find out the key loading cost of the server
+pub(super) async fn run_cache_check(
+    proc_args: &Arc<ProcArgs>,
+    cf_args: Arc<KeylessCloudflareArgs>,
+    warm_count: usize,
+) -> anyhow::Result<()> {
+    let mut cold_h = Histogram::<u64>::new(3).unwrap();
+    let mut warm_h = Histogram::<u64>::new(3).unwrap();
+
+    let peer = cf_args.select_target_addr(proc_args)?;
+    let use_tls = cf_args.select_tls();
+    let mut connection = tokio::time::timeout(
+        cf_args.new_connection_timeout(),
+        cf_args.new_simplex_keyless_connection(peer, use_tls, None),
+    )
+    .await
+    .map_err(|_| anyhow!("timed out to connect to {peer}"))??;
+
+    println!("# Key Cache");
+    println!(
+        "{:<40} {:>10} {:>10} {:>10} {:>10}",
+        "SKI", "Cold", "WarmMean", "WarmPct50", "WarmPct99"
+    );
+    for key in &cf_args.global.multi_keys {
+        let builder = cf_args.new_request_builder(&key.ski)?;
+        let mut request = builder.build(&cf_args.global.payload)?;
+
+        let cold = send_request(&cf_args, &mut connection, &mut request).await?;
+        let _ = cold_h.record(cold.as_nanos_u64());
+
+        let mut key_warm_h = Histogram::<u64>::new(3).unwrap();
+        for _ in 0..warm_count {
+            let warm = send_request(&cf_args, &mut connection, &mut request).await?;
+            let _ = key_warm_h.record(warm.as_nanos_u64());
+        }
+        let _ = warm_h.add(&key_warm_h);
+
+        let w_mean = Duration::from_secs_f64(key_warm_h.mean() / 1_000_000_000.0);
+        let w_pct50 = Duration::from_nanos(key_warm_h.value_at_quantile(0.50));
+        let w_pct99 = Duration::from_nanos(key_warm_h.value_at_quantile(0.99));
+        println!(
+            "{:<40} {cold:>10.3?} {w_mean:>10.3?} {w_pct50:>10.3?} {w_pct99:>10.3?}",
+            hex::encode(&key.ski),
+        );
+    }
+
+    println!("# Cold vs Warm");
+    println!(
+        "{:<8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "", "Count", "Min", "pct50", "pct90", "pct99", "Max"
+    );
+    print_histogram_row("Cold", &cold_h);
+    print_histogram_row("Warm", &warm_h);
+    Ok(())
+}
+
+async fn send_request(
+    cf_args: &KeylessCloudflareArgs,
+    connection: &mut SimplexTransfer,
+    request: &mut KeylessRequest,
+) -> anyhow::Result<Duration> {
+    let timeout = cf_args.request_timeout();
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, connection.send_request(request)).await {
+        Ok(Ok(_)) => Ok(start.elapsed()),
+        Ok(Err(e)) => Err(anyhow!("request failed: {e}")),
+        Err(_) => Err(anyhow!("request timed out after {timeout:?}")),
+    }
+}
+
+fn print_histogram_row(name: &str, h: &Histogram<u64>) {
+    let t_min = Duration::from_nanos(h.min());
+    let t_pct50 = Duration::from_nanos(h.value_at_quantile(0.50));
+    let t_pct90 = Duration::from_nanos(h.value_at_quantile(0.90));
+    let t_pct99 = Duration::from_nanos(h.value_at_quantile(0.99));
+    let t_max = Duration::from_nanos(h.max());
+    println!(
+        "{name:<8} {:>10} {t_min:>10.3?} {t_pct50:>10.3?} {t_pct90:>10.3?} {t_pct99:>10.3?} {t_max:>10.3?}",
+        h.len()
+    );
+}
@@ -0,0 +1,499 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll, Waker};
+use std::time::Duration;
+
+use atomic_waker::AtomicWaker;
+use concurrent_queue::{ConcurrentQueue, PopError, PushError};
+use rustc_hash::FxHashMap;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::{Instant, Sleep};
+
+use super::{KeylessLocalError, KeylessRequest, KeylessResponse, KeylessResponseError};
+
+struct ResponseValue {
+    data: Option<KeylessResponse>,
+    waker: Option<Waker>,
+    created: Instant,
+    end: bool,
+}
+
+impl ResponseValue {
+    fn new(waker: Waker) -> Self {
+        ResponseValue {
+            data: None,
+            waker: Some(waker),
+            created: Instant::now(),
+            end: false,
+        }
+    }
+
+    fn empty() -> Self {
+        ResponseValue {
+            data: None,
+            waker: None,
+            created: Instant::now(),
+            end: true,
+        }
+    }
+}
+
+struct SharedState {
+    write_waker: AtomicWaker,
+    next_req_id: AtomicU32,
+    req_queue: ConcurrentQueue<(KeylessRequest, Waker)>,
+    rsp_table: Mutex<FxHashMap<u32, ResponseValue>>,
+    error: Mutex<Option<Arc<KeylessResponseError>>>,
+}
+
+impl SharedState {
+    fn next_req_id(&self) -> u32 {
+        self.next_req_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn set_req_error(&self, e: io::Error) {
+        let mut req_err_guard = self.error.lock().unwrap();
+        *req_err_guard = Some(Arc::new(KeylessLocalError::WriteFailed(e).into()));
+    }
+
+    fn set_rsp_error(&self, e: KeylessResponseError) {
+        let mut rsp_err_guard = self.error.lock().unwrap();
+        *rsp_err_guard = Some(Arc::new(e));
+    }
+
+    fn clean_pending_req(&self) {
+        let mut rsp_table_guard = self.rsp_table.lock().unwrap();
+        while let Ok((r, waker)) = self.req_queue.pop() {
+            rsp_table_guard.insert(r.id(), ResponseValue::empty());
+            waker.wake();
+        }
+        for v in (*rsp_table_guard).values_mut() {
+            if let Some(waker) = v.waker.take() {
+                waker.wake();
+            }
+            v.end = true;
+        }
+    }
+
+    fn take_write_waker(&self) -> Option<Waker> {
+        self.write_waker.take()
+    }
+}
+
+impl Default for SharedState {
+    fn default() -> Self {
+        SharedState {
+            write_waker: AtomicWaker::new(),
+            next_req_id: AtomicU32::new(0),
+            req_queue: ConcurrentQueue::bounded(1024),
+            rsp_table: Mutex::new(FxHashMap::default()),
+            error: Mutex::new(None),
+        }
+    }
+}
+
+struct UnderlyingWriterState {
+    shared: Arc<SharedState>,
+    current_offset: usize,
+    current_request: Option<KeylessRequest>,
+    request_timeout: Duration,
+    shutdown_wait: Option<Pin<Box<Sleep>>>,
+}
+
+impl UnderlyingWriterState {
+    fn poll_write<W>(&mut self, cx: &mut Context<'_>, mut writer: Pin<&mut W>) -> Poll<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.shared.write_waker.register(cx.waker());
+
+        let mut do_flush = false;
+        loop {
+            if let Some(req) = self.current_request.take() {
+                let current_buffer = req.as_bytes();
+                while self.current_offset < current_buffer.len() {
+                    match writer
+                        .as_mut()
+                        .poll_write(cx, &current_buffer[self.current_offset..])
+                    {
+                        Poll::Ready(Ok(n)) => {
+                            self.current_offset += n;
+                            do_flush = true;
+                        }
+                        Poll::Ready(Err(e)) => {
+                            self.shared.req_queue.close();
+                            self.shared.set_req_error(e);
+                            self.shared.clean_pending_req();
+                            let _ = writer.as_mut().poll_shutdown(cx);
+                            return Poll::Ready(());
+                        }
+                        Poll::Pending => {
+                            self.current_request = Some(req);
+                            return Poll::Pending;
+                        }
+                    };
+                }
+            }
+
+            match self.shared.req_queue.pop() {
+                Ok((req, waker)) => {
+                    let mut rsp_table = self.shared.rsp_table.lock().unwrap();
+                    // keep the existing one for resent requests, the response may be received
+                    rsp_table
+                        .entry(req.id())
+                        .or_insert_with(|| ResponseValue::new(waker));
+                    drop(rsp_table);
+                    self.current_offset = 0;
+                    self.current_request = Some(req);
+                }
+                Err(PopError::Empty) => {
+                    if do_flush {
+                        if let Err(e) = ready!(writer.as_mut().poll_flush(cx)) {
+                            self.shared.req_queue.close();
+                            self.shared.set_req_error(e);
+                            self.shared.clean_pending_req();
+                            let _ = writer.as_mut().poll_shutdown(cx);
+                            return Poll::Ready(());
+                        }
+                    }
+                    return Poll::Pending;
+                }
+                Err(PopError::Closed) => {
+                    let _ = self.shared.take_write_waker(); // make sure no more wake by others
+                    let mut sleep = self
+                        .shutdown_wait
+                        .take()
+                        .unwrap_or_else(|| Box::pin(tokio::time::sleep(self.request_timeout)));
+                    return match sleep.as_mut().poll(cx) {
+                        Poll::Ready(_) => {
+                            let _ = writer.as_mut().poll_shutdown(cx);
+                            Poll::Ready(())
+                        }
+                        Poll::Pending => {
+                            self.shutdown_wait = Some(sleep);
+                            Poll::Pending
+                        }
+                    };
+                }
+            }
+        }
+    }
+}
+
+struct UnderlyingWriter<W> {
+    writer: W,
+    state: UnderlyingWriterState,
+}
+
+impl<W> Future for UnderlyingWriter<W>
+where
+    W: AsyncWrite + Unpin,
+{
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let me = &mut *self;
+
+        me.state.poll_write(cx, Pin::new(&mut me.writer))
+    }
+}
+
+pub(crate) enum SendRequestError {
+    /// no response received, the reason can be fetched from the transfer
+    NoResponse(u32),
+    /// the response is received but not valid for the request
+    InvalidResponse(u32, KeylessLocalError),
+    /// the request can not be sent, e.g. the request hmac failed to be computed
+    InvalidRequest(u32, KeylessLocalError),
+}
+
+struct RequestRetransmit {
+    request: KeylessRequest,
+    interval: Duration,
+    timer: Pin<Box<Sleep>>,
+}
+
+impl RequestRetransmit {
+    fn new(request: KeylessRequest, interval: Duration) -> Self {
+        RequestRetransmit {
+            request,
+            interval,
+            timer: Box::pin(tokio::time::sleep(interval)),
+        }
+    }
+
+    /// resend the request if the timer fires, and double the wait time for the next one
+    fn poll_resend(&mut self, cx: &mut Context<'_>, shared: &SharedState) {
+        while self.timer.as_mut().poll(cx).is_ready() {
+            // the request will be dropped if the queue is full or closed, which is fine as
+            // there will be another resend or the request will be ended by the writer
+            let _ = shared
+                .req_queue
+                .push((self.request.clone(), cx.waker().clone()));
+            shared.write_waker.wake();
+            self.interval = self.interval.saturating_mul(2);
+            self.timer = Box::pin(tokio::time::sleep(self.interval));
+        }
+    }
+}
+
+pub(crate) struct SendRequest {
+    shared: Arc<SharedState>,
+    request: Option<KeylessRequest>,
+    rsp_id: u32,
+    rsp_opcode: Option<u8>,
+    retransmit_interval: Option<Duration>,
+    retransmit: Option<RequestRetransmit>,
+}
+
+impl Future for SendRequest {
+    type Output = Result<KeylessResponse, SendRequestError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(mut req) = self.request.take() {
+            let rsp_waker = cx.waker().clone();
+            let id = self.shared.next_req_id();
+            if let Err(e) = req.set_id(id) {
+                return Poll::Ready(Err(SendRequestError::InvalidRequest(id, e)));
+            }
+            let retransmit = self
+                .retransmit_interval
+                .map(|interval| RequestRetransmit::new(req.clone(), interval));
+            match self.shared.req_queue.push((req, rsp_waker)) {
+                Ok(_) => {
+                    self.shared.write_waker.wake();
+                    self.rsp_id = id;
+                    if let Some(mut retransmit) = retransmit {
+                        retransmit.poll_resend(cx, &self.shared);
+                        self.retransmit = Some(retransmit);
+                    }
+                    Poll::Pending
+                }
+                Err(PushError::Closed(_)) => {
+                    Poll::Ready(Err(SendRequestError::NoResponse(self.rsp_id)))
+                }
+                Err(PushError::Full((req, waker))) => {
+                    self.request = Some(req);
+                    waker.wake();
+                    Poll::Pending
+                }
+            }
+        } else {
+            let mut rsp_table_guard = self.shared.rsp_table.lock().unwrap();
+            let ended = rsp_table_guard
+                .get(&self.rsp_id)
+                .map(|v| v.end)
+                .unwrap_or(false);
+            if !ended {
+                drop(rsp_table_guard);
+                let me = &mut *self;
+                if let Some(retransmit) = &mut me.retransmit {
+                    retransmit.poll_resend(cx, &me.shared);
+                }
+                return Poll::Pending;
+            }
+            match rsp_table_guard.remove(&self.rsp_id) {
+                Some(v) => {
+                    let Some(rsp) = v.data else {
+                        return Poll::Ready(Err(SendRequestError::NoResponse(self.rsp_id)));
+                    };
+                    match self.rsp_opcode.map(|opcode| rsp.check_opcode(opcode)) {
+                        Some(Err(e)) => {
+                            Poll::Ready(Err(SendRequestError::InvalidResponse(self.rsp_id, e)))
+                        }
+                        _ => Poll::Ready(Ok(rsp)),
+                    }
+                }
+                None => Poll::Pending,
+            }
+        }
+    }
+}
+
+pub(crate) struct MultiplexTransfer {
+    shared: Arc<SharedState>,
+    local_addr: SocketAddr,
+    peer_addr: SocketAddr,
+    is_tls: bool,
+    tls_version: Option<&'static str>,
+    verify_opcode: bool,
+    retransmit_interval: Option<Duration>,
+}
+
+impl Drop for MultiplexTransfer {
+    fn drop(&mut self) {
+        self.shared.req_queue.close();
+        if let Some(waker) = self.shared.take_write_waker() {
+            waker.wake(); // let the writer handle the quit
+        }
+    }
+}
+
+impl MultiplexTransfer {
+    pub(crate) fn is_closed(&self) -> bool {
+        self.shared.req_queue.is_closed()
+    }
+
+    #[inline]
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    #[inline]
+    pub(crate) fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    #[inline]
+    pub(crate) fn is_tls(&self) -> bool {
+        self.is_tls
+    }
+
+    #[inline]
+    pub(crate) fn tls_version(&self) -> Option<&'static str> {
+        self.tls_version
+    }
+
+    pub(crate) fn set_tls_version(&mut self, version: &'static str) {
+        self.tls_version = Some(version);
+    }
+
+    /// check the response opcode against the request one
+    pub(crate) fn set_verify_opcode(&mut self) {
+        self.verify_opcode = true;
+    }
+
+    /// resend the request if no response is received in time,
+    /// this should be set if the datagrams may be lost
+    pub(crate) fn set_retransmit_interval(&mut self, interval: Duration) {
+        self.retransmit_interval = Some(interval);
+    }
+
+    pub(crate) fn send_request(&self, req: KeylessRequest) -> SendRequest {
+        let rsp_opcode = self.verify_opcode.then(|| req.response_opcode());
+        SendRequest {
+            shared: self.shared.clone(),
+            request: Some(req),
+            rsp_id: 0,
+            rsp_opcode,
+            retransmit_interval: self.retransmit_interval,
+            retransmit: None,
+        }
+    }
+
+    pub(crate) fn fetch_error(&self) -> Option<Arc<KeylessResponseError>> {
+        let guard = self.shared.error.lock().unwrap();
+        guard.clone()
+    }
+
+    pub(crate) fn start<R, W>(
+        mut r: R,
+        w: W,
+        local_addr: SocketAddr,
+        peer_addr: SocketAddr,
+        is_tls: bool,
+        request_timeout: Duration,
+        max_response_size: usize,
+    ) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let shared = Arc::new(SharedState::default());
+        let handle = MultiplexTransfer {
+            shared: shared.clone(),
+            local_addr,
+            peer_addr,
+            is_tls,
+            tls_version: None,
+            verify_opcode: false,
+            retransmit_interval: None,
+        };
+
+        let underlying_w = UnderlyingWriter {
+            writer: w,
+            state: UnderlyingWriterState {
+                shared: Arc::clone(&shared),
+                current_offset: 0,
+                current_request: None,
+                request_timeout,
+                shutdown_wait: None,
+            },
+        };
+        tokio::spawn(underlying_w);
+
+        let clean_shared = shared.clone();
+        tokio::spawn(async move {
+            // use a timer to clean timeout cache and keep hashtable small
+            let mut interval = tokio::time::interval(request_timeout);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+
+                let mut rsp_table_guard = clean_shared.rsp_table.lock().unwrap();
+                rsp_table_guard.retain(|_, v| {
+                    if v.created.elapsed() > request_timeout {
+                        if let Some(waker) = v.waker.take() {
+                            v.end = true;
+                            waker.wake();
+                        }
+                        false
+                    } else {
+                        true
+                    }
+                });
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut buf: Vec<u8> = Vec::with_capacity(1024);
+            loop {
+                match KeylessResponse::read(&mut r, &mut buf, max_response_size).await {
+                    Ok(r) => {
+                        let mut rsp_table_guard = shared.rsp_table.lock().unwrap();
+                        let Some(entry) = rsp_table_guard.get_mut(&r.id()) else {
+                            continue;
+                        };
+                        if let Some(waker) = entry.waker.take() {
+                            entry.data = Some(r);
+                            entry.end = true;
+                            drop(rsp_table_guard);
+                            waker.wake();
+                        }
+                    }
+                    Err(e) => {
+                        shared.req_queue.close();
+                        shared.set_rsp_error(e);
+                        shared.clean_pending_req();
+                        if let Some(waker) = shared.take_write_waker() {
+                            waker.wake(); // tell the writer to quit
+                        }
+                        break;
+                    }
+                };
+            }
+        });
+
+        handle
+    }
+}
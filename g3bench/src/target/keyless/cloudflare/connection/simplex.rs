@@ -0,0 +1,151 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures_util::FutureExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{KeylessLocalError, KeylessRequest, KeylessResponse, KeylessResponseError};
+
+pub(crate) struct SimplexTransfer {
+    reader: Box<dyn AsyncRead + Send + Sync + Unpin>,
+    writer: Box<dyn AsyncWrite + Send + Sync + Unpin>,
+    next_req_id: u32,
+    read_buf: Vec<u8>,
+    max_response_size: usize,
+    local_addr: SocketAddr,
+    peer_addr: SocketAddr,
+    tls_version: Option<&'static str>,
+    verify_opcode: bool,
+    retransmit_interval: Option<Duration>,
+}
+
+impl SimplexTransfer {
+    pub(crate) fn new<R, W>(
+        reader: R,
+        writer: W,
+        local_addr: SocketAddr,
+        peer_addr: SocketAddr,
+        max_response_size: usize,
+    ) -> Self
+    where
+        R: AsyncRead + Send + Sync + Unpin + 'static,
+        W: AsyncWrite + Send + Sync + Unpin + 'static,
+    {
+        SimplexTransfer {
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+            next_req_id: 0,
+            read_buf: Vec::with_capacity(1024),
+            max_response_size,
+            local_addr,
+            peer_addr,
+            tls_version: None,
+            verify_opcode: false,
+            retransmit_interval: None,
+        }
+    }
+
+    pub(crate) fn set_tls_version(&mut self, version: &'static str) {
+        self.tls_version = Some(version);
+    }
+
+    /// check the response opcode against the request one
+    pub(crate) fn set_verify_opcode(&mut self) {
+        self.verify_opcode = true;
+    }
+
+    /// resend the request if no response is received in time,
+    /// this should be set if the datagrams may be lost
+    pub(crate) fn set_retransmit_interval(&mut self, interval: Duration) {
+        self.retransmit_interval = Some(interval);
+    }
+
+    pub(crate) fn is_closed(&mut self) -> bool {
+        let mut buf = [0u8; 4];
+        self.reader.read(&mut buf).now_or_never().is_some()
+    }
+
+    #[inline]
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    #[inline]
+    pub(crate) fn peer_addr(&self) -> SocketAddr {
+        self.peer_addr
+    }
+
+    #[inline]
+    pub(crate) fn tls_version(&self) -> Option<&'static str> {
+        self.tls_version
+    }
+
+    pub(crate) async fn send_request(
+        &mut self,
+        req: &mut KeylessRequest,
+    ) -> Result<KeylessResponse, KeylessResponseError> {
+        req.set_id(self.next_req_id)?;
+        self.next_req_id = self.next_req_id.wrapping_add(1);
+
+        self.writer
+            .write_all(req.as_bytes())
+            .await
+            .map_err(KeylessLocalError::WriteFailed)?;
+
+        let rsp = match self.retransmit_interval {
+            Some(interval) => self.recv_with_retransmit(req, interval).await?,
+            None => {
+                KeylessResponse::read(&mut self.reader, &mut self.read_buf, self.max_response_size)
+                    .await?
+            }
+        };
+        if self.verify_opcode {
+            rsp.check_opcode(req.response_opcode())?;
+        }
+        Ok(rsp)
+    }
+
+    async fn recv_with_retransmit(
+        &mut self,
+        req: &KeylessRequest,
+        mut interval: Duration,
+    ) -> Result<KeylessResponse, KeylessResponseError> {
+        loop {
+            let read =
+                KeylessResponse::read(&mut self.reader, &mut self.read_buf, self.max_response_size);
+            tokio::pin!(read);
+            let rsp = loop {
+                tokio::select! {
+                    r = &mut read => break r?,
+                    _ = tokio::time::sleep(interval) => {
+                        self.writer
+                            .write_all(req.as_bytes())
+                            .await
+                            .map_err(KeylessLocalError::WriteFailed)?;
+                        interval = interval.saturating_mul(2);
+                    }
+                }
+            };
+            // skip the late responses for the previous requests
+            if rsp.id() == req.id() {
+                return Ok(rsp);
+            }
+        }
+    }
+}
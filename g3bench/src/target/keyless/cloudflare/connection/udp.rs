@@ -0,0 +1,210 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::UdpSocket;
+use tokio::time::Sleep;
+
+/// the interval to wake up the dtls layer when waiting for datagrams,
+/// so it can check its own timer and do handshake retransmits
+const DTLS_TIMER_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// a connected udp socket, each write will be sent as one datagram,
+/// and each read will receive one datagram
+pub(crate) struct UdpDatagramStream {
+    socket: UdpSocket,
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl UdpDatagramStream {
+    pub(crate) fn new(socket: UdpSocket) -> Self {
+        UdpDatagramStream {
+            socket,
+            timer: None,
+        }
+    }
+}
+
+impl AsyncRead for UdpDatagramStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.socket.poll_recv(cx, buf) {
+            Poll::Ready(r) => {
+                self.timer = None;
+                Poll::Ready(r)
+            }
+            Poll::Pending => {
+                let timer = self
+                    .timer
+                    .get_or_insert_with(|| Box::pin(tokio::time::sleep(DTLS_TIMER_CHECK_INTERVAL)));
+                if timer.as_mut().poll(cx).is_ready() {
+                    // there may be lost datagrams, let the dtls layer retry
+                    self.timer = None;
+                    cx.waker().wake_by_ref();
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl AsyncWrite for UdpDatagramStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.socket.poll_send(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use g3_tls_cert::builder::{RootCertBuilder, TlsClientCertBuilder, TlsServerCertBuilder};
+    use g3_types::net::{Host, OpensslCertificatePair, OpensslClientConfigBuilder};
+    use openssl::ssl::{Ssl, SslContext, SslMethod, SslOptions, SslVerifyMode};
+    use openssl::x509::store::X509StoreBuilder;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::super::SimplexTransfer;
+    use crate::module::openssl::OpensslTlsClientArgs;
+    use crate::target::keyless::cloudflare::KeylessRequestBuilder;
+
+    fn pong_response(id: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut rsp = vec![0x01, 0x00, 0x00, 0x00];
+        rsp.extend_from_slice(id);
+        rsp.extend_from_slice(&[0x11, 0x00, 0x01, 0xF2]);
+        rsp.extend_from_slice(&[0x12, 0x00, payload.len() as u8]);
+        rsp.extend_from_slice(payload);
+        let len = rsp.len() - 8;
+        rsp[2] = (len >> 8) as u8;
+        rsp[3] = len as u8;
+        rsp
+    }
+
+    #[tokio::test]
+    async fn dtls_round_trip() {
+        let ca_builder = RootCertBuilder::new_ec256().unwrap();
+        let ca_cert = ca_builder.build(None).unwrap();
+        let host = Host::Domain("keyless.example.net".to_string());
+        let server_builder = TlsServerCertBuilder::new_ec256().unwrap();
+        let server_cert = server_builder
+            .build_fake(&host, &ca_cert, ca_builder.pkey(), None)
+            .unwrap();
+        let client_builder = TlsClientCertBuilder::new_ec256().unwrap();
+        let client_cert = client_builder
+            .build_for_host(&host, &ca_cert, ca_builder.pkey(), None)
+            .unwrap();
+
+        let mut tls = OpensslTlsClientArgs {
+            config: Some(OpensslClientConfigBuilder::default()),
+            ..Default::default()
+        };
+        let tls_config = tls.config.as_mut().unwrap();
+        tls_config.set_no_default_ca_certificates();
+        tls_config
+            .set_ca_certificates(vec![ca_cert.clone()])
+            .unwrap();
+        let mut cert_pair = OpensslCertificatePair::default();
+        cert_pair.set_certificates(vec![client_cert]).unwrap();
+        cert_pair
+            .set_private_key(client_builder.pkey().clone())
+            .unwrap();
+        tls_config.set_cert_pair(cert_pair);
+        let dtls_client = tls.build_dtls_client().unwrap();
+
+        // the server requires the client certificate
+        let mut ctx_builder = SslContext::builder(SslMethod::dtls()).unwrap();
+        ctx_builder.set_options(SslOptions::NO_QUERY_MTU);
+        ctx_builder.set_certificate(&server_cert).unwrap();
+        ctx_builder.set_private_key(server_builder.pkey()).unwrap();
+        let mut store_builder = X509StoreBuilder::new().unwrap();
+        store_builder.add_cert(ca_cert).unwrap();
+        ctx_builder
+            .set_verify_cert_store(store_builder.build())
+            .unwrap();
+        ctx_builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        let server_ctx = ctx_builder.build();
+
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server_socket.local_addr().unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+        server_socket.connect(client_addr).await.unwrap();
+        client_socket.connect(server_addr).await.unwrap();
+
+        let server = tokio::spawn(async move {
+            let mut ssl = Ssl::new(&server_ctx).unwrap();
+            ssl.set_mtu(1200).unwrap();
+            let acceptor =
+                g3_openssl::SslAcceptor::new(ssl, UdpDatagramStream::new(server_socket)).unwrap();
+            let mut stream = acceptor.accept().await.unwrap();
+            assert!(stream.ssl().peer_certificate().is_some());
+
+            // drop the first request, so the client should resend it
+            let mut first = [0u8; 2048];
+            let first_len = stream.read(&mut first).await.unwrap();
+            let mut buf = [0u8; 2048];
+            let len = stream.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..len], &first[..first_len]);
+
+            // a late response for a previous request should be skipped
+            stream
+                .write_all(&pong_response(&[0xFF, 0xFF, 0xFF, 0xFF], b"stale"))
+                .await
+                .unwrap();
+            stream
+                .write_all(&pong_response(&buf[4..8], b"pong"))
+                .await
+                .unwrap();
+        });
+
+        let mut ssl = dtls_client.build_ssl(&host, server_addr.port()).unwrap();
+        ssl.set_mtu(1200).unwrap();
+        let connector =
+            g3_openssl::SslConnector::new(ssl, UdpDatagramStream::new(client_socket)).unwrap();
+        let stream = connector.connect().await.unwrap();
+        let (r, w) = tokio::io::split(stream);
+        let mut transfer = SimplexTransfer::new(r, w, client_addr, server_addr, 2048);
+        transfer.set_retransmit_interval(Duration::from_millis(100));
+
+        let mut request = KeylessRequestBuilder::new_ping().build(b"ping").unwrap();
+        let rsp = tokio::time::timeout(Duration::from_secs(5), transfer.send_request(&mut request))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rsp.data(), b"pong");
+        server.await.unwrap();
+    }
+}
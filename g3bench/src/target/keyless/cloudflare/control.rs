@@ -0,0 +1,195 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use hdrhistogram::Histogram;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use g3_types::ext::DurationExt;
+
+use super::{KeylessCloudflareArgs, KeylessRequest, ProcArgs, SimplexTransfer};
+
+/// send ping requests to the control target in the background during the bench,
+/// the latency of which has the same network cost as the target but no crypto cost
+pub(super) struct KeylessControlProbe {
+    histogram: Arc<Mutex<Histogram<u64>>>,
+    failed: Arc<AtomicU64>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl KeylessControlProbe {
+    pub(super) fn spawn(
+        proc_args: &Arc<ProcArgs>,
+        cf_args: &Arc<KeylessCloudflareArgs>,
+    ) -> anyhow::Result<Self> {
+        let request = cf_args.new_ping_builder().build(&cf_args.global.payload)?;
+        let histogram = Arc::new(Mutex::new(Histogram::<u64>::new(3).unwrap()));
+        let failed = Arc::new(AtomicU64::new(0));
+
+        let handle = tokio::spawn(run_ping_loop(
+            Arc::clone(proc_args),
+            Arc::clone(cf_args),
+            request,
+            Arc::clone(&histogram),
+            Arc::clone(&failed),
+        ));
+        Ok(KeylessControlProbe {
+            histogram,
+            failed,
+            handle: Some(handle),
+        })
+    }
+
+    pub(super) fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+    }
+
+    pub(super) fn latency(&self) -> Histogram<u64> {
+        self.histogram.lock().unwrap().clone()
+    }
+
+    pub(super) fn summary(&self, target: &Histogram<u64>) {
+        let control = self.latency();
+
+        println!("# Control");
+        println!(
+            "{:<8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "", "Count", "Mean", "pct50", "pct90", "pct99"
+        );
+        let target_row = LatencyRow::new(target);
+        let control_row = LatencyRow::new(&control);
+        target_row.print("Target", target.len());
+        control_row.print("Control", control.len());
+        println!(
+            "{:<8} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "Delta",
+            "",
+            signed_delta(target_row.mean, control_row.mean),
+            signed_delta(target_row.pct50, control_row.pct50),
+            signed_delta(target_row.pct90, control_row.pct90),
+            signed_delta(target_row.pct99, control_row.pct99),
+        );
+        let failed = self.failed.load(Ordering::Relaxed);
+        if failed > 0 {
+            println!("Control Failed: {failed}");
+        }
+    }
+}
+
+impl Drop for KeylessControlProbe {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+struct LatencyRow {
+    mean: Duration,
+    pct50: Duration,
+    pct90: Duration,
+    pct99: Duration,
+}
+
+impl LatencyRow {
+    fn new(h: &Histogram<u64>) -> Self {
+        LatencyRow {
+            mean: Duration::from_secs_f64(h.mean() / 1_000_000_000.0),
+            pct50: Duration::from_nanos(h.value_at_quantile(0.50)),
+            pct90: Duration::from_nanos(h.value_at_quantile(0.90)),
+            pct99: Duration::from_nanos(h.value_at_quantile(0.99)),
+        }
+    }
+
+    fn print(&self, name: &str, count: u64) {
+        println!(
+            "{name:<8} {count:>10} {:>10.3?} {:>10.3?} {:>10.3?} {:>10.3?}",
+            self.mean, self.pct50, self.pct90, self.pct99
+        );
+    }
+}
+
+fn signed_delta(a: Duration, b: Duration) -> String {
+    if a >= b {
+        format!("+{:.3?}", a - b)
+    } else {
+        format!("-{:.3?}", b - a)
+    }
+}
+
+async fn run_ping_loop(
+    proc_args: Arc<ProcArgs>,
+    cf_args: Arc<KeylessCloudflareArgs>,
+    mut request: KeylessRequest,
+    histogram: Arc<Mutex<Histogram<u64>>>,
+    failed: Arc<AtomicU64>,
+) {
+    let mut connection: Option<SimplexTransfer> = None;
+    loop {
+        if let Some(c) = &mut connection {
+            if c.is_closed() {
+                connection = None;
+            }
+        }
+        let mut c = match connection.take() {
+            Some(c) => c,
+            None => match new_control_connection(&proc_args, &cf_args).await {
+                Ok(c) => c,
+                Err(e) => {
+                    failed.fetch_add(1, Ordering::Relaxed);
+                    if !proc_args.quiet {
+                        eprintln!("failed to connect to the control target: {e}");
+                    }
+                    tokio::time::sleep(cf_args.retry_interval()).await;
+                    continue;
+                }
+            },
+        };
+
+        let start = Instant::now();
+        match tokio::time::timeout(cf_args.timeout, c.send_request(&mut request)).await {
+            Ok(Ok(_)) => {
+                let _ = histogram
+                    .lock()
+                    .unwrap()
+                    .record(start.elapsed().as_nanos_u64());
+                connection = Some(c);
+            }
+            _ => {
+                failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+async fn new_control_connection(
+    proc_args: &ProcArgs,
+    cf_args: &KeylessCloudflareArgs,
+) -> anyhow::Result<SimplexTransfer> {
+    let peer = cf_args.select_control_addr(proc_args)?;
+    let use_tls = cf_args.select_tls();
+    tokio::time::timeout(
+        cf_args.new_connection_timeout(),
+        cf_args.new_simplex_keyless_connection(peer, use_tls, None),
+    )
+    .await
+    .map_err(|_| anyhow!("timed out to connect to {peer}"))?
+}
@@ -0,0 +1,430 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use bytes::BufMut;
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Padding;
+use openssl::sign::Signer;
+
+use super::KeylessLocalError;
+use crate::target::keyless::opts::{KeylessAction, KeylessRsaPadding, KeylessSignDigest};
+
+#[non_exhaustive]
+#[repr(u8)]
+#[derive(Clone, Copy)]
+pub(crate) enum KeylessOpCode {
+    // requests an RSA decrypted payload
+    RsaDecrypt = 0x01,
+    // requests an RSA signature on an MD5SHA1 hash payload
+    RsaSignMd5Sha1 = 0x02,
+    // requests an RSA signature on an SHA1 hash payload
+    RsaSignSha1 = 0x03,
+    // requests an RSA signature on an SHA224 hash payload
+    RsaSignSha224 = 0x04,
+    // requests an RSA signature on an SHA256 hash payload
+    RsaSignSha256 = 0x05,
+    // requests an RSA signature on an SHA384 hash payload
+    RsaSignSha384 = 0x06,
+    // requests an RSA signature on an SHA512 hash payload
+    RsaSignSha512 = 0x07,
+    // requests an ECDSA signature on an MD5SHA1 hash payload
+    EcdsaSignMd5sha1 = 0x12,
+    // requests an ECDSA signature on an SHA1 hash payload
+    EcdsaSignSha1 = 0x13,
+    // requests an ECDSA signature on an SHA224 hash payload
+    EcdsaSignSha224 = 0x14,
+    // requests an ECDSA signature on an SHA256 hash payload
+    EcdsaSignSha256 = 0x15,
+    // requests an ECDSA signature on an SHA384 hash payload
+    EcdsaSignSha384 = 0x16,
+    // requests an ECDSA signature on an SHA512 hash payload
+    EcdsaSignSha512 = 0x17,
+    // requests an Ed25519 signature on an arbitrary-length payload
+    Ed25519Sign = 0x18,
+    // asks to encrypt a blob (like a Session Ticket)
+    #[allow(unused)]
+    Seal = 0x21,
+    // asks to decrypt a blob encrypted by OpSeal
+    #[allow(unused)]
+    Unseal = 0x22,
+    // requests an RSASSA-PSS signature on an SHA256 hash payload
+    RsaPssSignSha256 = 0x35,
+    // requests an RSASSA-PSS signature on an SHA384 hash payload
+    RsaPssSignSha384 = 0x36,
+    // requests an RSASSA-PSS signature on an SHA512 hash payload
+    RsaPssSignSha512 = 0x37,
+    // requests to generate a new key pair and get the public key, this is an extension
+    GenerateKey = 0xC0,
+    // asks the server to echo back the payload, no key is needed
+    Ping = 0xF1,
+}
+
+impl TryFrom<KeylessAction> for KeylessOpCode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: KeylessAction) -> Result<Self, Self::Error> {
+        match value {
+            KeylessAction::RsaDecrypt(KeylessRsaPadding::Pkcs1)
+            | KeylessAction::RsaDecryptVerify(KeylessRsaPadding::Pkcs1) => {
+                Ok(KeylessOpCode::RsaDecrypt)
+            }
+            KeylessAction::RsaDecrypt(padding) | KeylessAction::RsaDecryptVerify(padding) => {
+                Err(anyhow!("unsupported rsa padding type {padding:?}"))
+            }
+            KeylessAction::RsaSign(KeylessSignDigest::Md5Sha1, KeylessRsaPadding::Pkcs1) => {
+                Ok(KeylessOpCode::RsaSignMd5Sha1)
+            }
+            KeylessAction::RsaSign(KeylessSignDigest::Sha1, KeylessRsaPadding::Pkcs1) => {
+                Ok(KeylessOpCode::RsaSignSha1)
+            }
+            KeylessAction::RsaSign(KeylessSignDigest::Sha224, KeylessRsaPadding::Pkcs1) => {
+                Ok(KeylessOpCode::RsaSignSha224)
+            }
+            KeylessAction::RsaSign(KeylessSignDigest::Sha256, KeylessRsaPadding::Pkcs1) => {
+                Ok(KeylessOpCode::RsaSignSha256)
+            }
+            KeylessAction::RsaSign(KeylessSignDigest::Sha256, KeylessRsaPadding::Pss) => {
+                Ok(KeylessOpCode::RsaPssSignSha256)
+            }
+            KeylessAction::RsaSign(KeylessSignDigest::Sha384, KeylessRsaPadding::Pkcs1) => {
+                Ok(KeylessOpCode::RsaSignSha384)
+            }
+            KeylessAction::RsaSign(KeylessSignDigest::Sha384, KeylessRsaPadding::Pss) => {
+                Ok(KeylessOpCode::RsaPssSignSha384)
+            }
+            KeylessAction::RsaSign(KeylessSignDigest::Sha512, KeylessRsaPadding::Pkcs1) => {
+                Ok(KeylessOpCode::RsaSignSha512)
+            }
+            KeylessAction::RsaSign(KeylessSignDigest::Sha512, KeylessRsaPadding::Pss) => {
+                Ok(KeylessOpCode::RsaPssSignSha512)
+            }
+            KeylessAction::RsaSign(digest, padding) => Err(anyhow!(
+                "unsupported rsa sign using digest {digest:?} padding {padding:?}"
+            )),
+            KeylessAction::EcdsaSign(KeylessSignDigest::Md5Sha1) => {
+                Ok(KeylessOpCode::EcdsaSignMd5sha1)
+            }
+            KeylessAction::EcdsaSign(KeylessSignDigest::Sha1) => Ok(KeylessOpCode::EcdsaSignSha1),
+            KeylessAction::EcdsaSign(KeylessSignDigest::Sha224) => {
+                Ok(KeylessOpCode::EcdsaSignSha224)
+            }
+            KeylessAction::EcdsaSign(KeylessSignDigest::Sha256) => {
+                Ok(KeylessOpCode::EcdsaSignSha256)
+            }
+            KeylessAction::EcdsaSign(KeylessSignDigest::Sha384) => {
+                Ok(KeylessOpCode::EcdsaSignSha384)
+            }
+            KeylessAction::EcdsaSign(KeylessSignDigest::Sha512) => {
+                Ok(KeylessOpCode::EcdsaSignSha512)
+            }
+            KeylessAction::Ed25519Sign => Ok(KeylessOpCode::Ed25519Sign),
+            KeylessAction::GenerateKey(_) => Ok(KeylessOpCode::GenerateKey),
+            _ => Err(anyhow!("unsupported action: {value:?}")),
+        }
+    }
+}
+
+/// hmac over the whole request frame with a shared secret, this is an extension.
+/// The frame is signed with the value of the hmac item filled with zero
+pub(crate) struct KeylessRequestHmac {
+    digest: MessageDigest,
+    key: PKey<Private>,
+}
+
+impl KeylessRequestHmac {
+    pub(crate) fn new(digest: MessageDigest, key: &[u8]) -> anyhow::Result<Self> {
+        let key = PKey::hmac(key).map_err(|e| anyhow!("invalid hmac key: {e}"))?;
+        Ok(KeylessRequestHmac { digest, key })
+    }
+
+    #[inline]
+    fn size(&self) -> usize {
+        self.digest.size()
+    }
+
+    fn sign(&self, data: &[u8]) -> Result<Vec<u8>, ErrorStack> {
+        let mut signer = Signer::new(self.digest, &self.key)?;
+        signer.sign_oneshot_to_vec(data)
+    }
+}
+
+pub(crate) struct KeylessRequestBuilder {
+    opcode: KeylessOpCode,
+    cert_ski: Vec<u8>,
+    proposed_rsa_padding: Option<KeylessRsaPadding>,
+    hmac: Option<Arc<KeylessRequestHmac>>,
+}
+
+impl KeylessRequestBuilder {
+    pub(crate) fn new(ski: &[u8], action: KeylessAction) -> anyhow::Result<Self> {
+        let opcode = KeylessOpCode::try_from(action)?;
+        Ok(KeylessRequestBuilder {
+            opcode,
+            cert_ski: ski.to_vec(),
+            proposed_rsa_padding: None,
+            hmac: None,
+        })
+    }
+
+    /// build ping requests, which has no cert ski set
+    pub(crate) fn new_ping() -> Self {
+        KeylessRequestBuilder {
+            opcode: KeylessOpCode::Ping,
+            cert_ski: Vec::new(),
+            proposed_rsa_padding: None,
+            hmac: None,
+        }
+    }
+
+    /// let the server choose the rsa padding, with the given one as the proposed default.
+    /// This is an extension, the padding is sent in item 0x40 with the 1 byte OpenSSL
+    /// padding value, e.g. 1 for PKCS1, 3 for NONE, 4 for OAEP, 5 for X931 and 6 for PSS
+    pub(crate) fn set_proposed_rsa_padding(&mut self, padding: KeylessRsaPadding) {
+        self.proposed_rsa_padding = Some(padding);
+    }
+
+    pub(crate) fn set_hmac(&mut self, hmac: Arc<KeylessRequestHmac>) {
+        self.hmac = Some(hmac);
+    }
+
+    pub(crate) fn build(&self, payload: &[u8]) -> anyhow::Result<KeylessRequest> {
+        let mut buf = Vec::with_capacity(super::MESSAGE_PADDED_LENGTH + 2);
+        // hdr and ID
+        buf.extend_from_slice(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        // SKI
+        if !self.cert_ski.is_empty() {
+            buf.push(0x04);
+            let ski_len = self.cert_ski.len();
+            buf.push(((ski_len >> 8) & 0xFF) as u8);
+            buf.push((ski_len & 0xFF) as u8);
+            buf.put_slice(self.cert_ski.as_slice());
+        }
+
+        // OpCode
+        buf.put_slice(&[0x11, 0x00, 0x01]);
+        buf.push(self.opcode as u8);
+
+        // RSA Padding Proposal
+        if let Some(padding) = self.proposed_rsa_padding {
+            buf.put_slice(&[0x40, 0x00, 0x01]);
+            buf.push(Padding::from(padding).as_raw() as u8);
+        }
+
+        // Payload
+        buf.push(0x12);
+        let payload_len = payload.len();
+        if payload_len > u16::MAX as usize {
+            return Err(anyhow!("payload length too long"));
+        }
+        buf.push(((payload_len >> 8) & 0xFF) as u8);
+        buf.push((payload_len & 0xFF) as u8);
+        buf.put_slice(&payload[0..payload_len]);
+
+        // Request HMAC
+        let hmac = self.hmac.as_ref().map(|hmac| {
+            let size = hmac.size();
+            buf.push(0x41);
+            buf.push(((size >> 8) & 0xFF) as u8);
+            buf.push((size & 0xFF) as u8);
+            let offset = buf.len();
+            buf.resize(offset + size, 0);
+            (Arc::clone(hmac), offset)
+        });
+
+        match super::MESSAGE_PADDED_LENGTH.checked_sub(buf.len()) {
+            Some(0) => {}
+            Some(1..=super::ITEM_HEADER_LENGTH) => buf.put_slice(&[0x20, 0x00, 0x00]),
+            Some(n) => {
+                let left = n - super::ITEM_HEADER_LENGTH;
+                buf.push(0x20);
+                buf.push(((left >> 8) & 0xFF) as u8);
+                buf.push((left & 0xFF) as u8);
+                buf.resize(super::MESSAGE_PADDED_LENGTH, 0);
+            }
+            None => {}
+        }
+
+        let len = buf.len() - super::MESSAGE_HEADER_LENGTH;
+        if len > u16::MAX as usize {
+            return Err(anyhow!("message length too long"));
+        }
+        buf[2] = ((len >> 8) & 0xFF) as u8;
+        buf[3] = (len & 0xFF) as u8;
+
+        let mut request = KeylessRequest {
+            buf,
+            id: 0,
+            opcode: self.opcode as u8,
+            hmac,
+        };
+        request.update_hmac()?;
+        Ok(request)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct KeylessRequest {
+    buf: Vec<u8>,
+    id: u32,
+    opcode: u8,
+    hmac: Option<(Arc<KeylessRequestHmac>, usize)>,
+}
+
+impl KeylessRequest {
+    /// rebuild the request from the bytes returned by `as_bytes`
+    pub(crate) fn from_bytes(buf: Vec<u8>) -> anyhow::Result<Self> {
+        if buf.len() < super::MESSAGE_HEADER_LENGTH {
+            return Err(anyhow!("the request message is too short"));
+        }
+        let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        if len + super::MESSAGE_HEADER_LENGTH != buf.len() {
+            return Err(anyhow!("the request message length not match"));
+        }
+        let id = u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
+        let opcode = find_opcode(&buf[super::MESSAGE_HEADER_LENGTH..])
+            .ok_or_else(|| anyhow!("no valid opcode item found in the request message"))?;
+        Ok(KeylessRequest {
+            buf,
+            id,
+            opcode,
+            hmac: None,
+        })
+    }
+
+    /// the opcode the server should use in the response to this request
+    pub(crate) fn response_opcode(&self) -> u8 {
+        if self.opcode == KeylessOpCode::Ping as u8 {
+            0xF2 // PONG
+        } else {
+            0xF0 // RESPONSE
+        }
+    }
+
+    /// set the request id, the hmac will be updated if set
+    pub(crate) fn set_id(&mut self, id: u32) -> Result<(), KeylessLocalError> {
+        let b = id.to_be_bytes();
+        self.buf[4] = b[0];
+        self.buf[5] = b[1];
+        self.buf[6] = b[2];
+        self.buf[7] = b[3];
+        self.id = id;
+        self.update_hmac()
+    }
+
+    fn update_hmac(&mut self) -> Result<(), KeylessLocalError> {
+        let Some((hmac, offset)) = &self.hmac else {
+            return Ok(());
+        };
+        let range = *offset..*offset + hmac.size();
+        self.buf[range.clone()].fill(0);
+        let mac = hmac
+            .sign(&self.buf)
+            .map_err(KeylessLocalError::RequestHmacFailed)?;
+        self.buf[range].copy_from_slice(&mac);
+        Ok(())
+    }
+
+    #[inline]
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        self.buf.as_slice()
+    }
+}
+
+fn find_opcode(mut items: &[u8]) -> Option<u8> {
+    while items.len() >= super::ITEM_HEADER_LENGTH {
+        let tag = items[0];
+        let len = u16::from_be_bytes([items[1], items[2]]) as usize;
+        let end = super::ITEM_HEADER_LENGTH + len;
+        if items.len() < end {
+            return None;
+        }
+        if tag == 0x11 && len == 1 {
+            return Some(items[3]);
+        }
+        items = &items[end..];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_hmac() {
+        let hmac = Arc::new(KeylessRequestHmac::new(MessageDigest::sha256(), b"secret").unwrap());
+        let mut builder = KeylessRequestBuilder::new_ping();
+        builder.set_hmac(hmac.clone());
+        let mut request = builder.build(b"hello").unwrap();
+        assert_eq!(
+            request.as_bytes().len(),
+            super::super::MESSAGE_PADDED_LENGTH
+        );
+
+        request.set_id(1).unwrap();
+        let (_, offset) = request.hmac.clone().unwrap();
+        let mac = request.as_bytes()[offset..offset + 32].to_vec();
+        let mut data = request.as_bytes().to_vec();
+        data[offset..offset + 32].fill(0);
+        assert_eq!(hmac.sign(&data).unwrap(), mac);
+
+        request.set_id(2).unwrap();
+        assert_ne!(&request.as_bytes()[offset..offset + 32], mac.as_slice());
+    }
+
+    #[test]
+    fn proposed_rsa_padding() {
+        let action = KeylessAction::RsaSign(KeylessSignDigest::Sha256, KeylessRsaPadding::Pss);
+        let mut builder = KeylessRequestBuilder::new(b"ski", action).unwrap();
+        let request = builder.build(b"hello").unwrap();
+        let items = &request.as_bytes()[super::super::MESSAGE_HEADER_LENGTH..];
+        assert_eq!(&items[6..10], &[0x11, 0x00, 0x01, request.opcode]);
+        assert_eq!(&items[10..13], &[0x12, 0x00, 0x05]);
+
+        builder.set_proposed_rsa_padding(KeylessRsaPadding::Pss);
+        let request = builder.build(b"hello").unwrap();
+        let items = &request.as_bytes()[super::super::MESSAGE_HEADER_LENGTH..];
+        assert_eq!(&items[..6], &[0x04, 0x00, 0x03, b's', b'k', b'i']);
+        assert_eq!(&items[6..10], &[0x11, 0x00, 0x01, request.opcode]);
+        assert_eq!(&items[10..14], &[0x40, 0x00, 0x01, 0x06]);
+        assert_eq!(&items[14..17], &[0x12, 0x00, 0x05]);
+        assert_eq!(&items[17..22], b"hello");
+    }
+
+    #[test]
+    fn response_opcode() {
+        let ping = KeylessRequestBuilder::new_ping().build(b"hello").unwrap();
+        assert_eq!(ping.response_opcode(), 0xF2);
+        let ping = KeylessRequest::from_bytes(ping.as_bytes().to_vec()).unwrap();
+        assert_eq!(ping.response_opcode(), 0xF2);
+
+        let builder = KeylessRequestBuilder::new(b"ski", KeylessAction::Ed25519Sign).unwrap();
+        let sign = builder.build(b"hello").unwrap();
+        assert_eq!(sign.response_opcode(), 0xF0);
+        let sign = KeylessRequest::from_bytes(sign.as_bytes().to_vec()).unwrap();
+        assert_eq!(sign.response_opcode(), 0xF0);
+    }
+}
@@ -0,0 +1,355 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io;
+
+use openssl::error::ErrorStack;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use g3_types::net::{T1L2BVParse, TlvParse};
+
+use crate::target::keyless::opts::KeylessRsaPadding;
+
+#[derive(Clone, Copy, Debug, Error)]
+pub(crate) enum KeylessServerError {
+    #[error("cryptography error")]
+    CryptographyFailure,
+    #[error("key not found due to no matching SKI/SNI/ServerIP")]
+    KeyNotFound,
+    #[error("I/O read failure")]
+    ReadError,
+    #[error("version mismatch")]
+    VersionMismatch,
+    #[error("bad opcode")]
+    BadOpCode,
+    #[error("unexpected opcode")]
+    UnexpectedOpCode,
+    #[error("malformed message")]
+    FormatError,
+    #[error("internal error")]
+    InternalError,
+    #[error("certificate not found")]
+    CertNotFound,
+    #[error("sealing key expired")]
+    Expired,
+    #[error("request authentication failed")]
+    Unauthorized,
+}
+
+impl KeylessServerError {
+    pub(crate) fn code(&self) -> u8 {
+        match self {
+            KeylessServerError::CryptographyFailure => 0x01,
+            KeylessServerError::KeyNotFound => 0x02,
+            KeylessServerError::ReadError => 0x03,
+            KeylessServerError::VersionMismatch => 0x04,
+            KeylessServerError::BadOpCode => 0x05,
+            KeylessServerError::UnexpectedOpCode => 0x06,
+            KeylessServerError::FormatError => 0x07,
+            KeylessServerError::InternalError => 0x08,
+            KeylessServerError::CertNotFound => 0x09,
+            KeylessServerError::Expired => 0x0A,
+            KeylessServerError::Unauthorized => 0x0B,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0x01 => Some(KeylessServerError::CryptographyFailure),
+            0x02 => Some(KeylessServerError::KeyNotFound),
+            0x03 => Some(KeylessServerError::ReadError),
+            0x04 => Some(KeylessServerError::VersionMismatch),
+            0x05 => Some(KeylessServerError::BadOpCode),
+            0x06 => Some(KeylessServerError::UnexpectedOpCode),
+            0x07 => Some(KeylessServerError::FormatError),
+            0x08 => Some(KeylessServerError::InternalError),
+            0x09 => Some(KeylessServerError::CertNotFound),
+            0x0A => Some(KeylessServerError::Expired),
+            0x0B => Some(KeylessServerError::Unauthorized),
+            _ => None,
+        }
+    }
+
+    /// the error name used in the cloudflare gokeyless server
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            KeylessServerError::CryptographyFailure => "CRYPTO_FAILED",
+            KeylessServerError::KeyNotFound => "KEY_NOT_FOUND",
+            KeylessServerError::ReadError => "READ",
+            KeylessServerError::VersionMismatch => "VERSION_MISMATCH",
+            KeylessServerError::BadOpCode => "BAD_OPCODE",
+            KeylessServerError::UnexpectedOpCode => "UNEXPECTED_OPCODE",
+            KeylessServerError::FormatError => "FORMAT",
+            KeylessServerError::InternalError => "INTERNAL",
+            KeylessServerError::CertNotFound => "CERT_NOT_FOUND",
+            KeylessServerError::Expired => "EXPIRED",
+            KeylessServerError::Unauthorized => "UNAUTHORIZED",
+        }
+    }
+}
+
+impl From<u8> for KeylessResponseError {
+    fn from(value: u8) -> Self {
+        match KeylessServerError::from_code(value) {
+            Some(e) => e.into(),
+            None => KeylessLocalError::UnsupportedServerErrorCode(value).into(),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum KeylessLocalError {
+    #[error("invalid message length")]
+    InvalidMessageLength,
+    #[error("message length {0} exceeds the max response size")]
+    MessageTooLarge(usize),
+    #[error("unexpected version {0}.{1}")]
+    UnexpectedVersion(u8, u8),
+    #[error("read failed: {0:?}")]
+    ReadFailed(io::Error),
+    #[error("write failed: {0:?}")]
+    WriteFailed(io::Error),
+    #[error("not enough data for a valid item")]
+    NotEnoughData,
+    #[error("invalid length for item {0}")]
+    InvalidItemLength(u8),
+    #[error("invalid item tag {0}")]
+    InvalidItemTag(u8),
+    #[error("invalid opcode {0}")]
+    InvalidOpCode(u8),
+    #[error("invalid rsa padding {0}")]
+    InvalidRsaPadding(u8),
+    #[error("unsupported server error code {0}")]
+    UnsupportedServerErrorCode(u8),
+    #[error("response opcode {1:#04x} mismatch, expected {0:#04x}")]
+    OpCodeMismatch(u8, u8),
+    #[error("failed to compute the request hmac: {0}")]
+    RequestHmacFailed(ErrorStack),
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum KeylessResponseError {
+    #[error("server error: {0}")]
+    ServerError(#[from] KeylessServerError),
+    #[error("local error: {0}")]
+    LocalError(#[from] KeylessLocalError),
+}
+
+struct KeylessResponseTlvParser<'a> {
+    opcode: u8,
+    payload: &'a [u8],
+    rsa_padding: Option<KeylessRsaPadding>,
+}
+
+impl<'a> T1L2BVParse<'a> for KeylessResponseTlvParser<'a> {
+    type Error = KeylessResponseError;
+
+    fn no_enough_data() -> Self::Error {
+        KeylessLocalError::NotEnoughData.into()
+    }
+
+    fn parse_value(&mut self, tag: u8, v: &'a [u8]) -> Result<(), Self::Error> {
+        match tag {
+            // OPCODE
+            0x11 => {
+                if v.len() != 1 {
+                    return Err(KeylessLocalError::InvalidItemLength(tag).into());
+                }
+                self.opcode = v[0];
+            }
+            // PAYLOAD
+            0x12 => self.payload = v,
+            // RSA Padding Chosen by Server, this is an extension.
+            // It's the 1 byte OpenSSL padding value, the same as in the request
+            0x40 => {
+                if v.len() != 1 {
+                    return Err(KeylessLocalError::InvalidItemLength(tag).into());
+                }
+                let padding = KeylessRsaPadding::from_raw(v[0] as i32)
+                    .ok_or(KeylessLocalError::InvalidRsaPadding(v[0]))?;
+                self.rsa_padding = Some(padding);
+            }
+            // PADDING
+            0x20 => {}
+            _ => return Err(KeylessLocalError::InvalidItemTag(tag).into()),
+        }
+        Ok(())
+    }
+}
+
+impl<'a> KeylessResponseTlvParser<'a> {
+    fn new() -> Self {
+        KeylessResponseTlvParser {
+            opcode: 0,
+            payload: &[],
+            rsa_padding: None,
+        }
+    }
+
+    fn parse_buf(&mut self, buf: &'a [u8]) -> Result<Vec<u8>, KeylessResponseError> {
+        self.parse_tlv(buf)?;
+        match self.opcode {
+            // RESPONSE or PONG
+            0xF0 | 0xF2 => Ok(self.payload.to_vec()),
+            0xFF => {
+                if self.payload.len() != 1 {
+                    return Err(KeylessLocalError::InvalidItemLength(0x12).into());
+                }
+                Err(KeylessResponseError::from(self.payload[0]))
+            }
+            _ => Err(KeylessLocalError::InvalidOpCode(self.opcode).into()),
+        }
+    }
+}
+
+pub(crate) struct KeylessResponse {
+    id: u32,
+    opcode: u8,
+    data: Vec<u8>,
+    rsa_padding: Option<KeylessRsaPadding>,
+}
+
+impl KeylessResponse {
+    #[inline]
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// the rsa padding chosen by the server, if it's reported
+    #[inline]
+    pub(crate) fn rsa_padding(&self) -> Option<KeylessRsaPadding> {
+        self.rsa_padding
+    }
+
+    /// check if the response opcode is the one expected by the request
+    pub(crate) fn check_opcode(&self, expected: u8) -> Result<(), KeylessLocalError> {
+        if self.opcode == expected {
+            Ok(())
+        } else {
+            Err(KeylessLocalError::OpCodeMismatch(expected, self.opcode))
+        }
+    }
+
+    #[inline]
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub(crate) fn into_vec(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub(crate) async fn read<R>(
+        reader: &mut R,
+        buf: &mut Vec<u8>,
+        max_size: usize,
+    ) -> Result<Self, KeylessResponseError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut hdr_buf = [0u8; 8];
+        let len = reader
+            .read_exact(&mut hdr_buf)
+            .await
+            .map_err(KeylessLocalError::ReadFailed)?;
+        if len < 4 {
+            return Err(KeylessLocalError::InvalidMessageLength.into());
+        }
+
+        let major = hdr_buf[0];
+        let minor = hdr_buf[1];
+        if major != 1 || minor != 0 {
+            return Err(KeylessLocalError::UnexpectedVersion(major, minor).into());
+        }
+
+        let len = ((hdr_buf[2] as usize) << 8) + hdr_buf[3] as usize;
+        if len > max_size {
+            return Err(KeylessLocalError::MessageTooLarge(len).into());
+        }
+        buf.clear();
+        buf.resize(len, 0);
+        let nr = reader
+            .read_exact(buf)
+            .await
+            .map_err(KeylessLocalError::ReadFailed)?;
+        if nr < len {
+            return Err(KeylessLocalError::InvalidMessageLength.into());
+        }
+
+        let id = u32::from_be_bytes([hdr_buf[4], hdr_buf[5], hdr_buf[6], hdr_buf[7]]);
+        let mut parser = KeylessResponseTlvParser::new();
+        let data = parser.parse_buf(buf)?;
+
+        Ok(KeylessResponse {
+            id,
+            opcode: parser.opcode,
+            data,
+            rsa_padding: parser.rsa_padding,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_bytes(items: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0x01, 0x00, 0x00, items.len() as u8, 0x00, 0x00, 0x00, 0x01];
+        buf.extend_from_slice(items);
+        buf
+    }
+
+    #[tokio::test]
+    async fn rsa_padding() {
+        let mut buf = Vec::new();
+
+        let data = response_bytes(&[0x11, 0x00, 0x01, 0xF0, 0x12, 0x00, 0x02, 0xAB, 0xCD]);
+        let rsp = KeylessResponse::read(&mut data.as_slice(), &mut buf, 1024)
+            .await
+            .unwrap();
+        assert_eq!(rsp.id(), 1);
+        assert_eq!(rsp.rsa_padding(), None);
+        assert_eq!(rsp.data(), &[0xAB, 0xCD]);
+
+        let data = response_bytes(&[
+            0x11, 0x00, 0x01, 0xF0, 0x40, 0x00, 0x01, 0x06, 0x12, 0x00, 0x01, 0xAB,
+        ]);
+        let rsp = KeylessResponse::read(&mut data.as_slice(), &mut buf, 1024)
+            .await
+            .unwrap();
+        assert_eq!(rsp.rsa_padding(), Some(KeylessRsaPadding::Pss));
+        assert_eq!(rsp.data(), &[0xAB]);
+
+        let data = response_bytes(&[0x11, 0x00, 0x01, 0xF0, 0x40, 0x00, 0x01, 0x02]);
+        let r = KeylessResponse::read(&mut data.as_slice(), &mut buf, 1024).await;
+        assert!(matches!(
+            r,
+            Err(KeylessResponseError::LocalError(
+                KeylessLocalError::InvalidRsaPadding(2)
+            ))
+        ));
+
+        let data = response_bytes(&[0x11, 0x00, 0x01, 0xF0, 0x40, 0x00, 0x02, 0x01, 0x01]);
+        let r = KeylessResponse::read(&mut data.as_slice(), &mut buf, 1024).await;
+        assert!(matches!(
+            r,
+            Err(KeylessResponseError::LocalError(
+                KeylessLocalError::InvalidItemLength(0x40)
+            ))
+        ));
+    }
+}
@@ -0,0 +1,482 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use clap::{ArgMatches, Command};
+use tokio::time::Instant;
+
+use super::{BenchError, BenchRuntimeStats, BenchTarget, BenchTaskContext, ProcArgs};
+use crate::module::otlp::OtlpExporter;
+
+mod opts;
+use opts::KeylessCloudflareArgs;
+
+mod adaptive;
+use adaptive::KeylessAdaptiveTimeout;
+
+mod artifact;
+use artifact::KeylessArtifact;
+
+mod cache;
+
+mod control;
+use control::KeylessControlProbe;
+
+mod peer_chain;
+use peer_chain::KeylessPeerChainDumper;
+
+mod record;
+use record::KeylessRecordWriter;
+
+mod request_csv;
+use request_csv::KeylessRequestCsvWriter;
+
+mod retry;
+use retry::KeylessRetryBudget;
+
+mod routing;
+
+mod session;
+use session::KeylessSessionCacheFile;
+
+mod sni;
+use sni::KeylessSniRotation;
+
+mod stats;
+use stats::{
+    KeylessErrorStatsMap, KeylessHistogram, KeylessHistogramRecorder, KeylessRuntimeStats,
+    KeylessTargetStatsMap,
+};
+
+mod task;
+use task::KeylessCloudflareTaskContext;
+
+mod message;
+use message::{
+    KeylessLocalError, KeylessRequest, KeylessRequestBuilder, KeylessRequestHmac, KeylessResponse,
+    KeylessResponseError, KeylessServerError,
+};
+
+mod connection;
+use connection::{MultiplexTransfer, SendRequestError, SimplexTransfer, UdpDatagramStream};
+
+mod pool;
+use pool::KeylessConnectionPool;
+
+pub(super) const COMMAND: &str = "cloudflare";
+
+struct KeylessCloudflareTarget {
+    args: Arc<KeylessCloudflareArgs>,
+    proc_args: Arc<ProcArgs>,
+    stats: Arc<KeylessRuntimeStats>,
+    histogram: Option<KeylessHistogram>,
+    histogram_recorder: KeylessHistogramRecorder,
+    pool: Option<Arc<KeylessConnectionPool>>,
+    control: Option<KeylessControlProbe>,
+    otlp: Option<OtlpExporter>,
+}
+
+impl BenchTarget<KeylessRuntimeStats, KeylessHistogram, KeylessCloudflareTaskContext>
+    for KeylessCloudflareTarget
+{
+    fn new_context(&self) -> anyhow::Result<KeylessCloudflareTaskContext> {
+        KeylessCloudflareTaskContext::new(
+            &self.args,
+            &self.proc_args,
+            &self.stats,
+            self.histogram_recorder.clone(),
+            self.pool.clone(),
+            self.otlp.as_ref().and_then(|e| e.sender()),
+        )
+    }
+
+    fn fetch_runtime_stats(&self) -> Arc<KeylessRuntimeStats> {
+        self.stats.clone()
+    }
+
+    fn take_histogram(&mut self) -> Option<KeylessHistogram> {
+        self.histogram.take()
+    }
+
+    fn notify_finish(&mut self) {
+        self.pool = None;
+        if let Some(control) = &mut self.control {
+            control.stop();
+        }
+        if let Some(mut otlp) = self.otlp.take() {
+            otlp.shutdown();
+        }
+    }
+
+    fn save_result(
+        &self,
+        total_time: Duration,
+        histogram: Option<&KeylessHistogram>,
+    ) -> anyhow::Result<()> {
+        if let Some(path) = &self.args.hdr_output {
+            if let Some(h) = histogram {
+                artifact::save_hdr_histogram(path, h.total_time())?;
+            }
+        }
+        if let (Some(control), Some(h)) = (&self.control, histogram) {
+            if !self.proc_args.quiet {
+                println!();
+                control.summary(h.total_time());
+            }
+        }
+        if self.args.artifact_dir.is_none() && self.args.baseline.is_none() {
+            return Ok(());
+        }
+
+        let global_state = crate::target::stats::global_state();
+        let passed = global_state.total_passed();
+        let summary = serde_json::json!({
+            "total_time_secs": total_time.as_secs_f64(),
+            "passed": passed,
+            "failed": global_state.total_failed(),
+            "requests_per_second": passed as f64 / total_time.as_secs_f64(),
+            "stats": self.stats.to_json(),
+            "latency": histogram.map(|h| artifact::latency_json(h.total_time())),
+            "control_latency": self.control.as_ref().map(|c| artifact::latency_json(&c.latency())),
+        });
+
+        if let Some(baseline) = &self.args.baseline {
+            if !self.proc_args.quiet {
+                println!();
+                artifact::compare_with_baseline(baseline, &summary);
+            }
+        }
+
+        if let Some(dir) = &self.args.artifact_dir {
+            let mut plan = Vec::new();
+            self.args
+                .write_plan(&self.proc_args, &mut plan)
+                .map_err(|e| anyhow!("failed to write bench plan: {e}"))?;
+            let artifact = KeylessArtifact {
+                plan,
+                summary,
+                latency: histogram.map(|h| h.total_time().clone()),
+                errors: self.stats.errors_to_json(),
+            };
+            artifact.save(dir)?;
+        }
+        Ok(())
+    }
+}
+
+pub(super) fn merge_artifacts(dirs: &[PathBuf]) -> anyhow::Result<()> {
+    artifact::merge_artifacts(dirs)
+}
+
+pub(super) fn command() -> Command {
+    opts::add_cloudflare_args(
+        Command::new(COMMAND).about("Use keyless server that speaks cloudflare protocol"),
+    )
+}
+
+pub(super) async fn run(proc_args: &Arc<ProcArgs>, cmd_args: &ArgMatches) -> anyhow::Result<()> {
+    let mut cf_args = opts::parse_cloudflare_args(cmd_args)?;
+    cf_args.resolve_target_address(proc_args).await?;
+
+    let pool_proc_args;
+    let proc_args = match cf_args.pool_concurrency(proc_args) {
+        Some(concurrency) => {
+            let mut args = ProcArgs::clone(proc_args);
+            args.concurrency = concurrency;
+            if !args.quiet {
+                cf_args.print_pool_concurrency(concurrency);
+            }
+            pool_proc_args = Arc::new(args);
+            &pool_proc_args
+        }
+        None => proc_args,
+    };
+    let max_concurrency = cf_args
+        .phases
+        .iter()
+        .map(|phase| phase.concurrency)
+        .max()
+        .unwrap_or(proc_args.concurrency);
+    cf_args.global.set_output_file_suffix(max_concurrency);
+
+    if cf_args.explain {
+        cf_args.explain(proc_args);
+        if !cf_args.confirm || !confirm_to_continue()? {
+            return Ok(());
+        }
+    }
+
+    if cf_args.probe {
+        let code = match run_probe(proc_args, Arc::new(cf_args)).await {
+            Ok(code) => code,
+            Err(e) => {
+                println!("KEYLESS UNKNOWN - {e}");
+                3
+            }
+        };
+        std::process::exit(code);
+    }
+
+    if let Some(count) = cf_args.check_key_routing {
+        return routing::run_key_routing_check(proc_args, Arc::new(cf_args), count).await;
+    }
+
+    if let Some(path) = cf_args.replay_file.clone() {
+        let keep_timing = cf_args.replay_timing;
+        return record::run_replay(proc_args, Arc::new(cf_args), &path, keep_timing).await;
+    }
+
+    if let Some(count) = cf_args.cold_warm {
+        return cache::run_cache_check(proc_args, Arc::new(cf_args), count).await;
+    }
+
+    if !cf_args.phases.is_empty() {
+        return run_phases(proc_args, Arc::new(cf_args)).await;
+    }
+
+    if let Some(budget) = &mut cf_args.retry_budget {
+        budget.set_total_requests(proc_args.requests);
+    }
+    let otlp = cf_args.otlp.spawn_exporter()?;
+    let cf_args = Arc::new(cf_args);
+
+    let runtime_stats = Arc::new(KeylessRuntimeStats::default());
+    let (histogram, histogram_recorder) = KeylessHistogram::new();
+
+    let pool = cf_args.pool_size.map(|s| {
+        Arc::new(KeylessConnectionPool::new(
+            &cf_args,
+            proc_args,
+            s,
+            &runtime_stats,
+            &histogram_recorder,
+        ))
+    });
+    if cf_args.pool_warmup {
+        if let Some(pool) = &pool {
+            pool.warmup(&cf_args).await?;
+        }
+    }
+    cf_args.wait_to_start(proc_args.quiet).await;
+
+    let control = if cf_args.has_control_target() {
+        Some(KeylessControlProbe::spawn(proc_args, &cf_args)?)
+    } else {
+        None
+    };
+
+    let target = KeylessCloudflareTarget {
+        args: cf_args.clone(),
+        proc_args: Arc::clone(proc_args),
+        stats: runtime_stats,
+        histogram: Some(histogram),
+        histogram_recorder,
+        pool,
+        control,
+        otlp,
+    };
+
+    let r = crate::target::run(target, proc_args).await;
+    cf_args.global.print_ordered_dump();
+    let w = cf_args.global.write_output_files();
+    if let Some(recorder) = &cf_args.recorder {
+        recorder.flush()?;
+    }
+    if let Some(writer) = &cf_args.request_csv {
+        writer.flush()?;
+    }
+    if let Some(budget) = &cf_args.retry_budget {
+        if !proc_args.quiet {
+            println!();
+            budget.summary();
+        }
+    }
+    if let Some(rotation) = &cf_args.sni_rotation {
+        if !proc_args.quiet {
+            println!();
+            rotation.summary();
+        }
+    }
+    cf_args.save_session_cache(proc_args.quiet);
+    r?;
+    w?;
+    cf_args.global.check_failed_tasks()
+}
+
+fn confirm_to_continue() -> anyhow::Result<bool> {
+    print!("Continue to run? [y/N] ");
+    std::io::stdout()
+        .flush()
+        .map_err(|e| anyhow!("failed to flush stdout: {e}"))?;
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| anyhow!("failed to read from stdin: {e}"))?;
+    Ok(matches!(line.trim(), "y" | "Y" | "yes"))
+}
+
+async fn run_phases(
+    proc_args: &Arc<ProcArgs>,
+    cf_args: Arc<KeylessCloudflareArgs>,
+) -> anyhow::Result<()> {
+    // the pool will be shared by all phases, so use standalone stats for it
+    let pool_stats = Arc::new(KeylessRuntimeStats::default());
+    let (_pool_histogram, pool_histogram_recorder) = KeylessHistogram::new();
+    let pool = cf_args.pool_size.map(|s| {
+        Arc::new(KeylessConnectionPool::new(
+            &cf_args,
+            proc_args,
+            s,
+            &pool_stats,
+            &pool_histogram_recorder,
+        ))
+    });
+    if cf_args.pool_warmup {
+        if let Some(pool) = &pool {
+            pool.warmup(&cf_args).await?;
+        }
+    }
+    cf_args.wait_to_start(proc_args.quiet).await;
+
+    let mut total_time = Duration::ZERO;
+    let mut total_passed = 0;
+    let mut total_failed = 0;
+    for (i, phase) in cf_args.phases.iter().enumerate() {
+        let mut phase_args = ProcArgs::clone(proc_args);
+        phase_args.concurrency = phase.concurrency;
+        phase_args.requests = None;
+        phase_args.time_limit = Some(phase.duration);
+        if !phase_args.quiet {
+            println!(
+                "### Phase {i}: concurrency {}, duration {:?}",
+                phase.concurrency, phase.duration
+            );
+        }
+
+        let runtime_stats = Arc::new(KeylessRuntimeStats::default());
+        let (histogram, histogram_recorder) = KeylessHistogram::new();
+        let target = KeylessCloudflareTarget {
+            args: cf_args.clone(),
+            proc_args: Arc::clone(proc_args),
+            stats: runtime_stats,
+            histogram: Some(histogram),
+            histogram_recorder,
+            pool: pool.clone(),
+            control: None,
+            otlp: cf_args.otlp.spawn_exporter()?,
+        };
+
+        let time_start = Instant::now();
+        crate::target::run(target, &phase_args).await?;
+        cf_args.global.print_ordered_dump();
+        cf_args.global.write_output_files()?;
+        total_time += time_start.elapsed();
+
+        let global_state = crate::target::stats::global_state();
+        total_passed += global_state.total_passed();
+        total_failed += global_state.total_failed();
+        if crate::target::stats::interrupted() {
+            break;
+        }
+    }
+
+    if !proc_args.quiet {
+        println!("### Overall");
+        println!("Time taken for tests: {total_time:?}");
+        println!("Complete requests:    {total_passed}");
+        if total_failed > 0 {
+            println!("Failed requests:      {total_failed}");
+        }
+        println!(
+            "Requests per second:  {:.3} [#/sec] (mean)",
+            total_passed as f64 / total_time.as_secs_f64()
+        );
+        if pool.is_some() {
+            println!();
+            println!("### Shared Pool");
+            pool_stats.summary(total_time);
+        }
+        if let Some(budget) = &cf_args.retry_budget {
+            println!();
+            budget.summary();
+        }
+        if let Some(rotation) = &cf_args.sni_rotation {
+            println!();
+            rotation.summary();
+        }
+    }
+    cf_args.save_session_cache(proc_args.quiet);
+    Ok(())
+}
+
+async fn run_probe(
+    proc_args: &Arc<ProcArgs>,
+    cf_args: Arc<KeylessCloudflareArgs>,
+) -> anyhow::Result<i32> {
+    let runtime_stats = Arc::new(KeylessRuntimeStats::default());
+    let (_histogram, histogram_recorder) = KeylessHistogram::new();
+    let mut context = KeylessCloudflareTaskContext::new(
+        &cf_args,
+        proc_args,
+        &runtime_stats,
+        histogram_recorder,
+        None,
+        None,
+    )?;
+
+    let time_start = Instant::now();
+    let r = context.run(0, time_start).await;
+    let latency = time_start.elapsed();
+
+    let (code, status, message) = match r {
+        Ok(_) => {
+            if cf_args.crit_latency.map(|d| latency > d).unwrap_or(false) {
+                (
+                    2,
+                    "CRITICAL",
+                    format!("request passed in {latency:?}, too slow"),
+                )
+            } else if cf_args.warn_latency.map(|d| latency > d).unwrap_or(false) {
+                (
+                    1,
+                    "WARNING",
+                    format!("request passed in {latency:?}, too slow"),
+                )
+            } else {
+                (0, "OK", format!("request passed in {latency:?}"))
+            }
+        }
+        Err(BenchError::Fatal(e)) | Err(BenchError::Task(e)) => {
+            (2, "CRITICAL", format!("request failed: {e}"))
+        }
+    };
+    let threshold = |d: Option<Duration>| {
+        d.map(|d| format!("{:.6}", d.as_secs_f64()))
+            .unwrap_or_default()
+    };
+    println!(
+        "KEYLESS {status} - {message} | time={:.6}s;{};{};0;",
+        latency.as_secs_f64(),
+        threshold(cf_args.warn_latency),
+        threshold(cf_args.crit_latency),
+    );
+    Ok(code)
+}
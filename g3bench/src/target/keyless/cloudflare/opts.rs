@@ -0,0 +1,1897 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, Context};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command, ValueHint};
+use openssl::hash::MessageDigest;
+use openssl::ssl::SslVerifyMode;
+use serde_json::Value;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+
+use g3_openssl::SslStream;
+use g3_types::collection::{SelectiveVec, WeightedValue};
+use g3_types::net::{
+    Host, OpensslClientConfig, OpensslClientConfigBuilder, PortRange, UpstreamAddr,
+};
+
+use super::{
+    KeylessAdaptiveTimeout, KeylessPeerChainDumper, KeylessRecordWriter, KeylessRequest,
+    KeylessRequestBuilder, KeylessRequestCsvWriter, KeylessRequestHmac, KeylessRetryBudget,
+    KeylessServerError, KeylessSessionCacheFile, KeylessSniRotation, MultiplexTransfer,
+    SimplexTransfer, UdpDatagramStream,
+};
+use crate::module::openssl::{AppendOpensslArgs, OpensslTlsClientArgs};
+use crate::module::otlp::{AppendOtlpArgs, OtlpArgs, OtlpTrace};
+use crate::module::proxy_protocol::{AppendProxyProtocolArgs, ProxyProtocolArgs};
+use crate::opts::ProcArgs;
+use crate::target::keyless::opts::{KeylessAction, KeylessRsaPssSaltlen};
+use crate::target::keyless::{AppendKeylessArgs, KeylessGlobalArgs};
+
+const ARG_CONNECTION_POOL: &str = "connection-pool";
+const ARG_POOL_WARMUP: &str = "pool-warmup";
+const ARG_POOL_INFLIGHT: &str = "pool-inflight";
+const ARG_TARGET: &str = "target";
+const ARG_CONTROL_TARGET: &str = "control-target";
+const ARG_NO_TLS: &str = "no-tls";
+const ARG_SNI_LIST: &str = "sni-list";
+const ARG_SESSION_CACHE_FILE: &str = "session-cache-file";
+const ARG_TLS_RATIO: &str = "tls-ratio";
+const ARG_UDP: &str = "udp";
+const ARG_UDP_RETRANSMIT: &str = "udp-retransmit";
+const ARG_LOCAL_ADDRESS: &str = "local-address";
+const ARG_LOCAL_PORT_RANGE: &str = "local-port-range";
+const ARG_REUSE_ADDR: &str = "reuse-addr";
+const ARG_CONNECT_TIMEOUT: &str = "connect-timeout";
+const ARG_TCP_NODELAY: &str = "tcp-nodelay";
+const ARG_CONNECT_RETRIES: &str = "connect-retries";
+const ARG_CONNECT_RETRY_INTERVAL: &str = "connect-retry-interval";
+const ARG_TIMEOUT: &str = "timeout";
+const ARG_ADAPTIVE_TIMEOUT: &str = "adaptive-timeout";
+const ARG_ADAPTIVE_TIMEOUT_WARMUP: &str = "adaptive-timeout-warmup";
+const ARG_ADAPTIVE_TIMEOUT_MIN: &str = "adaptive-timeout-min";
+const ARG_MAX_RESPONSE_SIZE: &str = "max-response-size";
+const ARG_REQUEST_RETRIES: &str = "request-retries";
+const ARG_RETRY_ON_CODES: &str = "retry-on-codes";
+const ARG_RETRY_BUDGET: &str = "retry-budget";
+const ARG_NO_MULTIPLEX: &str = "no-multiplex";
+const ARG_VERIFY_OPCODE: &str = "verify-opcode";
+const ARG_SERVER_CHOOSE_RSA_PADDING: &str = "server-choose-rsa-padding";
+const ARG_REQUEST_HMAC_KEY_FILE: &str = "request-hmac-key-file";
+const ARG_REQUEST_HMAC_DIGEST: &str = "request-hmac-digest";
+const ARG_NO_PAYLOAD_REUSE: &str = "no-payload-reuse";
+const ARG_PHASE: &str = "phase";
+const ARG_LOCAL_TIMING: &str = "local-timing";
+const ARG_PROBE: &str = "probe";
+const ARG_CHECK_KEY_ROUTING: &str = "check-key-routing";
+const ARG_COLD_WARM: &str = "cold-warm";
+const ARG_WARN_LATENCY: &str = "warn-latency";
+const ARG_CRIT_LATENCY: &str = "crit-latency";
+const ARG_CONNECT_ONLY: &str = "connect-only";
+const ARG_EXPLAIN: &str = "explain";
+const ARG_CONFIRM: &str = "confirm";
+const ARG_ARTIFACT_DIR: &str = "artifact-dir";
+const ARG_START_AT: &str = "start-at";
+const ARG_HDR_OUTPUT: &str = "hdr-output";
+const ARG_DUMP_PEER_CHAIN: &str = "dump-peer-chain";
+const ARG_RECORD: &str = "record";
+const ARG_REQUEST_CSV: &str = "request-csv";
+const ARG_REPLAY_FILE: &str = "replay-file";
+const ARG_REPLAY_TIMING: &str = "replay-timing";
+const ARG_BASELINE_DIR: &str = "baseline-dir";
+
+/// keep each dtls record in a single datagram on the common paths
+const DTLS_MTU: u32 = 1200;
+const MAX_CONNECT_RETRIES: u32 = 100;
+
+#[derive(Clone, Copy)]
+pub(super) struct KeylessBenchPhase {
+    pub(super) concurrency: usize,
+    pub(super) duration: Duration,
+}
+
+impl FromStr for KeylessBenchPhase {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((c, d)) = s.split_once(':') else {
+            return Err(anyhow!("no ':' delimiter found in phase value {s}"));
+        };
+        let concurrency =
+            usize::from_str(c).map_err(|e| anyhow!("invalid concurrency value {c}: {e}"))?;
+        if concurrency == 0 {
+            return Err(anyhow!("concurrency of phase should not be 0"));
+        }
+        let (n, unit) = match d.find(|c: char| !c.is_ascii_digit()) {
+            Some(p) => d.split_at(p),
+            None => (d, "s"),
+        };
+        let n = u64::from_str(n).map_err(|e| anyhow!("invalid duration value {d}: {e}"))?;
+        let duration = match unit {
+            "ms" => Duration::from_millis(n),
+            "s" => Duration::from_secs(n),
+            "m" => Duration::from_secs(n * 60),
+            "h" => Duration::from_secs(n * 3600),
+            _ => return Err(anyhow!("unsupported duration unit in {d}")),
+        };
+        if duration.is_zero() {
+            return Err(anyhow!("duration of phase should not be 0"));
+        }
+        Ok(KeylessBenchPhase {
+            concurrency,
+            duration,
+        })
+    }
+}
+
+pub(super) struct KeylessCloudflareArgs {
+    pub(super) global: KeylessGlobalArgs,
+    pub(super) pool_size: Option<usize>,
+    pub(super) pool_warmup: bool,
+    pool_inflight: usize,
+    target: UpstreamAddr,
+    control_target: Option<UpstreamAddr>,
+    bind: Option<IpAddr>,
+    local_port_range: Option<PortRange>,
+    next_local_port: AtomicUsize,
+    reuse_addr: bool,
+    tcp_nodelay: bool,
+    pub(super) no_multiplex: bool,
+    pub(super) server_choose_rsa_padding: bool,
+    verify_opcode: bool,
+    request_hmac: Option<(&'static str, Arc<KeylessRequestHmac>)>,
+    pub(super) no_payload_reuse: bool,
+    pub(super) phases: Vec<KeylessBenchPhase>,
+    pub(super) local_timing: bool,
+    pub(super) probe: bool,
+    pub(super) check_key_routing: Option<usize>,
+    pub(super) cold_warm: Option<usize>,
+    pub(super) warn_latency: Option<Duration>,
+    pub(super) crit_latency: Option<Duration>,
+    pub(super) connect_only: bool,
+    pub(super) explain: bool,
+    pub(super) confirm: bool,
+    pub(super) artifact_dir: Option<PathBuf>,
+    start_at: Option<SystemTime>,
+    pub(super) hdr_output: Option<PathBuf>,
+    pub(super) recorder: Option<KeylessRecordWriter>,
+    pub(super) request_csv: Option<KeylessRequestCsvWriter>,
+    pub(super) replay_file: Option<PathBuf>,
+    pub(super) replay_timing: bool,
+    pub(super) baseline: Option<Value>,
+    pub(super) timeout: Duration,
+    adaptive_timeout: Option<KeylessAdaptiveTimeout>,
+    max_response_size: usize,
+    pub(super) connect_timeout: Duration,
+    connect_retries: u32,
+    connect_retry_interval: Duration,
+    pub(super) request_retries: usize,
+    retry_on_codes: Option<Vec<u8>>,
+    pub(super) retry_budget: Option<KeylessRetryBudget>,
+    pub(super) tls: OpensslTlsClientArgs,
+    tls_ratio: Option<f64>,
+    pub(super) sni_rotation: Option<KeylessSniRotation>,
+    session_cache_file: Option<KeylessSessionCacheFile>,
+    dtls_client: Option<OpensslClientConfig>,
+    udp_retransmit: Duration,
+    peer_chain_dumper: Option<KeylessPeerChainDumper>,
+    proxy_protocol: ProxyProtocolArgs,
+    pub(super) otlp: OtlpArgs,
+
+    target_addrs: Option<SelectiveVec<WeightedValue<SocketAddr>>>,
+    control_addrs: Option<SelectiveVec<WeightedValue<SocketAddr>>>,
+}
+
+impl KeylessCloudflareArgs {
+    fn new(global_args: KeylessGlobalArgs, target: UpstreamAddr, no_tls: bool) -> Self {
+        let tls = if no_tls {
+            OpensslTlsClientArgs::default()
+        } else {
+            OpensslTlsClientArgs {
+                config: Some(OpensslClientConfigBuilder::with_cache_for_one_site()),
+                ..Default::default()
+            }
+        };
+        KeylessCloudflareArgs {
+            global: global_args,
+            pool_size: None,
+            pool_warmup: false,
+            pool_inflight: 4,
+            target,
+            control_target: None,
+            bind: None,
+            local_port_range: None,
+            next_local_port: AtomicUsize::new(0),
+            reuse_addr: false,
+            tcp_nodelay: true,
+            no_multiplex: false,
+            server_choose_rsa_padding: false,
+            verify_opcode: false,
+            request_hmac: None,
+            no_payload_reuse: false,
+            phases: Vec::new(),
+            local_timing: false,
+            probe: false,
+            check_key_routing: None,
+            cold_warm: None,
+            warn_latency: None,
+            crit_latency: None,
+            connect_only: false,
+            explain: false,
+            confirm: false,
+            artifact_dir: None,
+            start_at: None,
+            hdr_output: None,
+            recorder: None,
+            request_csv: None,
+            replay_file: None,
+            replay_timing: false,
+            baseline: None,
+            timeout: Duration::from_secs(5),
+            adaptive_timeout: None,
+            max_response_size: 65536,
+            connect_timeout: Duration::from_secs(10),
+            connect_retries: 0,
+            connect_retry_interval: Duration::from_millis(100),
+            request_retries: 0,
+            retry_on_codes: None,
+            retry_budget: None,
+            tls,
+            tls_ratio: None,
+            sni_rotation: None,
+            session_cache_file: None,
+            dtls_client: None,
+            udp_retransmit: Duration::from_secs(1),
+            peer_chain_dumper: None,
+            proxy_protocol: ProxyProtocolArgs::default(),
+            otlp: OtlpArgs::default(),
+            target_addrs: None,
+            control_addrs: None,
+        }
+    }
+
+    pub(super) fn new_request_builder(&self, ski: &[u8]) -> anyhow::Result<KeylessRequestBuilder> {
+        let mut builder = KeylessRequestBuilder::new(ski, self.global.action)?;
+        if self.server_choose_rsa_padding {
+            if let Some(padding) = self.global.action.rsa_padding() {
+                builder.set_proposed_rsa_padding(padding);
+            }
+        }
+        if let Some((_, hmac)) = &self.request_hmac {
+            builder.set_hmac(Arc::clone(hmac));
+        }
+        Ok(builder)
+    }
+
+    pub(super) fn new_ping_builder(&self) -> KeylessRequestBuilder {
+        let mut builder = KeylessRequestBuilder::new_ping();
+        if let Some((_, hmac)) = &self.request_hmac {
+            builder.set_hmac(Arc::clone(hmac));
+        }
+        builder
+    }
+
+    /// build the requests for all the keys, or the single one if no multiple keys set
+    pub(super) fn build_requests(&self) -> anyhow::Result<Vec<KeylessRequest>> {
+        if self.global.multi_keys.is_empty() {
+            let builder = self.new_request_builder(self.global.subject_key_id())?;
+            return Ok(vec![builder.build(&self.global.payload)?]);
+        }
+        let mut requests = Vec::with_capacity(self.global.multi_keys.len());
+        for key in &self.global.multi_keys {
+            let builder = self.new_request_builder(&key.ski)?;
+            requests.push(builder.build(&self.global.payload)?);
+        }
+        Ok(requests)
+    }
+
+    /// the concurrency derived from the pool size and the in-flight factor,
+    /// which is used only if no concurrency is set explicitly and no phases set
+    pub(super) fn pool_concurrency(&self, proc_args: &ProcArgs) -> Option<usize> {
+        if proc_args.concurrency_set || !self.phases.is_empty() {
+            return None;
+        }
+        self.pool_size.map(|size| size * self.pool_inflight)
+    }
+
+    pub(super) fn print_pool_concurrency(&self, concurrency: usize) {
+        if let Some(size) = self.pool_size {
+            println!(
+                "Effective Concurrency: {concurrency}, pool size {size} x {} in-flight",
+                self.pool_inflight
+            );
+        }
+    }
+
+    /// the timeout for a single request, which will be adaptive if enabled
+    pub(super) fn request_timeout(&self) -> Duration {
+        self.adaptive_timeout
+            .as_ref()
+            .map(|t| t.current())
+            .unwrap_or(self.timeout)
+    }
+
+    pub(super) fn record_request_latency(&self, latency: Duration) {
+        if let Some(t) = &self.adaptive_timeout {
+            t.record(latency);
+        }
+    }
+
+    #[inline]
+    pub(super) fn retry_interval(&self) -> Duration {
+        self.connect_retry_interval
+    }
+
+    pub(super) fn mark_new_request(&self) {
+        if let Some(budget) = &self.retry_budget {
+            budget.add_request();
+        }
+    }
+
+    /// take one retry from the global retry budget, always succeed if no budget set
+    pub(super) fn acquire_retry(&self) -> bool {
+        self.retry_budget
+            .as_ref()
+            .map(|budget| budget.acquire())
+            .unwrap_or(true)
+    }
+
+    /// check if the request failed as the extension action is rejected by the server
+    pub(super) fn is_unsupported_extension(&self, e: &anyhow::Error) -> bool {
+        if !matches!(self.global.action, KeylessAction::GenerateKey(_)) {
+            return false;
+        }
+        matches!(
+            e.downcast_ref::<KeylessServerError>(),
+            Some(KeylessServerError::BadOpCode | KeylessServerError::UnexpectedOpCode)
+        )
+    }
+
+    /// check if the request should be retried, only server returned errors are retryable,
+    /// and auth failures won't be retried unless explicitly set by retry codes
+    pub(super) fn should_retry_request(&self, e: &anyhow::Error) -> bool {
+        let Some(server_error) = e.downcast_ref::<KeylessServerError>() else {
+            return false;
+        };
+        match &self.retry_on_codes {
+            Some(codes) => codes.contains(&server_error.code()),
+            None => !matches!(server_error, KeylessServerError::Unauthorized),
+        }
+    }
+
+    pub(super) async fn resolve_target_address(
+        &mut self,
+        proc_args: &ProcArgs,
+    ) -> anyhow::Result<()> {
+        let addrs = proc_args.resolve(&self.target).await?;
+        self.target_addrs = Some(addrs);
+        if let Some(control) = &self.control_target {
+            let addrs = proc_args.resolve(control).await?;
+            self.control_addrs = Some(addrs);
+        }
+        Ok(())
+    }
+
+    /// the max time to spend for a new keyless connection, including all tcp connect retries
+    pub(super) fn new_connection_timeout(&self) -> Duration {
+        total_connect_timeout(
+            self.connect_timeout,
+            self.connect_retries,
+            self.connect_retry_interval,
+        )
+    }
+
+    /// print the resolved bench plan, with all the default values filled
+    pub(super) fn explain(&self, proc_args: &ProcArgs) {
+        let _ = self.write_plan(proc_args, &mut io::stdout());
+    }
+
+    /// write the resolved bench plan in human readable format
+    pub(super) fn write_plan<W: Write>(&self, proc_args: &ProcArgs, w: &mut W) -> io::Result<()> {
+        writeln!(w, "# Bench Plan")?;
+        writeln!(w, "Target: {}", self.target)?;
+        if let Some(addrs) = &self.target_addrs {
+            let addrs: Vec<String> = addrs
+                .pick_serial_n(usize::MAX)
+                .into_iter()
+                .map(|v| v.inner().to_string())
+                .collect();
+            writeln!(w, "Target Addresses: {}", addrs.join(", "))?;
+        }
+        if let Some(control) = &self.control_target {
+            writeln!(w, "Control Target: {control}")?;
+        }
+        let transport = if self.dtls_client.is_some() {
+            "dtls".to_string()
+        } else if self.tls.client.is_none() {
+            "tcp".to_string()
+        } else if let Some(ratio) = self.tls_ratio {
+            format!(
+                "tls for {:.1}% connections, tcp for the others",
+                ratio * 100.0
+            )
+        } else {
+            "tls".to_string()
+        };
+        writeln!(w, "Transport: {transport}")?;
+        if let Some(range) = &self.local_port_range {
+            writeln!(
+                w,
+                "Local Port Range: {}-{}, reuse addr {}",
+                range.start(),
+                range.end(),
+                self.reuse_addr
+            )?;
+        }
+        if self.tls.client.is_some() {
+            if let Some(name) = &self.tls.tls_name {
+                writeln!(w, "TLS Name: {name}")?;
+            }
+            writeln!(w, "TLS Verify: {}", !self.tls.no_verify)?;
+            if let Some(rotation) = &self.sni_rotation {
+                writeln!(w, "TLS SNI: rotate over {} names", rotation.len())?;
+            }
+            if let Some(file) = &self.session_cache_file {
+                writeln!(w, "TLS Session Cache File: {}", file.path().display())?;
+            }
+        }
+        match self.pool_size {
+            Some(size) => writeln!(
+                w,
+                "Connections: pool of {size}, warmup {}",
+                self.pool_warmup
+            )?,
+            None => writeln!(w, "Connections: one for each concurrency")?,
+        }
+        writeln!(w, "Multiplex: {}", !self.no_multiplex)?;
+        if self.connect_only {
+            writeln!(w, "Action: connect only")?;
+        } else if self.global.multi_keys.is_empty() {
+            writeln!(w, "Action: {:?}", self.global.action)?;
+        } else {
+            writeln!(
+                w,
+                "Action: {:?} for each of {} keys",
+                self.global.action,
+                self.global.multi_keys.len()
+            )?;
+        }
+        writeln!(w, "Payload Size: {}", self.global.payload.len())?;
+        writeln!(w, "Payload Reuse: {}", !self.no_payload_reuse)?;
+        if let Some((digest, _)) = &self.request_hmac {
+            writeln!(w, "Request HMAC: {digest}")?;
+        }
+        if self.verify_opcode {
+            writeln!(w, "Verify OpCode: true")?;
+        }
+        if self.phases.is_empty() {
+            writeln!(w, "Concurrency: {}", proc_args.concurrency)?;
+            if let Some(requests) = proc_args.requests {
+                writeln!(w, "Requests: {requests}")?;
+            }
+            if let Some(time_limit) = proc_args.time_limit {
+                writeln!(w, "Time Limit: {time_limit:?}")?;
+            }
+        } else {
+            for (i, phase) in self.phases.iter().enumerate() {
+                writeln!(
+                    w,
+                    "Phase {i}: concurrency {}, duration {:?}",
+                    phase.concurrency, phase.duration
+                )?;
+            }
+        }
+        if let Some(start_at) = self.start_at {
+            let start_at = chrono::DateTime::<chrono::Utc>::from(start_at);
+            writeln!(w, "Start At: {}", start_at.to_rfc3339())?;
+        }
+        let rate_limit = proc_args
+            .rate_limit
+            .as_ref()
+            .map(|quota| 1.0 / quota.get_inner().replenish_interval().as_secs_f64());
+        if let Some(rate) = rate_limit {
+            writeln!(w, "Rate Limit: {rate:.3}/s")?;
+        }
+        match &self.adaptive_timeout {
+            Some(_) => writeln!(w, "Request Timeout: adaptive, max {:?}", self.timeout)?,
+            None => writeln!(w, "Request Timeout: {:?}", self.timeout)?,
+        }
+        writeln!(
+            w,
+            "Request Retries: {}, Connect Retries: {}",
+            self.request_retries, self.connect_retries
+        )?;
+        if let Some(budget) = &self.retry_budget {
+            writeln!(
+                w,
+                "Retry Budget: {:.1}% of requests",
+                budget.ratio() * 100.0
+            )?;
+        }
+
+        let duration = if self.phases.is_empty() {
+            proc_args.time_limit
+        } else {
+            Some(self.phases.iter().map(|p| p.duration).sum())
+        };
+        let estimated = match (proc_args.requests, duration, rate_limit) {
+            (Some(requests), _, _) if self.phases.is_empty() => format!("{requests} tasks"),
+            (_, Some(duration), Some(rate)) => {
+                format!("{:.0} tasks at most", rate * duration.as_secs_f64())
+            }
+            (_, Some(duration), None) => format!("unlimited tasks in {duration:?}"),
+            _ => "unlimited tasks".to_string(),
+        };
+        let requests_per_task = self.global.multi_keys.len().max(1);
+        writeln!(
+            w,
+            "Estimated Load: {estimated}, {requests_per_task} request(s) per task"
+        )?;
+        Ok(())
+    }
+
+    pub(super) fn select_target_addr(&self, proc_args: &ProcArgs) -> anyhow::Result<SocketAddr> {
+        let addrs = self
+            .target_addrs
+            .as_ref()
+            .ok_or_else(|| anyhow!("no target addr set"))?;
+        Ok(*proc_args.select_peer(addrs))
+    }
+
+    /// wait until the shared start time, so all coordinated instances run at the same time
+    pub(super) async fn wait_to_start(&self, quiet: bool) {
+        let Some(start_at) = self.start_at else {
+            return;
+        };
+        match start_at.duration_since(SystemTime::now()) {
+            Ok(wait) => {
+                if !quiet {
+                    println!("waiting {wait:.3?} to start");
+                }
+                tokio::time::sleep(wait).await;
+            }
+            Err(e) => {
+                eprintln!(
+                    "WARN: the start time has passed {:.3?} ago, start now",
+                    e.duration()
+                );
+            }
+        }
+    }
+
+    #[inline]
+    pub(super) fn has_control_target(&self) -> bool {
+        self.control_target.is_some()
+    }
+
+    pub(super) fn select_control_addr(&self, proc_args: &ProcArgs) -> anyhow::Result<SocketAddr> {
+        let addrs = self
+            .control_addrs
+            .as_ref()
+            .ok_or_else(|| anyhow!("no control target addr set"))?;
+        Ok(*proc_args.select_peer(addrs))
+    }
+
+    /// decide whether the next new connection should use tls
+    pub(super) fn select_tls(&self) -> bool {
+        if self.tls.client.is_none() {
+            return false;
+        }
+        match self.tls_ratio {
+            Some(ratio) => rand::random::<f64>() < ratio,
+            None => true,
+        }
+    }
+
+    pub(super) async fn new_multiplex_keyless_connection(
+        &self,
+        peer: SocketAddr,
+        use_tls: bool,
+        mut trace: Option<&mut OtlpTrace>,
+    ) -> anyhow::Result<MultiplexTransfer> {
+        if let Some(dtls_client) = &self.dtls_client {
+            let (local_addr, ssl_stream) =
+                self.dtls_connect_to_peer(dtls_client, peer, trace).await?;
+            let tls_version = ssl_stream.ssl().version_str();
+            let (r, w) = tokio::io::split(ssl_stream);
+            let mut transfer = MultiplexTransfer::start(
+                r,
+                w,
+                local_addr,
+                peer,
+                true,
+                self.timeout,
+                self.max_response_size,
+            );
+            transfer.set_tls_version(tls_version);
+            transfer.set_retransmit_interval(self.udp_retransmit);
+            if self.verify_opcode {
+                transfer.set_verify_opcode();
+            }
+            return Ok(transfer);
+        }
+
+        let connect_start = SystemTime::now();
+        let tcp_stream = self.new_tcp_connection(peer).await?;
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.add_child("connect", connect_start);
+        }
+        let local_addr = tcp_stream
+            .local_addr()
+            .map_err(|e| anyhow!("failed to get local address: {e:?}"))?;
+        if let Some(tls_client) = self.tls.client.as_ref().filter(|_| use_tls) {
+            let tls_start = SystemTime::now();
+            let ssl_stream = self
+                .tls_connect_to_target(tls_client, tcp_stream, peer)
+                .await?;
+            if let Some(trace) = trace {
+                trace.add_child("tls", tls_start);
+            }
+            let tls_version = ssl_stream.ssl().version_str();
+            let (r, w) = tokio::io::split(ssl_stream);
+            let mut transfer = MultiplexTransfer::start(
+                r,
+                w,
+                local_addr,
+                peer,
+                true,
+                self.timeout,
+                self.max_response_size,
+            );
+            transfer.set_tls_version(tls_version);
+            if self.verify_opcode {
+                transfer.set_verify_opcode();
+            }
+            Ok(transfer)
+        } else {
+            let (r, w) = tcp_stream.into_split();
+            let mut transfer = MultiplexTransfer::start(
+                r,
+                w,
+                local_addr,
+                peer,
+                false,
+                self.timeout,
+                self.max_response_size,
+            );
+            if self.verify_opcode {
+                transfer.set_verify_opcode();
+            }
+            Ok(transfer)
+        }
+    }
+
+    pub(super) async fn new_simplex_keyless_connection(
+        &self,
+        peer: SocketAddr,
+        use_tls: bool,
+        mut trace: Option<&mut OtlpTrace>,
+    ) -> anyhow::Result<SimplexTransfer> {
+        if let Some(dtls_client) = &self.dtls_client {
+            let (local_addr, ssl_stream) =
+                self.dtls_connect_to_peer(dtls_client, peer, trace).await?;
+            let tls_version = ssl_stream.ssl().version_str();
+            let (r, w) = tokio::io::split(ssl_stream);
+            let mut transfer = SimplexTransfer::new(r, w, local_addr, peer, self.max_response_size);
+            transfer.set_tls_version(tls_version);
+            transfer.set_retransmit_interval(self.udp_retransmit);
+            if self.verify_opcode {
+                transfer.set_verify_opcode();
+            }
+            return Ok(transfer);
+        }
+
+        let connect_start = SystemTime::now();
+        let tcp_stream = self.new_tcp_connection(peer).await?;
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.add_child("connect", connect_start);
+        }
+        let local_addr = tcp_stream
+            .local_addr()
+            .map_err(|e| anyhow!("failed to get local address: {e:?}"))?;
+        if let Some(tls_client) = self.tls.client.as_ref().filter(|_| use_tls) {
+            let tls_start = SystemTime::now();
+            let ssl_stream = self
+                .tls_connect_to_target(tls_client, tcp_stream, peer)
+                .await?;
+            if let Some(trace) = trace {
+                trace.add_child("tls", tls_start);
+            }
+            let tls_version = ssl_stream.ssl().version_str();
+            let (r, w) = tokio::io::split(ssl_stream);
+            let mut transfer = SimplexTransfer::new(r, w, local_addr, peer, self.max_response_size);
+            transfer.set_tls_version(tls_version);
+            if self.verify_opcode {
+                transfer.set_verify_opcode();
+            }
+            Ok(transfer)
+        } else {
+            let (r, w) = tcp_stream.into_split();
+            let mut transfer = SimplexTransfer::new(r, w, local_addr, peer, self.max_response_size);
+            if self.verify_opcode {
+                transfer.set_verify_opcode();
+            }
+            Ok(transfer)
+        }
+    }
+
+    async fn new_tcp_connection(&self, peer: SocketAddr) -> anyhow::Result<TcpStream> {
+        let mut stream = self.connect_to_peer(peer).await?;
+
+        if let Some(data) = self.proxy_protocol.data() {
+            stream
+                .write_all(data)
+                .await
+                .map_err(|e| anyhow!("failed to write proxy protocol data: {e:?}"))?;
+        }
+
+        Ok(stream)
+    }
+
+    /// setup a new socket to the peer, which will be bound to the next port
+    /// in the local port range if set
+    fn new_tcp_socket(&self, peer: SocketAddr) -> anyhow::Result<TcpSocket> {
+        let Some(range) = &self.local_port_range else {
+            return g3_socket::tcp::new_socket_bind_to(
+                peer.ip(),
+                self.bind.map(|ip| SocketAddr::new(ip, 0)),
+                self.reuse_addr,
+                &Default::default(),
+                &Default::default(),
+                self.tcp_nodelay,
+            )
+            .map_err(|e| anyhow!("failed to setup socket to peer {peer}: {e:?}"));
+        };
+
+        let bind_ip = self.bind.unwrap_or_else(|| match peer {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        });
+        let count = range.count() as usize;
+        for _ in 0..count {
+            let offset = self.next_local_port.fetch_add(1, Ordering::Relaxed) % count;
+            let port = range.start() + offset as u16;
+            match g3_socket::tcp::new_socket_bind_to(
+                peer.ip(),
+                Some(SocketAddr::new(bind_ip, port)),
+                self.reuse_addr,
+                &Default::default(),
+                &Default::default(),
+                self.tcp_nodelay,
+            ) {
+                Ok(socket) => return Ok(socket),
+                Err(e) if e.kind() == io::ErrorKind::AddrInUse => continue,
+                Err(e) => return Err(anyhow!("failed to setup socket to peer {peer}: {e:?}")),
+            }
+        }
+        Err(anyhow!(
+            "failed to setup socket to peer {peer}: local port range {}-{} exhausted",
+            range.start(),
+            range.end()
+        ))
+    }
+
+    async fn connect_to_peer(&self, peer: SocketAddr) -> anyhow::Result<TcpStream> {
+        let mut attempt = 0u32;
+        loop {
+            attempt += 1;
+            let socket = self.new_tcp_socket(peer)?;
+            let e = match tokio::time::timeout(self.connect_timeout, socket.connect(peer)).await {
+                Ok(Ok(stream)) => return Ok(stream),
+                Ok(Err(e)) if e.kind() == io::ErrorKind::AddrNotAvailable => {
+                    anyhow!("connect to {peer} error: local port exhausted")
+                }
+                Ok(Err(e)) => anyhow!("connect to {peer} error: {e:?}"),
+                Err(_) => anyhow!("connect to {peer} timed out"),
+            };
+            if attempt > self.connect_retries {
+                return Err(e.context(format!("connect error after {attempt} attempt(s)")));
+            }
+            tokio::time::sleep(self.connect_retry_interval).await;
+        }
+    }
+
+    async fn dtls_connect_to_peer(
+        &self,
+        dtls_client: &OpensslClientConfig,
+        peer: SocketAddr,
+        mut trace: Option<&mut OtlpTrace>,
+    ) -> anyhow::Result<(SocketAddr, SslStream<UdpDatagramStream>)> {
+        let connect_start = SystemTime::now();
+        let bind_ip = self.bind.unwrap_or_else(|| match peer {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        });
+        let socket = UdpSocket::bind(SocketAddr::new(bind_ip, 0))
+            .await
+            .map_err(|e| anyhow!("failed to setup udp socket to peer {peer}: {e:?}"))?;
+        socket
+            .connect(peer)
+            .await
+            .map_err(|e| anyhow!("failed to connect udp socket to {peer}: {e:?}"))?;
+        let local_addr = socket
+            .local_addr()
+            .map_err(|e| anyhow!("failed to get local address: {e:?}"))?;
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.add_child("connect", connect_start);
+        }
+
+        let dtls_start = SystemTime::now();
+        let sni = self.sni_rotation.as_ref().map(|r| r.select());
+        let tls_name = match sni {
+            Some((_, name)) => name,
+            None => self
+                .tls
+                .tls_name
+                .as_ref()
+                .unwrap_or_else(|| self.target.host()),
+        };
+        let handshake_start = Instant::now();
+        let mut ssl = dtls_client
+            .build_ssl(tls_name, self.target.port())
+            .context("failed to build dtls ssl")?;
+        if self.tls.no_verify {
+            ssl.set_verify(SslVerifyMode::NONE);
+        }
+        ssl.set_mtu(DTLS_MTU)
+            .map_err(|e| anyhow!("failed to set dtls mtu: {e}"))?;
+        let connector = g3_openssl::SslConnector::new(ssl, UdpDatagramStream::new(socket))
+            .map_err(|e| anyhow!("dtls connector create failed: {e}"))?;
+        let ssl_stream = match tokio::time::timeout(self.connect_timeout, connector.connect()).await
+        {
+            Ok(Ok(stream)) => stream,
+            Ok(Err(e)) if self.tls.verbose_errors => {
+                return Err(anyhow!("dtls connect to {tls_name} failed: {e:?}"))
+            }
+            Ok(Err(e)) => return Err(anyhow!("dtls connect to {tls_name} failed: {e}")),
+            Err(_) => return Err(anyhow!("dtls connect to {tls_name} timed out")),
+        };
+        if let (Some(rotation), Some((index, _))) = (&self.sni_rotation, sni) {
+            rotation.record_handshake(index, handshake_start.elapsed());
+        }
+        if let Some(trace) = trace {
+            trace.add_child("tls", dtls_start);
+        }
+        if let Some(dumper) = &self.peer_chain_dumper {
+            dumper.dump(peer, ssl_stream.ssl());
+        }
+        Ok((local_addr, ssl_stream))
+    }
+
+    async fn tls_connect_to_target<S>(
+        &self,
+        tls_client: &OpensslClientConfig,
+        stream: S,
+        peer: SocketAddr,
+    ) -> anyhow::Result<SslStream<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let ssl_stream = match &self.sni_rotation {
+            Some(rotation) => {
+                let (index, tls_name) = rotation.select();
+                let handshake_start = Instant::now();
+                let ssl_stream = self
+                    .tls
+                    .connect_target_with_name(tls_client, stream, tls_name, self.target.port())
+                    .await?;
+                rotation.record_handshake(index, handshake_start.elapsed());
+                ssl_stream
+            }
+            None => {
+                self.tls
+                    .connect_target(tls_client, stream, &self.target)
+                    .await?
+            }
+        };
+        if let Some(file) = &self.session_cache_file {
+            file.record_handshake(ssl_stream.ssl());
+        }
+        if let Some(dumper) = &self.peer_chain_dumper {
+            dumper.dump(peer, ssl_stream.ssl());
+        }
+        Ok(ssl_stream)
+    }
+
+    /// save the tls sessions to the session cache file, and print the summary if not quiet
+    /// save the session cache file, a failure here should not hide the result of the run
+    pub(super) fn save_session_cache(&self, quiet: bool) {
+        let (Some(file), Some(tls_client)) = (&self.session_cache_file, &self.tls.client) else {
+            return;
+        };
+        match file.save(tls_client) {
+            Ok(saved) => {
+                if !quiet {
+                    println!();
+                    file.summary(saved);
+                }
+            }
+            Err(e) => eprintln!("WARN: failed to save the tls session cache: {e}"),
+        }
+    }
+}
+
+pub(super) fn add_cloudflare_args(app: Command) -> Command {
+    app.arg(
+        Arg::new(ARG_TARGET)
+            .help("Target service address, the port should always be set")
+            .value_name("ADDRESS")
+            .long(ARG_TARGET)
+            .required(true)
+            .num_args(1)
+            .value_parser(value_parser!(UpstreamAddr)),
+    )
+    .arg(
+        Arg::new(ARG_CONTROL_TARGET)
+            .help(
+                "Send ping requests to this control address in parallel, \
+                and report the latency delta to the target as the pure crypto cost. \
+                The same transport and tls config as the target will be used",
+            )
+            .value_name("ADDRESS")
+            .long(ARG_CONTROL_TARGET)
+            .num_args(1)
+            .value_parser(value_parser!(UpstreamAddr))
+            .conflicts_with_all([ARG_PHASE, ARG_PROBE, ARG_CONNECT_ONLY]),
+    )
+    .arg(
+        Arg::new(ARG_NO_TLS)
+            .help("Use no tls")
+            .long(ARG_NO_TLS)
+            .action(ArgAction::SetTrue)
+            .num_args(0),
+    )
+    .arg(
+        Arg::new(ARG_TLS_RATIO)
+            .help(
+                "Set the fraction of new connections that will use tls, \
+                the others will use plain tcp",
+            )
+            .value_name("RATIO")
+            .long(ARG_TLS_RATIO)
+            .num_args(1)
+            .value_parser(value_parser!(f64))
+            .conflicts_with(ARG_NO_TLS),
+    )
+    .arg(
+        Arg::new(ARG_SNI_LIST)
+            .help(
+                "Use the tls server names in this comma separated list in turn for new \
+                connections, and report the handshake time of each server name",
+            )
+            .value_name("NAMES")
+            .long(ARG_SNI_LIST)
+            .num_args(1)
+            .value_delimiter(',')
+            .conflicts_with_all([ARG_NO_TLS, "tls-name"]),
+    )
+    .arg(
+        Arg::new(ARG_SESSION_CACHE_FILE)
+            .help(
+                "Load tls sessions from this file at startup and save them back after the run, \
+                so the handshakes can be resumed across runs",
+            )
+            .value_name("PATH")
+            .long(ARG_SESSION_CACHE_FILE)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::FilePath)
+            .conflicts_with_all([ARG_NO_TLS, ARG_UDP, "tls-session-cache"]),
+    )
+    .arg(
+        Arg::new(ARG_UDP)
+            .help(
+                "Use dtls over udp instead of tls over tcp, the tls options will also be \
+                used for dtls, and the protocol should be tls1.2 for DTLS 1.2 if set. \
+                Each keyless message should fit in one dtls record",
+            )
+            .arg(
+                Arg::new(ARG_UDP_RETRANSMIT)
+                    .value_name("DURATION")
+                    .help(
+                        "Resend the request over udp if no response is received in this time, \
+                the wait time will be doubled after each resend",
+                    )
+                    .long(ARG_UDP_RETRANSMIT)
+                    .num_args(1)
+                    .default_value("1s")
+                    .requires(ARG_UDP),
+            )
+            .long(ARG_UDP)
+            .action(ArgAction::SetTrue)
+            .num_args(0)
+            .conflicts_with_all([ARG_NO_TLS, ARG_TLS_RATIO]),
+    )
+    .arg(
+        Arg::new(ARG_CONNECTION_POOL)
+            .help(
+                "Set the number of pooled underlying keyless connections.\n\
+                        If not set, each concurrency will use its own keyless connection",
+            )
+            .value_name("POOL SIZE")
+            .long(ARG_CONNECTION_POOL)
+            .short('C')
+            .num_args(1)
+            .value_parser(value_parser!(usize))
+            .conflicts_with(ARG_NO_MULTIPLEX),
+    )
+    .arg(
+        Arg::new(ARG_POOL_WARMUP)
+            .help(
+                "Send a real request on each pooled connection before the benchmark, \
+                and quit if any of them failed",
+            )
+            .long(ARG_POOL_WARMUP)
+            .action(ArgAction::SetTrue)
+            .requires(ARG_CONNECTION_POOL),
+    )
+    .arg(
+        Arg::new(ARG_POOL_INFLIGHT)
+            .help(
+                "Set the number of in-flight requests for each pooled connection. \
+                If the global concurrency is not set explicitly, \
+                it will be the pool size multiplied by this value",
+            )
+            .value_name("COUNT")
+            .long(ARG_POOL_INFLIGHT)
+            .num_args(1)
+            .value_parser(value_parser!(usize))
+            .default_value("4")
+            .requires(ARG_CONNECTION_POOL),
+    )
+    .arg(
+        Arg::new(ARG_LOCAL_ADDRESS)
+            .value_name("LOCAL IP ADDRESS")
+            .short('B')
+            .long(ARG_LOCAL_ADDRESS)
+            .num_args(1)
+            .value_parser(value_parser!(IpAddr)),
+    )
+    .arg(
+        Arg::new(ARG_LOCAL_PORT_RANGE)
+            .value_name("START-END")
+            .help(
+                "Bind new connections to the local ports in this range in turn, \
+                instead of the ephemeral ports assigned by the system",
+            )
+            .long(ARG_LOCAL_PORT_RANGE)
+            .num_args(1)
+            .value_parser(value_parser!(PortRange))
+            .conflicts_with(ARG_UDP),
+    )
+    .arg(
+        Arg::new(ARG_REUSE_ADDR)
+            .help(
+                "Set SO_REUSEADDR on new connections, \
+                so the local ports in TIME_WAIT state can be reused",
+            )
+            .long(ARG_REUSE_ADDR)
+            .action(ArgAction::SetTrue)
+            .num_args(0)
+            .conflicts_with(ARG_UDP),
+    )
+    .arg(
+        Arg::new(ARG_TCP_NODELAY)
+            .value_name("BOOL")
+            .help("Set TCP_NODELAY on the connections to the target")
+            .long(ARG_TCP_NODELAY)
+            .num_args(1)
+            .value_parser(value_parser!(bool))
+            .default_value("true"),
+    )
+    .arg(
+        Arg::new(ARG_CONNECT_TIMEOUT)
+            .value_name("TIMEOUT DURATION")
+            .help("Timeout for connection to next peer")
+            .default_value("10s")
+            .long(ARG_CONNECT_TIMEOUT)
+            .num_args(1),
+    )
+    .arg(
+        Arg::new(ARG_CONNECT_RETRIES)
+            .value_name("COUNT")
+            .help(
+                "Retry count for tcp connect to the target, each attempt will use its own timeout",
+            )
+            .long(ARG_CONNECT_RETRIES)
+            .num_args(1)
+            .value_parser(value_parser!(u32).range(..=MAX_CONNECT_RETRIES as i64))
+            .default_value("0"),
+    )
+    .arg(
+        Arg::new(ARG_CONNECT_RETRY_INTERVAL)
+            .value_name("DURATION")
+            .help("Wait time before the next tcp connect or request retry")
+            .long(ARG_CONNECT_RETRY_INTERVAL)
+            .num_args(1)
+            .default_value("100ms"),
+    )
+    .arg(
+        Arg::new(ARG_TIMEOUT)
+            .value_name("TIMEOUT DURATION")
+            .help("Timeout for a single request")
+            .default_value("5s")
+            .long(ARG_TIMEOUT)
+            .num_args(1),
+    )
+    .arg(
+        Arg::new(ARG_ADAPTIVE_TIMEOUT)
+            .value_name("FACTOR")
+            .help(
+                "Enable adaptive request timeout, which is FACTOR times the observed p99 latency. \
+                It will be updated every 100 passed requests after the warmup, \
+                bounded by the min value and the fixed request timeout",
+            )
+            .long(ARG_ADAPTIVE_TIMEOUT)
+            .num_args(1)
+            .value_parser(value_parser!(f64)),
+    )
+    .arg(
+        Arg::new(ARG_ADAPTIVE_TIMEOUT_WARMUP)
+            .value_name("COUNT")
+            .help("Number of passed requests to observe before adapting the request timeout")
+            .long(ARG_ADAPTIVE_TIMEOUT_WARMUP)
+            .num_args(1)
+            .value_parser(value_parser!(u64).range(1..))
+            .default_value("1000")
+            .requires(ARG_ADAPTIVE_TIMEOUT),
+    )
+    .arg(
+        Arg::new(ARG_ADAPTIVE_TIMEOUT_MIN)
+            .value_name("DURATION")
+            .help("Min value of the adaptive request timeout")
+            .long(ARG_ADAPTIVE_TIMEOUT_MIN)
+            .num_args(1)
+            .default_value("10ms")
+            .requires(ARG_ADAPTIVE_TIMEOUT),
+    )
+    .arg(
+        Arg::new(ARG_MAX_RESPONSE_SIZE)
+            .value_name("SIZE")
+            .help(
+                "Max size of a single response message, \
+                the connection will be closed if exceeded",
+            )
+            .long(ARG_MAX_RESPONSE_SIZE)
+            .num_args(1)
+            .default_value("64KiB"),
+    )
+    .arg(
+        Arg::new(ARG_REQUEST_RETRIES)
+            .value_name("COUNT")
+            .help("Retry count for requests that failed with server returned errors")
+            .long(ARG_REQUEST_RETRIES)
+            .num_args(1)
+            .value_parser(value_parser!(usize))
+            .default_value("0"),
+    )
+    .arg(
+        Arg::new(ARG_RETRY_ON_CODES)
+            .value_name("CODE")
+            .help(
+                "Only retry on these server error codes, \
+                requests failed with other codes will fail immediately",
+            )
+            .long(ARG_RETRY_ON_CODES)
+            .num_args(1..)
+            .value_delimiter(',')
+            .value_parser(value_parser!(u8).range(1..))
+            .requires(ARG_REQUEST_RETRIES),
+    )
+    .arg(
+        Arg::new(ARG_RETRY_BUDGET)
+            .value_name("RATIO")
+            .help(
+                "Limit the total retries to this ratio of all requests, \
+                no more retries will be made for the rest of the run once exhausted",
+            )
+            .long(ARG_RETRY_BUDGET)
+            .num_args(1)
+            .value_parser(value_parser!(f64))
+            .requires(ARG_REQUEST_RETRIES),
+    )
+    .arg(
+        Arg::new(ARG_NO_MULTIPLEX)
+            .help("Disable multiplex usage on the connection")
+            .long(ARG_NO_MULTIPLEX)
+            .action(ArgAction::SetTrue)
+            .num_args(0)
+            .conflicts_with(ARG_CONNECTION_POOL),
+    )
+    .arg(
+        Arg::new(ARG_SERVER_CHOOSE_RSA_PADDING)
+            .help(
+                "Let the server choose the rsa padding, the one set by --rsa-padding \
+                will be sent as the proposed default in the extension item 0x40. \
+                The server may report the padding it used in the same item in the response, \
+                and the local result checks will be skipped if it's not the proposed one",
+            )
+            .long(ARG_SERVER_CHOOSE_RSA_PADDING)
+            .action(ArgAction::SetTrue)
+            .num_args(0),
+    )
+    .arg(
+        Arg::new(ARG_VERIFY_OPCODE)
+            .help(
+                "Check if the response opcode matches the request, \
+                and report the mismatched ones as protocol errors",
+            )
+            .long(ARG_VERIFY_OPCODE)
+            .action(ArgAction::SetTrue)
+            .num_args(0),
+    )
+    .arg(
+        Arg::new(ARG_REQUEST_HMAC_KEY_FILE)
+            .value_name("KEY FILE")
+            .help(
+                "Sign each request frame with hmac using the shared secret in this file, \
+                the secret should be in hex at the first line. \
+                The hmac will be sent in an extension item",
+            )
+            .long(ARG_REQUEST_HMAC_KEY_FILE)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::FilePath),
+    )
+    .arg(
+        Arg::new(ARG_REQUEST_HMAC_DIGEST)
+            .value_name("DIGEST")
+            .help("The digest algorithm to use for the request hmac")
+            .long(ARG_REQUEST_HMAC_DIGEST)
+            .num_args(1)
+            .value_parser(["sha1", "sha256", "sha384", "sha512"])
+            .default_value("sha256")
+            .requires(ARG_REQUEST_HMAC_KEY_FILE),
+    )
+    .arg(
+        Arg::new(ARG_PHASE)
+            .value_name("CONCURRENCY:DURATION")
+            .help(
+                "Run a bench phase with the specified concurrency and duration (like 10:30s), \
+                can be set multiple times to run the phases in order. \
+                The global concurrency, requests and time limit will be ignored if set",
+            )
+            .long(ARG_PHASE)
+            .num_args(1)
+            .action(ArgAction::Append),
+    )
+    .arg(
+        Arg::new(ARG_PROBE)
+            .help(
+                "Send only one request and print the result in nagios plugin format, \
+                the exit code will be 0/1/2/3 for OK/WARNING/CRITICAL/UNKNOWN. \
+                Use with the global quiet option to get only the status line",
+            )
+            .long(ARG_PROBE)
+            .action(ArgAction::SetTrue)
+            .num_args(0)
+            .conflicts_with(ARG_PHASE),
+    )
+    .arg(
+        Arg::new(ARG_CHECK_KEY_ROUTING)
+            .value_name("COUNT")
+            .help(
+                "Open this many new connections, send a request for each key on each of them, \
+                and print the pass/fail matrix of each target address. \
+                The exit code will be non-zero if any key is not accepted",
+            )
+            .long(ARG_CHECK_KEY_ROUTING)
+            .num_args(1)
+            .value_parser(value_parser!(usize))
+            .conflicts_with_all([ARG_PHASE, ARG_PROBE, ARG_CONNECTION_POOL, ARG_CONNECT_ONLY]),
+    )
+    .arg(
+        Arg::new(ARG_COLD_WARM)
+            .value_name("COUNT")
+            .help(
+                "Send one cold request followed by this many warm requests for each key \
+                in the key directory, and compare the cold and warm latency",
+            )
+            .long(ARG_COLD_WARM)
+            .num_args(1)
+            .value_parser(value_parser!(usize))
+            .conflicts_with_all([
+                ARG_PHASE,
+                ARG_PROBE,
+                ARG_CONNECTION_POOL,
+                ARG_CONNECT_ONLY,
+                ARG_CHECK_KEY_ROUTING,
+            ]),
+    )
+    .arg(
+        Arg::new(ARG_WARN_LATENCY)
+            .value_name("DURATION")
+            .help("Latency threshold for WARNING status in probe mode")
+            .long(ARG_WARN_LATENCY)
+            .num_args(1)
+            .requires(ARG_PROBE),
+    )
+    .arg(
+        Arg::new(ARG_CRIT_LATENCY)
+            .value_name("DURATION")
+            .help("Latency threshold for CRITICAL status in probe mode")
+            .long(ARG_CRIT_LATENCY)
+            .num_args(1)
+            .requires(ARG_PROBE),
+    )
+    .arg(
+        Arg::new(ARG_NO_PAYLOAD_REUSE)
+            .help(
+                "Build the request message from a fresh copy of the payload for each request. \
+                By default the read-only payload is shared and the request message is built \
+                only once for each concurrency",
+            )
+            .long(ARG_NO_PAYLOAD_REUSE)
+            .action(ArgAction::SetTrue)
+            .num_args(0),
+    )
+    .arg(
+        Arg::new(ARG_CONNECT_ONLY)
+            .help(
+                "Only setup new connections and close them immediately, \
+                no request will be sent",
+            )
+            .long(ARG_CONNECT_ONLY)
+            .action(ArgAction::SetTrue)
+            .num_args(0)
+            .conflicts_with_all([ARG_CONNECTION_POOL, ARG_PROBE]),
+    )
+    .arg(
+        Arg::new(ARG_EXPLAIN)
+            .help("Print the resolved bench plan and quit, unless --confirm is also set")
+            .long(ARG_EXPLAIN)
+            .action(ArgAction::SetTrue)
+            .num_args(0),
+    )
+    .arg(
+        Arg::new(ARG_CONFIRM)
+            .help("Wait for confirmation to continue after the bench plan is printed")
+            .long(ARG_CONFIRM)
+            .action(ArgAction::SetTrue)
+            .num_args(0)
+            .requires(ARG_EXPLAIN),
+    )
+    .arg(
+        Arg::new(ARG_ARTIFACT_DIR)
+            .value_name("PATH")
+            .help(
+                "Save the bench plan, summary, latency percentiles and histogram, \
+                and error distribution to this directory, with a manifest file",
+            )
+            .long(ARG_ARTIFACT_DIR)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::DirPath)
+            .conflicts_with_all([ARG_PHASE, ARG_PROBE]),
+    )
+    .arg(
+        Arg::new(ARG_START_AT)
+            .value_name("TIME")
+            .help(
+                "Wait until this unix timestamp or RFC3339 time before starting the bench, \
+                so multiple instances on different hosts can run at the same time",
+            )
+            .long(ARG_START_AT)
+            .num_args(1)
+            .conflicts_with(ARG_PROBE),
+    )
+    .arg(
+        Arg::new(ARG_HDR_OUTPUT)
+            .value_name("PATH")
+            .help(
+                "Save the latency histogram to this file, \
+                in the V2 compressed format of HdrHistogram",
+            )
+            .long(ARG_HDR_OUTPUT)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::FilePath)
+            .conflicts_with_all([ARG_PHASE, ARG_PROBE]),
+    )
+    .arg(
+        Arg::new(ARG_DUMP_PEER_CHAIN)
+            .value_name("DIR")
+            .help(
+                "Save the certificate chain presented by the server to this directory, \
+                as PEM files, once for each distinct peer address",
+            )
+            .long(ARG_DUMP_PEER_CHAIN)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::DirPath)
+            .conflicts_with(ARG_NO_TLS),
+    )
+    .arg(
+        Arg::new(ARG_RECORD)
+            .value_name("PATH")
+            .help("Record each request and its result to this file, which can be replayed later")
+            .long(ARG_RECORD)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::FilePath)
+            .conflicts_with_all([ARG_PROBE, ARG_CONNECT_ONLY]),
+    )
+    .arg(
+        Arg::new(ARG_REQUEST_CSV)
+            .value_name("PATH")
+            .help(
+                "Write one csv row for each completed request to this file, with columns: \
+                timestamp_us, task_id, connection (the local address), action, latency_us, \
+                status (ok, server_error or error), output_len. \
+                New columns will only be appended at the end",
+            )
+            .long(ARG_REQUEST_CSV)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::FilePath)
+            .conflicts_with_all([ARG_PROBE, ARG_CONNECT_ONLY]),
+    )
+    .arg(
+        Arg::new(ARG_REPLAY_FILE)
+            .value_name("PATH")
+            .help(
+                "Replay the requests recorded in this file in order on a single connection, \
+                and compare the results with the recorded ones",
+            )
+            .long(ARG_REPLAY_FILE)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::FilePath)
+            .conflicts_with_all([
+                ARG_PHASE,
+                ARG_PROBE,
+                ARG_CONNECTION_POOL,
+                ARG_CONNECT_ONLY,
+                ARG_CHECK_KEY_ROUTING,
+                ARG_COLD_WARM,
+                ARG_RECORD,
+            ]),
+    )
+    .arg(
+        Arg::new(ARG_REPLAY_TIMING)
+            .help("Keep the recorded timing of the requests when replay")
+            .long(ARG_REPLAY_TIMING)
+            .action(ArgAction::SetTrue)
+            .num_args(0)
+            .requires(ARG_REPLAY_FILE),
+    )
+    .arg(
+        Arg::new(ARG_BASELINE_DIR)
+            .value_name("PATH")
+            .help("Compare the result with the one saved in this artifact directory")
+            .long(ARG_BASELINE_DIR)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::DirPath)
+            .conflicts_with_all([ARG_PHASE, ARG_PROBE]),
+    )
+    .arg(
+        Arg::new(ARG_LOCAL_TIMING)
+            .help(
+                "Also run the action locally and report the time used, \
+                only active if the private key is loaded",
+            )
+            .long(ARG_LOCAL_TIMING)
+            .action(ArgAction::SetTrue)
+            .num_args(0),
+    )
+    .append_keyless_args()
+    .append_openssl_args()
+    .append_proxy_protocol_args()
+    .append_otlp_args()
+}
+
+/// load the hex encoded hmac key from the first line of the file
+fn load_hmac_key_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("failed to read hmac key file {}: {e}", path.display()))?;
+    let line = contents.lines().next().unwrap_or_default().trim();
+    if line.is_empty() {
+        return Err(anyhow!("no hmac key found in file {}", path.display()));
+    }
+    hex::decode(line).map_err(|e| anyhow!("invalid hmac key in file {}: {e}", path.display()))
+}
+
+/// the timeout of all the connect attempts and the wait time between them,
+/// saturated to the max duration for huge values
+fn total_connect_timeout(timeout: Duration, retries: u32, retry_interval: Duration) -> Duration {
+    timeout
+        .saturating_mul(retries.saturating_add(1))
+        .saturating_add(retry_interval.saturating_mul(retries))
+}
+
+fn parse_start_at(s: &str) -> anyhow::Result<SystemTime> {
+    if let Ok(secs) = u64::from_str(s) {
+        return Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+    }
+    let time = chrono::DateTime::parse_from_rfc3339(s)
+        .map_err(|e| anyhow!("invalid start time {s}, neither unix timestamp nor RFC3339: {e}"))?;
+    Ok(SystemTime::from(time))
+}
+
+/// there is no standard port for keyless service, so the port should always be set
+fn check_target_port(addr: &UpstreamAddr) -> anyhow::Result<()> {
+    if addr.port() == 0 {
+        Err(anyhow!(
+            "no port set in address {}, it should be in host:port or [ipv6]:port format",
+            addr.host()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// reject the action args that have no representation in the cloudflare keyless protocol
+fn check_remote_action(
+    action: KeylessAction,
+    rsa_pss_saltlen: Option<KeylessRsaPssSaltlen>,
+) -> anyhow::Result<()> {
+    if rsa_pss_saltlen.is_some() {
+        return Err(anyhow!(
+            "rsa-pss salt length can not be set in the cloudflare keyless requests"
+        ));
+    }
+    if let Some(digest) = action.sign_digest() {
+        if digest.is_sha3() {
+            return Err(anyhow!(
+                "sha3 digests are not supported by the cloudflare keyless protocol"
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn parse_cloudflare_args(args: &ArgMatches) -> anyhow::Result<KeylessCloudflareArgs> {
+    let target = if let Some(v) = args.get_one::<UpstreamAddr>(ARG_TARGET) {
+        check_target_port(v).context("invalid target")?;
+        v.clone()
+    } else {
+        return Err(anyhow!("no target set"));
+    };
+    let no_tls = args.get_flag(ARG_NO_TLS);
+
+    let mut global_args =
+        KeylessGlobalArgs::parse_args(args).context("failed to parse global keyless args")?;
+    global_args.setup_cross_check(args, true)?;
+
+    check_remote_action(global_args.action, global_args.rsa_pss_saltlen)?;
+
+    let mut cf_args = KeylessCloudflareArgs::new(global_args, target, no_tls);
+
+    if let Some(c) = args.get_one::<usize>(ARG_CONNECTION_POOL) {
+        if *c > 0 {
+            cf_args.pool_size = Some(*c);
+        }
+    }
+    if args.get_flag(ARG_POOL_WARMUP) {
+        cf_args.pool_warmup = true;
+    }
+    if let Some(n) = args.get_one::<usize>(ARG_POOL_INFLIGHT) {
+        if *n == 0 {
+            return Err(anyhow!("the pool in-flight count should not be 0"));
+        }
+        cf_args.pool_inflight = *n;
+    }
+
+    if let Some(ip) = args.get_one::<IpAddr>(ARG_LOCAL_ADDRESS) {
+        cf_args.bind = Some(*ip);
+    }
+    if let Some(range) = args.get_one::<PortRange>(ARG_LOCAL_PORT_RANGE) {
+        cf_args.local_port_range = Some(*range);
+    }
+    if args.get_flag(ARG_REUSE_ADDR) {
+        cf_args.reuse_addr = true;
+    }
+
+    if let Some(nodelay) = args.get_one::<bool>(ARG_TCP_NODELAY) {
+        cf_args.tcp_nodelay = *nodelay;
+    }
+
+    if let Some(timeout) = g3_clap::humanize::get_duration(args, ARG_CONNECT_TIMEOUT)? {
+        cf_args.connect_timeout = timeout;
+    }
+    if let Some(retries) = args.get_one::<u32>(ARG_CONNECT_RETRIES) {
+        cf_args.connect_retries = *retries;
+    }
+    if let Some(interval) = g3_clap::humanize::get_duration(args, ARG_CONNECT_RETRY_INTERVAL)? {
+        cf_args.connect_retry_interval = interval;
+    }
+    if let Some(size) = g3_clap::humanize::get_usize(args, ARG_MAX_RESPONSE_SIZE)? {
+        cf_args.max_response_size = size;
+    }
+    if let Some(retries) = args.get_one::<usize>(ARG_REQUEST_RETRIES) {
+        cf_args.request_retries = *retries;
+    }
+    if let Some(codes) = args.get_many::<u8>(ARG_RETRY_ON_CODES) {
+        cf_args.retry_on_codes = Some(codes.copied().collect());
+    }
+    if let Some(ratio) = args.get_one::<f64>(ARG_RETRY_BUDGET) {
+        if !(*ratio > 0.0 && *ratio <= 1.0) {
+            return Err(anyhow!("the retry budget ratio should be in range (0, 1]"));
+        }
+        cf_args.retry_budget = Some(KeylessRetryBudget::new(*ratio));
+    }
+    if let Some(timeout) = g3_clap::humanize::get_duration(args, ARG_TIMEOUT)? {
+        cf_args.timeout = timeout;
+    }
+    if let Some(factor) = args.get_one::<f64>(ARG_ADAPTIVE_TIMEOUT) {
+        if !(*factor >= 1.0 && factor.is_finite()) {
+            return Err(anyhow!(
+                "the adaptive timeout factor should be at least 1.0"
+            ));
+        }
+        let warmup = args
+            .get_one::<u64>(ARG_ADAPTIVE_TIMEOUT_WARMUP)
+            .copied()
+            .unwrap_or(1000);
+        let min = g3_clap::humanize::get_duration(args, ARG_ADAPTIVE_TIMEOUT_MIN)?
+            .unwrap_or(Duration::from_millis(10));
+        if min > cf_args.timeout {
+            return Err(anyhow!(
+                "the min adaptive timeout should not be larger than the request timeout"
+            ));
+        }
+        cf_args.adaptive_timeout = Some(KeylessAdaptiveTimeout::new(
+            *factor,
+            warmup,
+            min,
+            cf_args.timeout,
+        ));
+    }
+
+    if args.get_flag(ARG_NO_MULTIPLEX) {
+        cf_args.no_multiplex = true;
+    }
+    if args.get_flag(ARG_SERVER_CHOOSE_RSA_PADDING) {
+        if cf_args.global.action.rsa_padding().is_none() {
+            return Err(anyhow!(
+                "the server can only choose padding for rsa actions"
+            ));
+        }
+        cf_args.server_choose_rsa_padding = true;
+    }
+    if args.get_flag(ARG_VERIFY_OPCODE) {
+        cf_args.verify_opcode = true;
+    }
+    if let Some(path) = args.get_one::<PathBuf>(ARG_REQUEST_HMAC_KEY_FILE) {
+        let key = load_hmac_key_file(path)?;
+        let (name, digest) = match args.get_one::<String>(ARG_REQUEST_HMAC_DIGEST) {
+            Some(s) if s == "sha1" => ("sha1", MessageDigest::sha1()),
+            Some(s) if s == "sha384" => ("sha384", MessageDigest::sha384()),
+            Some(s) if s == "sha512" => ("sha512", MessageDigest::sha512()),
+            _ => ("sha256", MessageDigest::sha256()),
+        };
+        let hmac = KeylessRequestHmac::new(digest, &key)?;
+        cf_args.request_hmac = Some((name, Arc::new(hmac)));
+    }
+    if let Some(phases) = args.get_many::<String>(ARG_PHASE) {
+        for s in phases {
+            let phase = KeylessBenchPhase::from_str(s)?;
+            cf_args.phases.push(phase);
+        }
+    }
+    if let Some(count) = args.get_one::<usize>(ARG_CHECK_KEY_ROUTING) {
+        if *count == 0 {
+            return Err(anyhow!("the key routing check count should not be 0"));
+        }
+        cf_args.check_key_routing = Some(*count);
+    }
+    if let Some(count) = args.get_one::<usize>(ARG_COLD_WARM) {
+        if cf_args.global.multi_keys.is_empty() {
+            return Err(anyhow!(
+                "the key directory should be set for the cold-warm check"
+            ));
+        }
+        if *count == 0 {
+            return Err(anyhow!("the warm request count should not be 0"));
+        }
+        cf_args.cold_warm = Some(*count);
+    }
+    if args.get_flag(ARG_PROBE) {
+        cf_args.probe = true;
+        cf_args.warn_latency = g3_clap::humanize::get_duration(args, ARG_WARN_LATENCY)?;
+        cf_args.crit_latency = g3_clap::humanize::get_duration(args, ARG_CRIT_LATENCY)?;
+        if let (Some(warn), Some(crit)) = (cf_args.warn_latency, cf_args.crit_latency) {
+            if warn > crit {
+                return Err(anyhow!(
+                    "the warn latency should not be larger than the crit latency"
+                ));
+            }
+        }
+    }
+    if args.get_flag(ARG_NO_PAYLOAD_REUSE) {
+        cf_args.no_payload_reuse = true;
+    }
+    if args.get_flag(ARG_CONNECT_ONLY) {
+        cf_args.connect_only = true;
+    }
+    if args.get_flag(ARG_EXPLAIN) {
+        cf_args.explain = true;
+        cf_args.confirm = args.get_flag(ARG_CONFIRM);
+    }
+    if let Some(control) = args.get_one::<UpstreamAddr>(ARG_CONTROL_TARGET) {
+        check_target_port(control).context("invalid control target")?;
+        cf_args.control_target = Some(control.clone());
+    }
+    if let Some(dir) = args.get_one::<PathBuf>(ARG_ARTIFACT_DIR) {
+        cf_args.artifact_dir = Some(dir.clone());
+    }
+    if let Some(s) = args.get_one::<String>(ARG_START_AT) {
+        cf_args.start_at = Some(parse_start_at(s)?);
+    }
+    if let Some(path) = args.get_one::<PathBuf>(ARG_HDR_OUTPUT) {
+        cf_args.hdr_output = Some(path.clone());
+    }
+    if let Some(dir) = args.get_one::<PathBuf>(ARG_DUMP_PEER_CHAIN) {
+        cf_args.peer_chain_dumper = Some(KeylessPeerChainDumper::new(dir)?);
+    }
+    if let Some(path) = args.get_one::<PathBuf>(ARG_RECORD) {
+        if !cf_args.global.multi_keys.is_empty() {
+            return Err(anyhow!("requests can not be recorded with multiple keys"));
+        }
+        let action = format!("{:?}", cf_args.global.action);
+        cf_args.recorder = Some(KeylessRecordWriter::create(path, action)?);
+    }
+    if let Some(path) = args.get_one::<PathBuf>(ARG_REQUEST_CSV) {
+        if !cf_args.global.multi_keys.is_empty() {
+            return Err(anyhow!("request csv can not be written with multiple keys"));
+        }
+        let action = format!("{:?}", cf_args.global.action);
+        cf_args.request_csv = Some(KeylessRequestCsvWriter::create(path, action)?);
+    }
+    if let Some(path) = args.get_one::<PathBuf>(ARG_REPLAY_FILE) {
+        cf_args.replay_file = Some(path.clone());
+        cf_args.replay_timing = args.get_flag(ARG_REPLAY_TIMING);
+    }
+    if let Some(dir) = args.get_one::<PathBuf>(ARG_BASELINE_DIR) {
+        let baseline = super::artifact::load_baseline(dir)?;
+        cf_args.baseline = Some(baseline);
+    }
+    if args.get_flag(ARG_LOCAL_TIMING) && cf_args.global.private_key.is_some() {
+        cf_args.local_timing = true;
+    }
+
+    cf_args
+        .tls
+        .parse_tls_args(args)
+        .context("invalid tls config")?;
+    cf_args.tls.verbose_errors = cf_args.global.openssl_errors;
+    if let Some(ratio) = args.get_one::<f64>(ARG_TLS_RATIO) {
+        if !(0.0..=1.0).contains(ratio) {
+            return Err(anyhow!("the tls ratio should be in range [0, 1]"));
+        }
+        cf_args.tls_ratio = Some(*ratio);
+    }
+    if let Some(names) = args.get_many::<String>(ARG_SNI_LIST) {
+        let mut hosts = Vec::new();
+        for name in names {
+            let host = Host::from_str(name).map_err(|e| anyhow!("invalid sni {name}: {e}"))?;
+            hosts.push(host);
+        }
+        cf_args.sni_rotation = Some(KeylessSniRotation::new(hosts)?);
+    }
+    if let Some(path) = args.get_one::<PathBuf>(ARG_SESSION_CACHE_FILE) {
+        let mut file = KeylessSessionCacheFile::new(path.clone());
+        if let Some(tls_client) = &cf_args.tls.client {
+            file.load(tls_client).context(format!(
+                "failed to load tls sessions from file {}",
+                path.display()
+            ))?;
+        }
+        cf_args.session_cache_file = Some(file);
+    }
+    if args.get_flag(ARG_UDP) {
+        let dtls_client = cf_args.tls.build_dtls_client()?;
+        cf_args.dtls_client = Some(dtls_client);
+        if let Some(interval) = g3_clap::humanize::get_duration(args, ARG_UDP_RETRANSMIT)? {
+            cf_args.udp_retransmit = interval;
+        }
+    }
+    cf_args
+        .proxy_protocol
+        .parse_args(args)
+        .context("invalid proxy protocol config")?;
+    if cf_args.dtls_client.is_some() && cf_args.proxy_protocol.data().is_some() {
+        return Err(anyhow!("proxy protocol is not supported over udp"));
+    }
+    cf_args
+        .otlp
+        .parse_args(args)
+        .context("invalid otlp config")?;
+
+    Ok(cf_args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::keyless::opts::{KeylessRsaPadding, KeylessSignDigest};
+
+    #[test]
+    fn target_port() {
+        let addr = UpstreamAddr::from_str("keyless.example.net").unwrap();
+        assert!(check_target_port(&addr).is_err());
+        let addr = UpstreamAddr::from_str("127.0.0.1").unwrap();
+        assert!(check_target_port(&addr).is_err());
+
+        let addr = UpstreamAddr::from_str("keyless.example.net:2407").unwrap();
+        assert!(check_target_port(&addr).is_ok());
+        assert_eq!(addr.port(), 2407);
+        let addr = UpstreamAddr::from_str("127.0.0.1:2407").unwrap();
+        assert!(check_target_port(&addr).is_ok());
+
+        let addr = UpstreamAddr::from_str("[::1]").unwrap();
+        assert!(check_target_port(&addr).is_err());
+        let addr = UpstreamAddr::from_str("::1").unwrap();
+        assert!(check_target_port(&addr).is_err());
+        let addr = UpstreamAddr::from_str("[::1]:2407").unwrap();
+        assert!(check_target_port(&addr).is_ok());
+        assert_eq!(addr.port(), 2407);
+    }
+
+    #[test]
+    fn connect_timeout() {
+        let timeout = Duration::from_secs(10);
+        let interval = Duration::from_millis(100);
+        assert_eq!(total_connect_timeout(timeout, 0, interval), timeout);
+        assert_eq!(
+            total_connect_timeout(timeout, 2, interval),
+            Duration::from_millis(30_200)
+        );
+        assert_eq!(
+            total_connect_timeout(Duration::MAX, u32::MAX, interval),
+            Duration::MAX
+        );
+        assert_eq!(
+            total_connect_timeout(timeout, u32::MAX, Duration::MAX),
+            Duration::MAX
+        );
+    }
+
+    #[test]
+    fn hmac_key_file() {
+        let path = std::env::temp_dir().join(format!("g3bench-hmac-{}", std::process::id()));
+        std::fs::write(&path, "0102ff\n").unwrap();
+        assert_eq!(load_hmac_key_file(&path).unwrap(), [0x01, 0x02, 0xff]);
+        std::fs::write(&path, "\n0102ff\n").unwrap();
+        assert!(load_hmac_key_file(&path).is_err());
+        std::fs::write(&path, "xyz").unwrap();
+        assert!(load_hmac_key_file(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(load_hmac_key_file(&path).is_err());
+    }
+
+    #[test]
+    fn remote_action() {
+        let digest = KeylessSignDigest::from_str("sha3-256").unwrap();
+        assert!(check_remote_action(KeylessAction::EcdsaSign(digest), None).is_err());
+        let digest = KeylessSignDigest::from_str("sha256").unwrap();
+        assert!(check_remote_action(KeylessAction::EcdsaSign(digest), None).is_ok());
+
+        let action = KeylessAction::RsaSign(digest, KeylessRsaPadding::Pss);
+        assert!(check_remote_action(action, None).is_ok());
+        let saltlen = Some(KeylessRsaPssSaltlen::Max);
+        assert!(check_remote_action(action, saltlen).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use ahash::AHashSet;
+use anyhow::anyhow;
+use openssl::ssl::SslRef;
+
+/// dump the certificate chain presented by each distinct peer to a sub dir
+pub(super) struct KeylessPeerChainDumper {
+    dir: PathBuf,
+    dumped: Mutex<AHashSet<SocketAddr>>,
+}
+
+impl KeylessPeerChainDumper {
+    pub(super) fn new(dir: &Path) -> anyhow::Result<Self> {
+        fs::create_dir_all(dir)
+            .map_err(|e| anyhow!("failed to create peer chain dir {}: {e}", dir.display()))?;
+        Ok(KeylessPeerChainDumper {
+            dir: dir.to_path_buf(),
+            dumped: Mutex::new(AHashSet::new()),
+        })
+    }
+
+    /// dump the chain if this is the first successful handshake with the peer
+    pub(super) fn dump(&self, peer: SocketAddr, ssl: &SslRef) {
+        if !self.dumped.lock().unwrap().insert(peer) {
+            return;
+        }
+        if let Err(e) = self.dump_chain(peer, ssl) {
+            eprintln!("WARN: failed to dump the certificate chain of peer {peer}: {e}");
+        }
+    }
+
+    fn dump_chain(&self, peer: SocketAddr, ssl: &SslRef) -> anyhow::Result<()> {
+        let Some(chain) = ssl.peer_cert_chain() else {
+            return Err(anyhow!("no certificate chain presented"));
+        };
+
+        let dir = self.dir.join(format!("{}_{}", peer.ip(), peer.port()));
+        fs::create_dir_all(&dir)
+            .map_err(|e| anyhow!("failed to create dir {}: {e}", dir.display()))?;
+        for (i, cert) in chain.iter().enumerate() {
+            let pem = cert
+                .to_pem()
+                .map_err(|e| anyhow!("failed to encode certificate #{i}: {e}"))?;
+            let path = dir.join(format!("{i}.pem"));
+            fs::write(&path, pem)
+                .map_err(|e| anyhow!("failed to write file {}: {e}", path.display()))?;
+        }
+        Ok(())
+    }
+}
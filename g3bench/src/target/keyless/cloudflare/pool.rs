@@ -0,0 +1,279 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures_util::future;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use super::{
+    KeylessCloudflareArgs, KeylessHistogramRecorder, KeylessRequest, KeylessRuntimeStats,
+    MultiplexTransfer, ProcArgs, SendRequestError,
+};
+
+struct KeylessConnectionUnlocked {
+    args: Arc<KeylessCloudflareArgs>,
+    proc_args: Arc<ProcArgs>,
+    index: usize,
+    save: Option<Arc<MultiplexTransfer>>,
+    runtime_stats: Arc<KeylessRuntimeStats>,
+    histogram_recorder: KeylessHistogramRecorder,
+    reuse_conn_count: u64,
+}
+
+impl Drop for KeylessConnectionUnlocked {
+    fn drop(&mut self) {
+        self.histogram_recorder
+            .record_conn_reuse_count(self.reuse_conn_count);
+        self.reuse_conn_count = 0;
+    }
+}
+
+impl KeylessConnectionUnlocked {
+    fn new(
+        args: Arc<KeylessCloudflareArgs>,
+        proc_args: Arc<ProcArgs>,
+        index: usize,
+        runtime_stats: Arc<KeylessRuntimeStats>,
+        histogram_recorder: KeylessHistogramRecorder,
+    ) -> Self {
+        KeylessConnectionUnlocked {
+            args,
+            proc_args,
+            index,
+            save: None,
+            runtime_stats,
+            histogram_recorder,
+            reuse_conn_count: 0,
+        }
+    }
+
+    async fn fetch_handle(&mut self) -> anyhow::Result<Arc<MultiplexTransfer>> {
+        if let Some(handle) = &self.save {
+            if !handle.is_closed() {
+                self.reuse_conn_count += 1;
+                return Ok(handle.clone());
+            }
+            self.save = None;
+        }
+
+        self.histogram_recorder
+            .record_conn_reuse_count(self.reuse_conn_count);
+        self.reuse_conn_count = 0;
+
+        let peer = self.args.select_target_addr(&self.proc_args)?;
+        let use_tls = self.args.select_tls();
+        self.runtime_stats.add_conn_attempt();
+        let handle = match tokio::time::timeout(
+            self.args.new_connection_timeout(),
+            self.args
+                .new_multiplex_keyless_connection(peer, use_tls, None),
+        )
+        .await
+        {
+            Ok(Ok(h)) => Arc::new(h),
+            Ok(Err(e)) => {
+                self.runtime_stats.add_target_conn_failed(peer, use_tls);
+                return Err(e.context(format!("P#{} new connection failed", self.index)));
+            }
+            Err(_) => {
+                self.runtime_stats.add_target_conn_failed(peer, use_tls);
+                return Err(anyhow!("timeout to get new connection"));
+            }
+        };
+        self.runtime_stats.add_conn_success();
+        self.save = Some(handle.clone());
+        Ok(handle)
+    }
+}
+
+struct KeylessConnection {
+    inner: Mutex<KeylessConnectionUnlocked>,
+}
+
+impl KeylessConnection {
+    fn new(
+        args: Arc<KeylessCloudflareArgs>,
+        proc_args: Arc<ProcArgs>,
+        index: usize,
+        runtime_stats: Arc<KeylessRuntimeStats>,
+        histogram_recorder: KeylessHistogramRecorder,
+    ) -> Self {
+        KeylessConnection {
+            inner: Mutex::new(KeylessConnectionUnlocked::new(
+                args,
+                proc_args,
+                index,
+                runtime_stats,
+                histogram_recorder,
+            )),
+        }
+    }
+
+    async fn fetch_handle(&self) -> anyhow::Result<Arc<MultiplexTransfer>> {
+        let mut inner = self.inner.lock().await;
+        inner.fetch_handle().await
+    }
+
+    async fn warmup(
+        &self,
+        requests: &[KeylessRequest],
+        timeout: Duration,
+    ) -> anyhow::Result<String> {
+        let handle = self.fetch_handle().await?;
+        let time_start = Instant::now();
+        for req in requests {
+            match tokio::time::timeout(timeout, handle.send_request(req.clone())).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(
+                    SendRequestError::InvalidRequest(id, e)
+                    | SendRequestError::InvalidResponse(id, e),
+                )) => {
+                    return Err(anyhow!("{}/{id} error: {e}", handle.local_addr()));
+                }
+                Ok(Err(SendRequestError::NoResponse(id))) => {
+                    return match handle.fetch_error() {
+                        Some(e) => Err(anyhow!("{}/{id} error: {e}", handle.local_addr())),
+                        None => Err(anyhow!(
+                            "{}/{id}: we get no response but no error reported",
+                            handle.local_addr()
+                        )),
+                    };
+                }
+                Err(_) => return Err(anyhow!("{}: request timed out", handle.local_addr())),
+            }
+        }
+        let transport = if handle.is_tls() { "tls" } else { "tcp" };
+        Ok(format!(
+            "{} -> {} ({transport}) passed in {:?}",
+            handle.local_addr(),
+            handle.peer_addr(),
+            time_start.elapsed()
+        ))
+    }
+}
+
+pub(super) struct KeylessConnectionPool {
+    pool: Vec<KeylessConnection>,
+    pool_size: usize,
+    cur_index: AtomicUsize,
+    seeded_rng: Option<std::sync::Mutex<StdRng>>,
+}
+
+impl KeylessConnectionPool {
+    pub(super) fn new(
+        args: &Arc<KeylessCloudflareArgs>,
+        proc_args: &Arc<ProcArgs>,
+        pool_size: usize,
+        runtime_stats: &Arc<KeylessRuntimeStats>,
+        histogram_recorder: &KeylessHistogramRecorder,
+    ) -> Self {
+        let mut pool = Vec::with_capacity(pool_size);
+        for i in 0..pool_size {
+            pool.push(KeylessConnection::new(
+                args.clone(),
+                proc_args.clone(),
+                i,
+                runtime_stats.clone(),
+                histogram_recorder.clone(),
+            ));
+        }
+
+        KeylessConnectionPool {
+            pool,
+            pool_size,
+            cur_index: AtomicUsize::new(0),
+            seeded_rng: proc_args
+                .seed
+                .map(|seed| std::sync::Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    /// assign a fixed connection to the new task if a seed is set.
+    /// the task contexts are created serially, so the result will be the same across runs
+    pub(super) fn assign_index(&self) -> Option<usize> {
+        let rng = self.seeded_rng.as_ref()?;
+        if self.pool_size == 0 {
+            return None;
+        }
+        let mut rng = rng.lock().unwrap();
+        Some(rng.gen_range(0..self.pool_size))
+    }
+
+    /// send the real requests on each pooled connection, fail if any of them failed
+    pub(super) async fn warmup(&self, args: &KeylessCloudflareArgs) -> anyhow::Result<()> {
+        let requests = args.build_requests()?;
+        let results =
+            future::join_all(self.pool.iter().map(|c| c.warmup(&requests, args.timeout))).await;
+
+        let mut failed = 0;
+        for (i, r) in results.into_iter().enumerate() {
+            match r {
+                Ok(s) => println!("P#{i} warmup: {s}"),
+                Err(e) => {
+                    failed += 1;
+                    println!("P#{i} warmup failed: {e:?}");
+                }
+            }
+        }
+        if failed > 0 {
+            return Err(anyhow!(
+                "{failed} of {} pooled connection(s) failed the warmup",
+                self.pool_size
+            ));
+        }
+        Ok(())
+    }
+
+    pub(super) async fn fetch_handle(
+        &self,
+        index: Option<usize>,
+    ) -> anyhow::Result<Arc<MultiplexTransfer>> {
+        if let Some(c) = index.and_then(|i| self.pool.get(i)) {
+            return c.fetch_handle().await;
+        }
+
+        match self.pool_size {
+            0 => Err(anyhow!("no connections configured for this pool")),
+            1 => self.pool[0].fetch_handle().await,
+            _ => {
+                let mut indent = self.cur_index.load(Ordering::Acquire);
+                loop {
+                    let mut next = indent + 1;
+                    if next >= self.pool_size {
+                        next = 0;
+                    }
+
+                    match self.cur_index.compare_exchange(
+                        indent,
+                        next,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                    ) {
+                        Ok(_) => return self.pool.get(indent).unwrap().fetch_handle().await,
+                        Err(v) => indent = v,
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use serde_json::{json, Value};
+use tokio::time::Instant;
+
+use super::{KeylessCloudflareArgs, KeylessRequest, KeylessResponseError, ProcArgs};
+
+/// write each request and its result as a json line, which can be replayed later
+pub(super) struct KeylessRecordWriter {
+    action: String,
+    time_start: Instant,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl KeylessRecordWriter {
+    pub(super) fn create(path: &Path, action: String) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .map_err(|e| anyhow!("failed to create record file {}: {e}", path.display()))?;
+        Ok(KeylessRecordWriter {
+            action,
+            time_start: Instant::now(),
+            file: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub(super) fn record(
+        &self,
+        task_id: usize,
+        request: &KeylessRequest,
+        result: Result<&[u8], &anyhow::Error>,
+    ) {
+        let mut record = json!({
+            "time_ms": self.time_start.elapsed().as_millis() as u64,
+            "task": task_id,
+            "action": self.action,
+            "request": hex::encode(request.as_bytes()),
+        });
+        match result {
+            Ok(data) => record["result"] = Value::from(hex::encode(data)),
+            Err(e) => record["error"] = Value::from(format!("{e:?}")),
+        }
+
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = writeln!(file, "{record}") {
+            eprintln!("WARN: failed to write to the record file: {e}");
+        }
+    }
+
+    pub(super) fn flush(&self) -> anyhow::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        file.flush()
+            .map_err(|e| anyhow!("failed to flush the record file: {e}"))
+    }
+}
+
+struct KeylessReplayRecord {
+    time: Duration,
+    request: KeylessRequest,
+    result: Option<Vec<u8>>,
+}
+
+impl KeylessReplayRecord {
+    fn parse(line: &str) -> anyhow::Result<Self> {
+        let v: Value = serde_json::from_str(line).map_err(|e| anyhow!("invalid json: {e}"))?;
+        let time_ms = v
+            .get("time_ms")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| anyhow!("no valid time_ms field found"))?;
+        let request = v
+            .get("request")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("no valid request field found"))?;
+        let request = hex::decode(request).map_err(|e| anyhow!("invalid request hex: {e}"))?;
+        let result = match v.get("result").and_then(|v| v.as_str()) {
+            Some(s) => Some(hex::decode(s).map_err(|e| anyhow!("invalid result hex: {e}"))?),
+            None => None,
+        };
+        Ok(KeylessReplayRecord {
+            time: Duration::from_millis(time_ms),
+            request: KeylessRequest::from_bytes(request)?,
+            result,
+        })
+    }
+}
+
+fn load_replay_file(path: &Path) -> anyhow::Result<Vec<KeylessReplayRecord>> {
+    let file = File::open(path)
+        .map_err(|e| anyhow!("failed to open replay file {}: {e}", path.display()))?;
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| anyhow!("failed to read replay file: {e}"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = KeylessReplayRecord::parse(&line)
+            .map_err(|e| anyhow!("invalid record at line {}: {e}", i + 1))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// re-issue the recorded requests in order on a single connection,
+/// and compare the results with the recorded ones
+pub(super) async fn run_replay(
+    proc_args: &Arc<ProcArgs>,
+    cf_args: Arc<KeylessCloudflareArgs>,
+    path: &Path,
+    keep_timing: bool,
+) -> anyhow::Result<()> {
+    let records = load_replay_file(path)?;
+
+    let peer = cf_args.select_target_addr(proc_args)?;
+    let use_tls = cf_args.select_tls();
+    let mut connection = tokio::time::timeout(
+        cf_args.new_connection_timeout(),
+        cf_args.new_simplex_keyless_connection(peer, use_tls, None),
+    )
+    .await
+    .map_err(|_| anyhow!("timed out to connect to {peer}"))??;
+
+    let timeout = cf_args.request_timeout();
+    let time_start = Instant::now();
+    let mut passed = 0;
+    let mut changed = 0;
+    let mut failed = 0;
+    for (i, mut record) in records.into_iter().enumerate() {
+        if keep_timing {
+            tokio::time::sleep_until(time_start + record.time).await;
+        }
+        let data = match tokio::time::timeout(timeout, connection.send_request(&mut record.request))
+            .await
+        {
+            Ok(Ok(rsp)) => Ok(rsp.into_vec()),
+            Ok(Err(KeylessResponseError::ServerError(e))) => Err(anyhow!("server error: {e}")),
+            Ok(Err(e)) => return Err(anyhow!("replay of record #{i} failed: {e}")),
+            Err(_) => return Err(anyhow!("replay of record #{i} timed out")),
+        };
+        match (data, &record.result) {
+            (Ok(data), Some(expected)) if data.eq(expected) => passed += 1,
+            (Ok(data), _) => {
+                changed += 1;
+                if !proc_args.quiet {
+                    println!("record #{i}: result changed to {}", hex::encode(data));
+                }
+            }
+            (Err(e), Some(_)) => {
+                failed += 1;
+                if !proc_args.quiet {
+                    println!("record #{i}: {e}");
+                }
+            }
+            (Err(_), None) => passed += 1,
+        }
+    }
+
+    println!("# Replay");
+    println!("Same Result:    {passed}");
+    println!("Changed Result: {changed}");
+    println!("Newly Failed:   {failed}");
+    if failed > 0 {
+        Err(anyhow!("{failed} recorded requests failed on replay"))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::target::keyless::cloudflare::KeylessRequestBuilder;
+    use crate::target::keyless::opts::KeylessAction;
+
+    #[test]
+    fn parse_record() {
+        let builder = KeylessRequestBuilder::new(&[0x01; 20], KeylessAction::Ed25519Sign).unwrap();
+        let mut request = builder.build(b"hello").unwrap();
+        request.set_id(3).unwrap();
+
+        let line = json!({
+            "time_ms": 10,
+            "request": hex::encode(request.as_bytes()),
+            "result": "0102",
+        })
+        .to_string();
+        let record = KeylessReplayRecord::parse(&line).unwrap();
+        assert_eq!(record.time, Duration::from_millis(10));
+        assert_eq!(record.request.as_bytes(), request.as_bytes());
+        assert_eq!(record.request.id(), 3);
+        assert_eq!(record.result, Some(vec![0x01, 0x02]));
+
+        assert!(KeylessRequest::from_bytes(request.as_bytes()[..100].to_vec()).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+
+use super::{KeylessResponse, KeylessServerError};
+
+const CSV_BUFFER_SIZE: usize = 64 * 1024;
+
+/// the column names, new columns should only be appended at the end
+const CSV_HEADER: &str = "timestamp_us,task_id,connection,action,latency_us,status,output_len";
+
+/// write one csv row for each completed request
+pub(super) struct KeylessRequestCsvWriter {
+    action: String,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl KeylessRequestCsvWriter {
+    pub(super) fn create(path: &Path, action: String) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .map_err(|e| anyhow!("failed to create csv file {}: {e}", path.display()))?;
+        let mut file = BufWriter::with_capacity(CSV_BUFFER_SIZE, file);
+        writeln!(file, "{CSV_HEADER}").map_err(|e| anyhow!("failed to write csv header: {e}"))?;
+        Ok(KeylessRequestCsvWriter {
+            // the debug format of the action may contain ','
+            action: format!("\"{action}\""),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub(super) fn write_row(
+        &self,
+        task_id: usize,
+        connection: SocketAddr,
+        request_start: SystemTime,
+        r: &anyhow::Result<KeylessResponse>,
+    ) {
+        let latency = request_start.elapsed().unwrap_or_default();
+        let timestamp = request_start
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros();
+        let (status, output_len) = match r {
+            Ok(rsp) => ("ok", rsp.data().len()),
+            Err(e) if e.downcast_ref::<KeylessServerError>().is_some() => ("server_error", 0),
+            Err(_) => ("error", 0),
+        };
+
+        let mut file = self.file.lock().unwrap();
+        if let Err(e) = writeln!(
+            file,
+            "{timestamp},{task_id},{connection},{},{},{status},{output_len}",
+            self.action,
+            latency.as_micros()
+        ) {
+            eprintln!("WARN: failed to write to the csv file: {e}");
+        }
+    }
+
+    pub(super) fn flush(&self) -> anyhow::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        file.flush()
+            .map_err(|e| anyhow!("failed to flush the csv file: {e}"))
+    }
+}
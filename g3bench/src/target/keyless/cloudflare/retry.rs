@@ -0,0 +1,124 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// the min number of retries allowed if the total requests is not known,
+/// so the budget will not be exhausted by the failures at the start
+const MIN_RETRY_BUDGET: u64 = 10;
+
+/// Global retry budget.
+///
+/// The retries will be limited to `ratio` of the total requests, or of the requests that have
+/// been sent if the total is not known. Once exhausted, no more retries will be allowed for the
+/// rest of the run.
+pub(super) struct KeylessRetryBudget {
+    ratio: f64,
+    total: Option<u64>,
+    requests: AtomicU64,
+    retries: AtomicU64,
+    exhausted: AtomicBool,
+}
+
+impl KeylessRetryBudget {
+    pub(super) fn new(ratio: f64) -> Self {
+        KeylessRetryBudget {
+            ratio,
+            total: None,
+            requests: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            exhausted: AtomicBool::new(false),
+        }
+    }
+
+    pub(super) fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    pub(super) fn set_total_requests(&mut self, total: Option<usize>) {
+        self.total = total.map(|v| v as u64);
+    }
+
+    fn budget(&self) -> u64 {
+        match self.total {
+            Some(total) => (total as f64 * self.ratio) as u64,
+            None => {
+                let requests = self.requests.load(Ordering::Relaxed);
+                ((requests as f64 * self.ratio) as u64).max(MIN_RETRY_BUDGET)
+            }
+        }
+    }
+
+    pub(super) fn add_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// take one retry from the budget, return false if the budget has been exhausted
+    pub(super) fn acquire(&self) -> bool {
+        if self.exhausted.load(Ordering::Relaxed) {
+            return false;
+        }
+        let retries = self.retries.fetch_add(1, Ordering::Relaxed) + 1;
+        if retries > self.budget() {
+            self.retries.fetch_sub(1, Ordering::Relaxed);
+            self.exhausted.store(true, Ordering::Relaxed);
+            false
+        } else {
+            true
+        }
+    }
+
+    pub(super) fn summary(&self) {
+        let used = self.retries.load(Ordering::Relaxed);
+        let budget = self.budget();
+        println!("# Retry Budget");
+        println!("Used:      {used}/{budget}");
+        if budget > 0 {
+            println!("Consumed:  {:.2}%", (used as f64 / budget as f64) * 100.0);
+        }
+        println!("Exhausted: {}", self.exhausted.load(Ordering::Relaxed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exhausted() {
+        let mut b = KeylessRetryBudget::new(0.1);
+        b.set_total_requests(Some(100));
+        for _ in 0..10 {
+            assert!(b.acquire());
+        }
+        assert!(!b.acquire());
+        assert!(b.exhausted.load(Ordering::Relaxed));
+
+        let b = KeylessRetryBudget::new(0.1);
+        for _ in 0..200 {
+            b.add_request();
+        }
+        for _ in 0..20 {
+            assert!(b.acquire());
+        }
+        assert!(!b.acquire());
+        // no more retries even if there are new requests
+        for _ in 0..100 {
+            b.add_request();
+        }
+        assert!(!b.acquire());
+    }
+}
@@ -0,0 +1,161 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+
+use super::{KeylessCloudflareArgs, KeylessRequest, KeylessResponseError, ProcArgs};
+
+#[derive(Default)]
+struct KeyRoutingResult {
+    passed: u64,
+    failed: u64,
+}
+
+#[derive(Default)]
+struct PeerRoutingResult {
+    connections: u64,
+    conn_failed: u64,
+    keys: Vec<KeyRoutingResult>,
+}
+
+/// open `count` new connections, and send a request for each key on each of them,
+/// to find out the backends which do not accept some of the keys
+pub(super) async fn run_key_routing_check(
+    proc_args: &Arc<ProcArgs>,
+    cf_args: Arc<KeylessCloudflareArgs>,
+    count: usize,
+) -> anyhow::Result<()> {
+    let skis: Vec<&[u8]> = if cf_args.global.multi_keys.is_empty() {
+        vec![cf_args.global.subject_key_id()]
+    } else {
+        cf_args
+            .global
+            .multi_keys
+            .iter()
+            .map(|k| k.ski.as_slice())
+            .collect()
+    };
+    let mut requests = Vec::with_capacity(skis.len());
+    for ski in &skis {
+        let builder = cf_args.new_request_builder(ski)?;
+        requests.push(builder.build(&cf_args.global.payload)?);
+    }
+
+    let mut results: BTreeMap<SocketAddr, PeerRoutingResult> = BTreeMap::new();
+    for _ in 0..count {
+        let peer = cf_args.select_target_addr(proc_args)?;
+        let use_tls = cf_args.select_tls();
+        let peer_result = results.entry(peer).or_default();
+        if peer_result.keys.is_empty() {
+            peer_result.keys.resize_with(skis.len(), Default::default);
+        }
+        peer_result.connections += 1;
+
+        match check_connection(&cf_args, peer, use_tls, &mut requests).await {
+            Ok(passed) => {
+                for (r, passed) in peer_result.keys.iter_mut().zip(passed) {
+                    if passed {
+                        r.passed += 1;
+                    } else {
+                        r.failed += 1;
+                    }
+                }
+            }
+            Err(e) => {
+                peer_result.conn_failed += 1;
+                if !proc_args.quiet {
+                    eprintln!("failed to connect to {peer}: {e}");
+                }
+            }
+        }
+    }
+
+    println!("# Key Routing");
+    print!(
+        "{:<40} {:>11} {:>10}",
+        "Address", "Connections", "ConnFailed"
+    );
+    for ski in &skis {
+        print!(" {:>17}", short_ski(ski));
+    }
+    println!();
+    let mut all_passed = true;
+    for (peer, r) in &results {
+        print!(
+            "{:<40} {:>11} {:>10}",
+            peer.to_string(),
+            r.connections,
+            r.conn_failed
+        );
+        for k in &r.keys {
+            let status = if k.failed > 0 || k.passed == 0 {
+                all_passed = false;
+                "FAIL"
+            } else {
+                "PASS"
+            };
+            let cell = format!("{status} {}/{}", k.passed, k.passed + k.failed);
+            print!(" {cell:>17}");
+        }
+        println!();
+    }
+
+    if all_passed {
+        Ok(())
+    } else {
+        Err(anyhow!("some keys are not accepted by all the backends"))
+    }
+}
+
+async fn check_connection(
+    cf_args: &KeylessCloudflareArgs,
+    peer: SocketAddr,
+    use_tls: bool,
+    requests: &mut [KeylessRequest],
+) -> anyhow::Result<Vec<bool>> {
+    let mut connection = tokio::time::timeout(
+        cf_args.new_connection_timeout(),
+        cf_args.new_simplex_keyless_connection(peer, use_tls, None),
+    )
+    .await
+    .map_err(|_| anyhow!("timed out to connect"))??;
+
+    let timeout = cf_args.request_timeout();
+    let total = requests.len();
+    let mut passed = Vec::with_capacity(total);
+    for req in requests {
+        match tokio::time::timeout(timeout, connection.send_request(req)).await {
+            Ok(Ok(_)) => passed.push(true),
+            Ok(Err(KeylessResponseError::ServerError(_))) => passed.push(false),
+            _ => {
+                // the connection is not usable any more
+                passed.resize(total, false);
+                break;
+            }
+        }
+    }
+    Ok(passed)
+}
+
+/// the first 8 bytes of the ski in hex, which should be enough to tell the keys apart
+fn short_ski(ski: &[u8]) -> String {
+    let len = ski.len().min(8);
+    hex::encode(&ski[..len])
+}
@@ -0,0 +1,237 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use openssl::ssl::{SslRef, SslSession};
+
+use g3_types::net::OpensslClientConfig;
+
+/// Persist the TLS client sessions to a file, so the next run can resume them.
+///
+/// The file contains one hex encoded DER session per line, the expired ones will be skipped
+/// when loading, and the new handshakes will be counted as resumed or full.
+pub(super) struct KeylessSessionCacheFile {
+    path: PathBuf,
+    loaded: usize,
+    expired: usize,
+    resumed: AtomicU64,
+    full: AtomicU64,
+}
+
+impl KeylessSessionCacheFile {
+    pub(super) fn new(path: PathBuf) -> Self {
+        KeylessSessionCacheFile {
+            path,
+            loaded: 0,
+            expired: 0,
+            resumed: AtomicU64::new(0),
+            full: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub(super) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// load the sessions into the client session cache, it's ok if the file does not exist yet
+    pub(super) fn load(&mut self, tls_client: &OpensslClientConfig) -> anyhow::Result<()> {
+        if !self.path.exists() {
+            return Ok(());
+        }
+        let (sessions, expired) = load_sessions(&self.path, unix_now())?;
+        self.loaded = sessions.len();
+        self.expired = expired;
+        tls_client.import_sessions(sessions)
+    }
+
+    pub(super) fn record_handshake(&self, ssl: &SslRef) {
+        if ssl.session_reused() {
+            self.resumed.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.full.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// save the sessions in the client session cache and return the count
+    pub(super) fn save(&self, tls_client: &OpensslClientConfig) -> anyhow::Result<usize> {
+        let sessions = tls_client.export_sessions();
+        let mut contents = String::new();
+        for s in &sessions {
+            let der = s
+                .to_der()
+                .map_err(|e| anyhow!("failed to encode tls session: {e}"))?;
+            contents.push_str(&hex::encode(der));
+            contents.push('\n');
+        }
+        write_private_file(&self.path, contents.as_bytes())
+            .map_err(|e| anyhow!("failed to write file {}: {e}", self.path.display()))?;
+        Ok(sessions.len())
+    }
+
+    pub(super) fn summary(&self, saved: usize) {
+        println!("# TLS Session Cache File");
+        println!("Loaded:   {}", self.loaded);
+        println!("Expired:  {}", self.expired);
+        println!("Saved:    {saved}");
+        println!("Resumed:  {}", self.resumed.load(Ordering::Relaxed));
+        println!("Full:     {}", self.full.load(Ordering::Relaxed));
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// the sessions can be used to resume the connections, so only the owner should be able to
+/// read them. The contents is written to a temp file first and then renamed, so the readers
+/// will never see a partially written file
+fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_os_string();
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let r = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .and_then(|mut f| {
+            f.write_all(contents)?;
+            f.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&tmp_path, path));
+    if r.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    r
+}
+
+fn load_sessions(path: &Path, now: i64) -> anyhow::Result<(Vec<SslSession>, usize)> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("failed to read file {}: {e}", path.display()))?;
+    parse_sessions(&contents, now)
+}
+
+/// parse the hex encoded DER sessions, and return the valid ones with the count of expired ones
+fn parse_sessions(contents: &str, now: i64) -> anyhow::Result<(Vec<SslSession>, usize)> {
+    let mut sessions = Vec::new();
+    let mut expired = 0;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let der = hex::decode(line).map_err(|e| anyhow!("invalid hex at line {}: {e}", i + 1))?;
+        let session = SslSession::from_der(&der)
+            .map_err(|e| anyhow!("invalid tls session at line {}: {e}", i + 1))?;
+        if session.time() as i64 + session.timeout() as i64 <= now {
+            expired += 1;
+            continue;
+        }
+        sessions.push(session);
+    }
+    Ok((sessions, expired))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+
+    use g3_tls_cert::builder::RootCertBuilder;
+    use openssl::ssl::{SslAcceptor, SslConnector, SslMethod, SslVerifyMode, SslVersion};
+
+    /// do a local TLS 1.2 handshake to get a real client session
+    fn new_session() -> SslSession {
+        let builder = RootCertBuilder::new_ec256().unwrap();
+        let cert = builder.build(None).unwrap();
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor.set_private_key(builder.pkey()).unwrap();
+        let acceptor = acceptor.build();
+
+        let (client_stream, server_stream) = UnixStream::pair().unwrap();
+        let server = std::thread::spawn(move || {
+            let _ = acceptor.accept(server_stream);
+        });
+
+        let mut connector = SslConnector::builder(SslMethod::tls_client()).unwrap();
+        connector.set_verify(SslVerifyMode::NONE);
+        connector
+            .set_max_proto_version(Some(SslVersion::TLS1_2))
+            .unwrap();
+        let stream = connector
+            .build()
+            .connect("localhost", client_stream)
+            .unwrap();
+        let session = stream.ssl().session().unwrap().to_owned();
+        drop(stream);
+        server.join().unwrap();
+        session
+    }
+
+    #[test]
+    fn expired_split() {
+        let session = new_session();
+        let expire = session.time() as i64 + session.timeout() as i64;
+        let contents = format!(
+            "{}\n\n{}\n",
+            hex::encode(session.to_der().unwrap()),
+            hex::encode(session.to_der().unwrap())
+        );
+
+        let (sessions, expired) = parse_sessions(&contents, expire - 1).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(expired, 0);
+
+        let (sessions, expired) = parse_sessions(&contents, expire).unwrap();
+        assert!(sessions.is_empty());
+        assert_eq!(expired, 2);
+    }
+
+    #[test]
+    fn private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("g3bench-session-{}", std::process::id()));
+        std::fs::write(&path, "old").unwrap();
+        write_private_file(&path, b"new").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_line() {
+        let e = parse_sessions("xyz\n", 0).unwrap_err();
+        assert!(e.to_string().starts_with("invalid hex at line 1"));
+
+        let e = parse_sessions("\n0011\n", 0).unwrap_err();
+        assert!(e.to_string().starts_with("invalid tls session at line 2"));
+    }
+}
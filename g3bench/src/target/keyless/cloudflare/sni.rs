@@ -0,0 +1,118 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use hdrhistogram::Histogram;
+
+use g3_types::ext::DurationExt;
+use g3_types::net::Host;
+
+/// Rotate the TLS server name for each new connection.
+///
+/// The handshake time is recorded for each server name, so the cold cache cost of the per SNI
+/// certificate selection on the server side can be compared.
+pub(super) struct KeylessSniRotation {
+    names: Vec<Host>,
+    next: AtomicUsize,
+    handshake_time: Mutex<Vec<Histogram<u64>>>,
+}
+
+impl KeylessSniRotation {
+    pub(super) fn new(names: Vec<Host>) -> anyhow::Result<Self> {
+        if names.is_empty() {
+            return Err(anyhow!("the sni list should not be empty"));
+        }
+        let mut handshake_time = Vec::with_capacity(names.len());
+        for _ in 0..names.len() {
+            let h = Histogram::new(3)
+                .map_err(|e| anyhow!("failed to create handshake histogram: {e}"))?;
+            handshake_time.push(h);
+        }
+        Ok(KeylessSniRotation {
+            names,
+            next: AtomicUsize::new(0),
+            handshake_time: Mutex::new(handshake_time),
+        })
+    }
+
+    #[inline]
+    pub(super) fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// select the next server name, the index should be used when recording the handshake time
+    pub(super) fn select(&self) -> (usize, &Host) {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.names.len();
+        (i, &self.names[i])
+    }
+
+    pub(super) fn record_handshake(&self, index: usize, time: Duration) {
+        let mut handshake_time = self.handshake_time.lock().unwrap();
+        if let Some(h) = handshake_time.get_mut(index) {
+            let _ = h.record(time.as_nanos_u64());
+        }
+    }
+
+    pub(super) fn summary(&self) {
+        let handshake_time = self.handshake_time.lock().unwrap();
+        println!("# TLS Handshake by SNI");
+        println!(
+            "{:<40} {:>10} {:>10} {:>10} {:>10} {:>10}",
+            "SNI", "Count", "Min", "Mean", "pct90", "Max"
+        );
+        for (name, h) in self.names.iter().zip(handshake_time.iter()) {
+            let t_min = Duration::from_nanos(h.min());
+            let t_mean = Duration::from_nanos(h.mean() as u64);
+            let t_pct90 = Duration::from_nanos(h.value_at_quantile(0.90));
+            let t_max = Duration::from_nanos(h.max());
+            println!(
+                "{:<40} {:>10} {t_min:>10.3?} {t_mean:>10.3?} {t_pct90:>10.3?} {t_max:>10.3?}",
+                name.to_string(),
+                h.len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn rotate() {
+        let names = ["a.example.net", "b.example.net"]
+            .into_iter()
+            .map(|s| Host::from_str(s).unwrap())
+            .collect();
+        let r = KeylessSniRotation::new(names).unwrap();
+        let (i, name) = r.select();
+        assert_eq!((i, name.to_string().as_str()), (0, "a.example.net"));
+        let (i, name) = r.select();
+        assert_eq!((i, name.to_string().as_str()), (1, "b.example.net"));
+        let (i, _) = r.select();
+        assert_eq!(i, 0);
+
+        r.record_handshake(1, Duration::from_millis(1));
+        let handshake_time = r.handshake_time.lock().unwrap();
+        assert_eq!(handshake_time[0].len(), 0);
+        assert_eq!(handshake_time[1].len(), 1);
+    }
+}
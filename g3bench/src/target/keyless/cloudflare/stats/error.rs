@@ -0,0 +1,93 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use ahash::AHashMap;
+use serde_json::{json, Value};
+
+/// the max number of distinct error messages to keep
+const MAX_DISTINCT_ERRORS: usize = 64;
+/// the max number of error messages to print in the summary
+const MAX_SUMMARY_ERRORS: usize = 10;
+
+/// count of distinct error messages, new messages will be counted as others
+/// if there are already too many distinct ones
+#[derive(Default)]
+pub(crate) struct KeylessErrorStatsMap {
+    inner: AHashMap<String, u64>,
+    others: u64,
+}
+
+impl KeylessErrorStatsMap {
+    pub(crate) fn record(&mut self, e: &anyhow::Error) {
+        self.add(format!("{e:#}"), 1);
+    }
+
+    fn add(&mut self, msg: String, count: u64) {
+        if let Some(v) = self.inner.get_mut(&msg) {
+            *v += count;
+        } else if self.inner.len() < MAX_DISTINCT_ERRORS {
+            self.inner.insert(msg, count);
+        } else {
+            self.others += count;
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: &KeylessErrorStatsMap) {
+        for (msg, count) in &other.inner {
+            self.add(msg.clone(), *count);
+        }
+        self.others += other.others;
+    }
+
+    fn sorted(&self) -> Vec<(&String, &u64)> {
+        let mut errors: Vec<_> = self.inner.iter().collect();
+        errors.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        errors
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        let errors: Vec<Value> = self
+            .sorted()
+            .into_iter()
+            .map(|(msg, count)| json!({"message": msg, "count": count}))
+            .collect();
+        json!({
+            "errors": errors,
+            "others": self.others,
+        })
+    }
+
+    pub(crate) fn summary(&self) {
+        if self.inner.is_empty() {
+            return;
+        }
+
+        let errors = self.sorted();
+
+        println!("# Errors");
+        let mut others = self.others;
+        for (i, (msg, count)) in errors.into_iter().enumerate() {
+            if i < MAX_SUMMARY_ERRORS {
+                println!("{count:>10} {msg}");
+            } else {
+                others += count;
+            }
+        }
+        if others > 0 {
+            println!("{others:>10} <other errors>");
+        }
+    }
+}
@@ -0,0 +1,175 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use g3_statsd_client::StatsdClient;
+
+use super::{KeylessErrorStatsMap, KeylessStatusStats, KeylessTargetStatsMap};
+use crate::target::BenchRuntimeStats;
+
+#[derive(Default)]
+pub(crate) struct KeylessRuntimeStats {
+    task_total: AtomicU64,
+    task_alive: AtomicI64,
+    task_passed: AtomicU64,
+    task_failed: AtomicU64,
+    conn_attempt: AtomicU64,
+    conn_attempt_total: AtomicU64,
+    conn_success: AtomicU64,
+    conn_success_total: AtomicU64,
+    opcode_mismatch: AtomicU64,
+    status_stats: KeylessStatusStats,
+    target_stats: Mutex<KeylessTargetStatsMap>,
+    error_stats: Mutex<KeylessErrorStatsMap>,
+}
+
+impl KeylessRuntimeStats {
+    pub(crate) fn add_task_total(&self) {
+        self.task_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn inc_task_alive(&self) {
+        self.task_alive.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dec_task_alive(&self) {
+        self.task_alive.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_task_passed(&self) {
+        self.task_passed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_task_failed(&self) {
+        self.task_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_conn_attempt(&self) {
+        self.conn_attempt.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_conn_success(&self) {
+        self.conn_success.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_opcode_mismatch(&self) {
+        self.opcode_mismatch.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_status_success(&self) {
+        self.status_stats.add_success();
+    }
+
+    pub(crate) fn add_status_code(&self, code: u8) {
+        self.status_stats.add(code);
+    }
+
+    pub(crate) fn add_target_conn_failed(&self, peer: SocketAddr, tls: bool) {
+        let mut target_stats = self.target_stats.lock().unwrap();
+        target_stats.record_conn_failed(peer, tls);
+    }
+
+    pub(crate) fn merge_target_stats(&self, other: &KeylessTargetStatsMap) {
+        let mut target_stats = self.target_stats.lock().unwrap();
+        target_stats.merge(other);
+    }
+
+    pub(crate) fn merge_error_stats(&self, other: &KeylessErrorStatsMap) {
+        let mut error_stats = self.error_stats.lock().unwrap();
+        error_stats.merge(other);
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        let conn_attempt = self.conn_attempt_total.load(Ordering::Relaxed)
+            + self.conn_attempt.load(Ordering::Relaxed);
+        let conn_success = self.conn_success_total.load(Ordering::Relaxed)
+            + self.conn_success.load(Ordering::Relaxed);
+        json!({
+            "connection": {
+                "attempt": conn_attempt,
+                "success": conn_success,
+            },
+            "opcode_mismatch": self.opcode_mismatch.load(Ordering::Relaxed),
+            "response_status": self.status_stats.to_json(),
+        })
+    }
+
+    pub(crate) fn errors_to_json(&self) -> Value {
+        let error_stats = self.error_stats.lock().unwrap();
+        error_stats.to_json()
+    }
+}
+
+impl BenchRuntimeStats for KeylessRuntimeStats {
+    fn emit(&self, client: &mut StatsdClient) {
+        macro_rules! emit_count {
+            ($field:ident, $name:literal) => {
+                let $field = self.$field.swap(0, Ordering::Relaxed);
+                client.count(concat!("keyless.", $name), $field).send();
+            };
+        }
+
+        let task_alive = self.task_alive.load(Ordering::Relaxed);
+        client.gauge("keyless.task.alive", task_alive).send();
+
+        emit_count!(task_total, "task.total");
+        emit_count!(task_passed, "task.passed");
+        emit_count!(task_failed, "task.failed");
+        emit_count!(conn_attempt, "connection.attempt");
+        self.conn_attempt_total
+            .fetch_add(conn_attempt, Ordering::Relaxed);
+        emit_count!(conn_success, "connection.success");
+        self.conn_success_total
+            .fetch_add(conn_success, Ordering::Relaxed);
+    }
+
+    fn summary(&self, total_time: Duration) {
+        let total_secs = total_time.as_secs_f64();
+
+        println!("# Connections");
+        let total_attempt = self.conn_attempt_total.load(Ordering::Relaxed)
+            + self.conn_attempt.load(Ordering::Relaxed);
+        println!("Attempt count: {total_attempt}");
+        let total_success = self.conn_success_total.load(Ordering::Relaxed)
+            + self.conn_success.load(Ordering::Relaxed);
+        println!("Success count: {total_success}");
+        println!(
+            "Success ratio: {:.2}%",
+            (total_success as f64 / total_attempt as f64) * 100.0
+        );
+        println!("Success rate:  {:.3}/s", total_success as f64 / total_secs);
+
+        let opcode_mismatch = self.opcode_mismatch.load(Ordering::Relaxed);
+        if opcode_mismatch > 0 {
+            println!("# Protocol Errors");
+            println!("OpCode Mismatch: {opcode_mismatch}");
+        }
+
+        self.status_stats.summary();
+
+        let target_stats = self.target_stats.lock().unwrap();
+        target_stats.summary(total_time);
+
+        let error_stats = self.error_stats.lock().unwrap();
+        error_stats.summary();
+    }
+}
@@ -0,0 +1,88 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{json, Value};
+
+use crate::target::keyless::cloudflare::KeylessServerError;
+
+/// the status code for successful responses, which is not a server error code
+const STATUS_NONE: u8 = 0x00;
+
+/// count of the response status codes, indexed by the raw code
+pub(crate) struct KeylessStatusStats {
+    codes: [AtomicU64; 256],
+}
+
+impl Default for KeylessStatusStats {
+    fn default() -> Self {
+        KeylessStatusStats {
+            codes: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl KeylessStatusStats {
+    pub(crate) fn add_success(&self) {
+        self.add(STATUS_NONE);
+    }
+
+    pub(crate) fn add(&self, code: u8) {
+        self.codes[code as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn collect(&self) -> Vec<(u8, u64)> {
+        self.codes
+            .iter()
+            .enumerate()
+            .filter_map(|(code, v)| {
+                let count = v.load(Ordering::Relaxed);
+                (count > 0).then_some((code as u8, count))
+            })
+            .collect()
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        let codes: Vec<Value> = self
+            .collect()
+            .into_iter()
+            .map(|(code, count)| json!({"code": code, "name": status_name(code), "count": count}))
+            .collect();
+        Value::Array(codes)
+    }
+
+    pub(crate) fn summary(&self) {
+        let codes = self.collect();
+        if codes.is_empty() {
+            return;
+        }
+
+        println!("# Response Status");
+        for (code, count) in codes {
+            println!("{count:>10} {code:#04x} {}", status_name(code));
+        }
+    }
+}
+
+fn status_name(code: u8) -> &'static str {
+    if code == STATUS_NONE {
+        return "NONE";
+    }
+    KeylessServerError::from_code(code)
+        .map(|e| e.name())
+        .unwrap_or("<unknown>")
+}
@@ -0,0 +1,221 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use ahash::AHashMap;
+use hdrhistogram::Histogram;
+
+use g3_types::ext::DurationExt;
+
+struct KeylessTargetStats {
+    passed: u64,
+    failed: u64,
+    conn_failed: u64,
+    total_time: Histogram<u64>,
+}
+
+impl Default for KeylessTargetStats {
+    fn default() -> Self {
+        KeylessTargetStats {
+            passed: 0,
+            failed: 0,
+            conn_failed: 0,
+            total_time: Histogram::new(3).unwrap(),
+        }
+    }
+}
+
+impl KeylessTargetStats {
+    fn merge(&mut self, other: &KeylessTargetStats) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.conn_failed += other.conn_failed;
+        let _ = self.total_time.add(&other.total_time);
+    }
+
+    fn print_table_row(&self, name: &str, tls: bool) {
+        const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+
+        let h = &self.total_time;
+        let t_mean = Duration::from_secs_f64(h.mean() / NANOS_PER_SEC);
+        let t_pct90 = Duration::from_nanos(h.value_at_quantile(0.90));
+        let t_max = Duration::from_nanos(h.max());
+        let transport = if tls { "tls" } else { "tcp" };
+        println!(
+            "{name:<40} {transport:>9} {:>10} {:>10} {:>10} {t_mean:>10.3?} {t_pct90:>10.3?} {t_max:>10.3?}",
+            self.passed, self.failed, self.conn_failed,
+        );
+    }
+}
+
+/// request stats segmented by the resolved target address and the transport,
+/// and also by the negotiated tls version, and by the key class for multiple keys
+#[derive(Default)]
+pub(crate) struct KeylessTargetStatsMap {
+    inner: AHashMap<(SocketAddr, bool), KeylessTargetStats>,
+    versions: AHashMap<&'static str, KeylessTargetStats>,
+    key_classes: AHashMap<String, KeylessTargetStats>,
+}
+
+impl KeylessTargetStatsMap {
+    pub(crate) fn record_passed(
+        &mut self,
+        peer: SocketAddr,
+        tls_version: Option<&'static str>,
+        total_time: Duration,
+    ) {
+        let nanos = total_time.as_nanos_u64();
+        let stats = self.inner.entry((peer, tls_version.is_some())).or_default();
+        stats.passed += 1;
+        let _ = stats.total_time.record(nanos);
+
+        let stats = self.version_entry(tls_version);
+        stats.passed += 1;
+        let _ = stats.total_time.record(nanos);
+    }
+
+    pub(crate) fn record_failed(&mut self, peer: SocketAddr, tls_version: Option<&'static str>) {
+        self.inner
+            .entry((peer, tls_version.is_some()))
+            .or_default()
+            .failed += 1;
+        self.version_entry(tls_version).failed += 1;
+    }
+
+    fn version_entry(&mut self, tls_version: Option<&'static str>) -> &mut KeylessTargetStats {
+        self.versions
+            .entry(tls_version.unwrap_or("tcp"))
+            .or_default()
+    }
+
+    fn key_class_entry(&mut self, class: &str) -> &mut KeylessTargetStats {
+        if !self.key_classes.contains_key(class) {
+            self.key_classes
+                .insert(class.to_string(), KeylessTargetStats::default());
+        }
+        self.key_classes.get_mut(class).unwrap()
+    }
+
+    /// record the time of a single request with the key of this class
+    pub(crate) fn record_key_passed(&mut self, class: &str, time: Duration) {
+        let stats = self.key_class_entry(class);
+        stats.passed += 1;
+        let _ = stats.total_time.record(time.as_nanos_u64());
+    }
+
+    pub(crate) fn record_key_failed(&mut self, class: &str) {
+        self.key_class_entry(class).failed += 1;
+    }
+
+    pub(crate) fn record_conn_failed(&mut self, peer: SocketAddr, tls: bool) {
+        self.inner.entry((peer, tls)).or_default().conn_failed += 1;
+    }
+
+    pub(crate) fn merge(&mut self, other: &KeylessTargetStatsMap) {
+        for (key, stats) in &other.inner {
+            self.inner.entry(*key).or_default().merge(stats);
+        }
+        for (version, stats) in &other.versions {
+            self.versions.entry(version).or_default().merge(stats);
+        }
+        for (class, stats) in &other.key_classes {
+            self.key_class_entry(class).merge(stats);
+        }
+    }
+
+    pub(crate) fn summary(&self, total_time: Duration) {
+        if self.inner.is_empty() {
+            return;
+        }
+
+        let mut peers: Vec<_> = self.inner.iter().collect();
+        peers.sort_by_key(|(key, _)| **key);
+
+        println!("# Targets");
+        print_table_header();
+        for ((peer, tls), stats) in peers {
+            stats.print_table_row(&peer.to_string(), *tls);
+        }
+
+        let mut transports: [Option<KeylessTargetStats>; 2] = [None, None];
+        for ((_, tls), stats) in &self.inner {
+            transports[*tls as usize]
+                .get_or_insert_with(KeylessTargetStats::default)
+                .merge(stats);
+        }
+        if transports.iter().all(|v| v.is_some()) {
+            println!("# Transports");
+            print_table_header();
+            for (i, stats) in transports.iter().enumerate() {
+                if let Some(stats) = stats {
+                    stats.print_table_row("*", i != 0);
+                }
+            }
+        }
+
+        if self.versions.iter().any(|(v, _)| *v != "tcp") {
+            let mut versions: Vec<_> = self.versions.iter().map(|(v, s)| (*v, s)).collect();
+            versions.sort_by_key(|(v, _)| *v);
+            println!("# TLS Versions");
+            print_rate_table("Version", versions, total_time);
+        }
+
+        if !self.key_classes.is_empty() {
+            let mut classes: Vec<_> = self
+                .key_classes
+                .iter()
+                .map(|(c, s)| (c.as_str(), s))
+                .collect();
+            classes.sort_by_key(|(c, _)| *c);
+            println!("# Key Classes");
+            print_rate_table("Class", classes, total_time);
+        }
+    }
+}
+
+fn print_rate_table(column: &str, rows: Vec<(&str, &KeylessTargetStats)>, total_time: Duration) {
+    const NANOS_PER_SEC: f64 = 1_000_000_000.0;
+
+    let total_secs = total_time.as_secs_f64();
+    println!(
+        "{column:<12} {:>10} {:>10} {:>12} {:>10} {:>10} {:>10}",
+        "Passed", "Failed", "Rate/s", "Mean", "pct90", "Max"
+    );
+    for (name, stats) in rows {
+        let h = &stats.total_time;
+        let t_mean = Duration::from_secs_f64(h.mean() / NANOS_PER_SEC);
+        let t_pct90 = Duration::from_nanos(h.value_at_quantile(0.90));
+        let t_max = Duration::from_nanos(h.max());
+        let rate = if total_secs > 0.0 {
+            stats.passed as f64 / total_secs
+        } else {
+            0.0
+        };
+        println!(
+            "{name:<12} {:>10} {:>10} {rate:>12.3} {t_mean:>10.3?} {t_pct90:>10.3?} {t_max:>10.3?}",
+            stats.passed, stats.failed,
+        );
+    }
+}
+
+fn print_table_header() {
+    println!(
+        "{:<40} {:>9} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "Address", "Transport", "Passed", "Failed", "ConnFailed", "Mean", "pct90", "Max"
+    );
+}
@@ -0,0 +1,700 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::{anyhow, Context};
+use futures_util::future;
+use tokio::time::Instant;
+
+use super::{
+    BenchTaskContext, KeylessCloudflareArgs, KeylessConnectionPool, KeylessErrorStatsMap,
+    KeylessHistogramRecorder, KeylessLocalError, KeylessRequest, KeylessRequestBuilder,
+    KeylessResponse, KeylessResponseError, KeylessRuntimeStats, KeylessServerError,
+    KeylessTargetStatsMap, MultiplexTransfer, SendRequestError, SimplexTransfer,
+};
+use crate::module::otlp::{OtlpSender, OtlpTrace};
+use crate::opts::ProcArgs;
+use crate::target::keyless::opts::KeylessRsaPadding;
+use crate::target::BenchError;
+
+pub(super) struct KeylessCloudflareTaskContext {
+    args: Arc<KeylessCloudflareArgs>,
+    proc_args: Arc<ProcArgs>,
+
+    pool: Option<Arc<KeylessConnectionPool>>,
+    pool_index: Option<usize>,
+    multiplex: Option<Arc<MultiplexTransfer>>,
+    simplex: Option<SimplexTransfer>,
+
+    reuse_conn_count: u64,
+    server_rsa_padding: Option<KeylessRsaPadding>,
+    request_builder: KeylessRequestBuilder,
+    request_message: KeylessRequest,
+    multi_request_builders: Vec<KeylessRequestBuilder>,
+    multi_request_messages: Vec<KeylessRequest>,
+
+    runtime_stats: Arc<KeylessRuntimeStats>,
+    histogram_recorder: KeylessHistogramRecorder,
+    target_stats: KeylessTargetStatsMap,
+    error_stats: KeylessErrorStatsMap,
+
+    otlp: Option<OtlpSender>,
+    trace: Option<OtlpTrace>,
+}
+
+impl Drop for KeylessCloudflareTaskContext {
+    fn drop(&mut self) {
+        self.histogram_recorder
+            .record_conn_reuse_count(self.reuse_conn_count);
+        self.runtime_stats.merge_target_stats(&self.target_stats);
+        self.runtime_stats.merge_error_stats(&self.error_stats);
+    }
+}
+
+impl KeylessCloudflareTaskContext {
+    pub(super) fn new(
+        args: &Arc<KeylessCloudflareArgs>,
+        proc_args: &Arc<ProcArgs>,
+        runtime_stats: &Arc<KeylessRuntimeStats>,
+        histogram_recorder: KeylessHistogramRecorder,
+        pool: Option<Arc<KeylessConnectionPool>>,
+        otlp: Option<OtlpSender>,
+    ) -> anyhow::Result<Self> {
+        let request_builder = args.new_request_builder(args.global.subject_key_id())?;
+        let request_message = request_builder.build(&args.global.payload)?;
+        let mut multi_request_builders = Vec::with_capacity(args.global.multi_keys.len());
+        let mut multi_request_messages = Vec::with_capacity(args.global.multi_keys.len());
+        for key in &args.global.multi_keys {
+            let builder = args.new_request_builder(&key.ski)?;
+            multi_request_messages.push(builder.build(&args.global.payload)?);
+            multi_request_builders.push(builder);
+        }
+        let pool_index = pool.as_ref().and_then(|p| p.assign_index());
+        Ok(KeylessCloudflareTaskContext {
+            args: Arc::clone(args),
+            proc_args: Arc::clone(proc_args),
+            pool,
+            pool_index,
+            multiplex: None,
+            simplex: None,
+            reuse_conn_count: 0,
+            server_rsa_padding: None,
+            request_builder,
+            request_message,
+            multi_request_builders,
+            multi_request_messages,
+            runtime_stats: Arc::clone(runtime_stats),
+            histogram_recorder,
+            target_stats: KeylessTargetStatsMap::default(),
+            error_stats: KeylessErrorStatsMap::default(),
+            otlp,
+            trace: None,
+        })
+    }
+
+    /// rebuild all request messages, each from a fresh copy of the payload
+    fn rebuild_request_messages(&mut self) -> anyhow::Result<()> {
+        let payload = self.args.global.payload.clone();
+        self.request_message = self.request_builder.build(&payload)?;
+        for (builder, msg) in self
+            .multi_request_builders
+            .iter()
+            .zip(self.multi_request_messages.iter_mut())
+        {
+            let payload = self.args.global.payload.clone();
+            *msg = builder.build(&payload)?;
+        }
+        Ok(())
+    }
+
+    fn record_result(
+        &self,
+        task_id: usize,
+        connection: SocketAddr,
+        request_start: SystemTime,
+        r: &anyhow::Result<KeylessResponse>,
+    ) {
+        if let Some(writer) = &self.args.request_csv {
+            writer.write_row(task_id, connection, request_start, r);
+        }
+        if let Some(recorder) = &self.args.recorder {
+            recorder.record(
+                task_id,
+                &self.request_message,
+                r.as_ref().map(|rsp| rsp.data()),
+            );
+        }
+    }
+
+    /// record the status code of the response, errors not reported by the server are skipped
+    fn record_status(runtime_stats: &KeylessRuntimeStats, r: &anyhow::Result<KeylessResponse>) {
+        match r {
+            Ok(_) => runtime_stats.add_status_success(),
+            Err(e) => {
+                if let Some(se) = e.downcast_ref::<KeylessServerError>() {
+                    runtime_stats.add_status_code(se.code());
+                } else if let Some(KeylessLocalError::UnsupportedServerErrorCode(code)) =
+                    e.downcast_ref::<KeylessLocalError>()
+                {
+                    runtime_stats.add_status_code(*code);
+                }
+            }
+        }
+    }
+
+    async fn fetch_multiplex_handle(&mut self) -> anyhow::Result<Arc<MultiplexTransfer>> {
+        if let Some(pool) = &self.pool {
+            return pool.fetch_handle(self.pool_index).await;
+        }
+
+        if let Some(handle) = &self.multiplex {
+            if !handle.is_closed() {
+                self.reuse_conn_count += 1;
+                return Ok(handle.clone());
+            }
+            self.multiplex = None;
+        }
+
+        if self.reuse_conn_count > 0 {
+            self.histogram_recorder
+                .record_conn_reuse_count(self.reuse_conn_count);
+            self.reuse_conn_count = 0;
+        }
+
+        let peer = self.args.select_target_addr(&self.proc_args)?;
+        let use_tls = self.args.select_tls();
+        self.runtime_stats.add_conn_attempt();
+        let handle = match tokio::time::timeout(
+            self.args.new_connection_timeout(),
+            self.args
+                .new_multiplex_keyless_connection(peer, use_tls, self.trace.as_mut()),
+        )
+        .await
+        {
+            Ok(Ok(h)) => Arc::new(h),
+            Ok(Err(e)) => {
+                self.target_stats.record_conn_failed(peer, use_tls);
+                return Err(e);
+            }
+            Err(_) => {
+                self.target_stats.record_conn_failed(peer, use_tls);
+                return Err(anyhow!("timeout to get new connection"));
+            }
+        };
+        self.runtime_stats.add_conn_success();
+
+        self.multiplex = Some(handle.clone());
+        Ok(handle)
+    }
+
+    async fn fetch_simplex_connection(&mut self) -> anyhow::Result<SimplexTransfer> {
+        if let Some(mut c) = self.simplex.take() {
+            if !c.is_closed() {
+                self.reuse_conn_count += 1;
+                return Ok(c);
+            }
+        }
+
+        if self.reuse_conn_count > 0 {
+            self.histogram_recorder
+                .record_conn_reuse_count(self.reuse_conn_count);
+            self.reuse_conn_count = 0;
+        }
+
+        let peer = self.args.select_target_addr(&self.proc_args)?;
+        let use_tls = self.args.select_tls();
+        self.runtime_stats.add_conn_attempt();
+        match tokio::time::timeout(
+            self.args.new_connection_timeout(),
+            self.args
+                .new_simplex_keyless_connection(peer, use_tls, self.trace.as_mut()),
+        )
+        .await
+        {
+            Ok(Ok(c)) => {
+                self.runtime_stats.add_conn_success();
+                Ok(c)
+            }
+            Ok(Err(e)) => {
+                self.target_stats.record_conn_failed(peer, use_tls);
+                Err(e)
+            }
+            Err(_) => {
+                self.target_stats.record_conn_failed(peer, use_tls);
+                Err(anyhow!("timeout to get new connection"))
+            }
+        }
+    }
+
+    fn add_trace_span(&mut self, name: &'static str, start: SystemTime) {
+        if let Some(trace) = &mut self.trace {
+            trace.add_child(name, start);
+        }
+    }
+
+    fn run_local_action(&mut self) -> anyhow::Result<()> {
+        let local_start = Instant::now();
+        self.args
+            .global
+            .handle_local_action()
+            .context("local action failed")?;
+        self.histogram_recorder
+            .record_local_time(local_start.elapsed());
+        Ok(())
+    }
+
+    fn log_server_rsa_padding(&mut self, task_id: usize, rsp: &KeylessResponse) {
+        if !self.args.server_choose_rsa_padding {
+            return;
+        }
+        let padding = rsp.rsa_padding();
+        if padding != self.server_rsa_padding {
+            match padding {
+                Some(p) => println!("== Task {task_id}: server chose rsa padding {p:?}"),
+                None => println!("== Task {task_id}: server reported no rsa padding"),
+            }
+            self.server_rsa_padding = padding;
+        }
+    }
+
+    async fn do_run_multiplex(
+        &self,
+        handle: &MultiplexTransfer,
+        request: KeylessRequest,
+    ) -> anyhow::Result<KeylessResponse> {
+        let timeout = self.args.request_timeout();
+        let start = Instant::now();
+        match tokio::time::timeout(timeout, handle.send_request(request)).await {
+            Ok(Ok(rsp)) => {
+                self.args.record_request_latency(start.elapsed());
+                Ok(rsp)
+            }
+            Ok(Err(
+                SendRequestError::InvalidRequest(id, e) | SendRequestError::InvalidResponse(id, e),
+            )) => {
+                let msg = format!("{}/{id} error: {e}", handle.local_addr());
+                Err(anyhow::Error::new(e).context(msg))
+            }
+            Ok(Err(SendRequestError::NoResponse(id))) => match handle.fetch_error() {
+                Some(e) => {
+                    let msg = format!("{}/{id} error: {e}", handle.local_addr());
+                    match e.as_ref() {
+                        KeylessResponseError::ServerError(se) => {
+                            Err(anyhow::Error::new(*se).context(msg))
+                        }
+                        KeylessResponseError::LocalError(
+                            KeylessLocalError::UnsupportedServerErrorCode(code),
+                        ) => Err(anyhow::Error::new(
+                            KeylessLocalError::UnsupportedServerErrorCode(*code),
+                        )
+                        .context(msg)),
+                        _ => Err(anyhow!(msg)),
+                    }
+                }
+                None => Err(anyhow!(
+                    "{}/{id}: we get no response but no error reported",
+                    handle.local_addr()
+                )),
+            },
+            Err(_) => Err(anyhow!(
+                "{}: request timed out after {timeout:?}",
+                handle.local_addr()
+            )),
+        }
+    }
+
+    async fn do_run_multiplex_all(
+        &mut self,
+        handle: &MultiplexTransfer,
+    ) -> anyhow::Result<Vec<Vec<u8>>> {
+        let this = &*self;
+        let requests = this.multi_request_messages.iter().map(|req| async move {
+            let start = Instant::now();
+            let r = this.do_run_multiplex(handle, req.clone()).await;
+            (r, start.elapsed())
+        });
+        let results = future::join_all(requests).await;
+
+        let mut outputs = Vec::with_capacity(results.len());
+        let mut first_error = None;
+        for ((r, time), key) in results.into_iter().zip(&self.args.global.multi_keys) {
+            Self::record_status(&self.runtime_stats, &r);
+            match r {
+                Ok(rsp) => {
+                    self.target_stats.record_key_passed(key.class(), time);
+                    outputs.push(rsp.into_vec());
+                }
+                Err(e) => {
+                    self.target_stats.record_key_failed(key.class());
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(outputs),
+        }
+    }
+
+    async fn do_run_simplex(
+        args: &KeylessCloudflareArgs,
+        connection: &mut SimplexTransfer,
+        request: &mut KeylessRequest,
+    ) -> anyhow::Result<KeylessResponse> {
+        let timeout = args.request_timeout();
+        let start = Instant::now();
+        match tokio::time::timeout(timeout, connection.send_request(request)).await {
+            Ok(Ok(rsp)) => {
+                args.record_request_latency(start.elapsed());
+                Ok(rsp)
+            }
+            Ok(Err(e)) => {
+                let msg = format!("{} error: {e}", connection.local_addr());
+                match e {
+                    KeylessResponseError::ServerError(se) => {
+                        Err(anyhow::Error::new(se).context(msg))
+                    }
+                    KeylessResponseError::LocalError(
+                        e @ (KeylessLocalError::OpCodeMismatch(..)
+                        | KeylessLocalError::UnsupportedServerErrorCode(_)),
+                    ) => Err(anyhow::Error::new(e).context(msg)),
+                    _ => Err(anyhow!(msg)),
+                }
+            }
+            Err(_) => Err(anyhow!(
+                "{}: request timed out after {timeout:?}",
+                connection.local_addr()
+            )),
+        }
+    }
+
+    async fn do_run_simplex_all(
+        args: &KeylessCloudflareArgs,
+        connection: &mut SimplexTransfer,
+        requests: &mut [KeylessRequest],
+        runtime_stats: &KeylessRuntimeStats,
+        target_stats: &mut KeylessTargetStatsMap,
+    ) -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut outputs = Vec::with_capacity(requests.len());
+        for (req, key) in requests.iter_mut().zip(&args.global.multi_keys) {
+            let start = Instant::now();
+            let r = Self::do_run_simplex(args, connection, req).await;
+            Self::record_status(runtime_stats, &r);
+            match r {
+                Ok(rsp) => {
+                    target_stats.record_key_passed(key.class(), start.elapsed());
+                    outputs.push(rsp.into_vec());
+                }
+                Err(e) => {
+                    target_stats.record_key_failed(key.class());
+                    return Err(e);
+                }
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// setup a new connection and close it immediately
+    async fn run_connect_only(&mut self, time_started: Instant) -> Result<(), BenchError> {
+        let peer = self
+            .args
+            .select_target_addr(&self.proc_args)
+            .map_err(BenchError::Fatal)?;
+        let use_tls = self.args.select_tls();
+        self.runtime_stats.add_conn_attempt();
+        match tokio::time::timeout(
+            self.args.new_connection_timeout(),
+            self.args
+                .new_simplex_keyless_connection(peer, use_tls, self.trace.as_mut()),
+        )
+        .await
+        {
+            Ok(Ok(connection)) => {
+                let total_time = time_started.elapsed();
+                let tls_version = connection.tls_version();
+                drop(connection);
+                self.runtime_stats.add_conn_success();
+                self.histogram_recorder.record_total_time(total_time);
+                self.target_stats
+                    .record_passed(peer, tls_version, total_time);
+                Ok(())
+            }
+            Ok(Err(e)) => {
+                self.target_stats.record_conn_failed(peer, use_tls);
+                Err(BenchError::Task(e))
+            }
+            Err(_) => {
+                self.target_stats.record_conn_failed(peer, use_tls);
+                Err(BenchError::Task(anyhow!("timeout to get new connection")))
+            }
+        }
+    }
+
+    async fn run_multi(&mut self, task_id: usize, time_started: Instant) -> Result<(), BenchError> {
+        if self.args.no_multiplex {
+            let mut connection = self
+                .fetch_simplex_connection()
+                .await
+                .map_err(BenchError::Fatal)?;
+            let peer = connection.peer_addr();
+            let tls_version = connection.tls_version();
+
+            let request_start = SystemTime::now();
+            let r = Self::do_run_simplex_all(
+                &self.args,
+                &mut connection,
+                &mut self.multi_request_messages,
+                &self.runtime_stats,
+                &mut self.target_stats,
+            )
+            .await;
+            self.add_trace_span("request", request_start);
+            match r {
+                Ok(outputs) => {
+                    let total_time = time_started.elapsed();
+                    self.simplex = Some(connection);
+                    self.histogram_recorder.record_total_time(total_time);
+                    self.target_stats
+                        .record_passed(peer, tls_version, total_time);
+                    self.args
+                        .global
+                        .check_multi_result(task_id, outputs)
+                        .map_err(BenchError::Task)
+                }
+                Err(e) => {
+                    self.target_stats.record_failed(peer, tls_version);
+                    Err(BenchError::Task(e))
+                }
+            }
+        } else {
+            let handle = self
+                .fetch_multiplex_handle()
+                .await
+                .map_err(BenchError::Fatal)?;
+            let peer = handle.peer_addr();
+            let tls_version = handle.tls_version();
+
+            let request_start = SystemTime::now();
+            let r = self.do_run_multiplex_all(&handle).await;
+            self.add_trace_span("request", request_start);
+            match r {
+                Ok(outputs) => {
+                    let total_time = time_started.elapsed();
+                    self.histogram_recorder.record_total_time(total_time);
+                    self.target_stats
+                        .record_passed(peer, tls_version, total_time);
+                    self.args
+                        .global
+                        .check_multi_result(task_id, outputs)
+                        .map_err(BenchError::Task)
+                }
+                Err(e) => {
+                    self.target_stats.record_failed(peer, tls_version);
+                    self.multiplex = None;
+                    Err(BenchError::Task(e))
+                }
+            }
+        }
+    }
+}
+
+impl BenchTaskContext for KeylessCloudflareTaskContext {
+    fn mark_task_start(&self) {
+        self.runtime_stats.add_task_total();
+        self.runtime_stats.inc_task_alive();
+    }
+
+    fn mark_task_passed(&self) {
+        self.runtime_stats.add_task_passed();
+        self.runtime_stats.dec_task_alive();
+    }
+
+    fn mark_task_failed(&self) {
+        self.runtime_stats.add_task_failed();
+        self.runtime_stats.dec_task_alive();
+    }
+
+    async fn run(&mut self, task_id: usize, time_started: Instant) -> Result<(), BenchError> {
+        let r = if self.otlp.is_none() {
+            self.run_request(task_id, time_started).await
+        } else {
+            self.run_traced_request(task_id, time_started).await
+        };
+        if let Err(BenchError::Fatal(e) | BenchError::Task(e)) = &r {
+            self.error_stats.record(e);
+            if matches!(
+                e.downcast_ref::<KeylessLocalError>(),
+                Some(KeylessLocalError::OpCodeMismatch(..))
+            ) {
+                self.runtime_stats.add_opcode_mismatch();
+            }
+        }
+        r
+    }
+}
+
+impl KeylessCloudflareTaskContext {
+    async fn run_traced_request(
+        &mut self,
+        task_id: usize,
+        time_started: Instant,
+    ) -> Result<(), BenchError> {
+        let mut trace = OtlpTrace::new("keyless.request");
+        trace.add_attribute("keyless.action", format!("{:?}", self.args.global.action));
+        trace.add_attribute(
+            "keyless.key_id",
+            hex::encode(self.args.global.subject_key_id()),
+        );
+        self.trace = Some(trace);
+
+        let r = self.run_request(task_id, time_started).await;
+        if let Some(mut trace) = self.trace.take() {
+            match &r {
+                Ok(_) => trace.add_attribute("keyless.result", "ok".to_string()),
+                Err(BenchError::Fatal(e)) | Err(BenchError::Task(e)) => {
+                    trace.add_attribute("keyless.result", "error".to_string());
+                    trace.set_error(e);
+                }
+            }
+            if let Some(otlp) = &self.otlp {
+                otlp.send(trace);
+            }
+        }
+        r
+    }
+
+    async fn run_request(
+        &mut self,
+        task_id: usize,
+        time_started: Instant,
+    ) -> Result<(), BenchError> {
+        self.args.mark_new_request();
+        let mut retries = 0;
+        loop {
+            match self.run_request_once(task_id, time_started).await {
+                Err(BenchError::Task(e))
+                    if retries < self.args.request_retries
+                        && self.args.should_retry_request(&e)
+                        && self.args.acquire_retry() =>
+                {
+                    retries += 1;
+                    tokio::time::sleep(self.args.retry_interval()).await;
+                }
+                Err(BenchError::Task(e)) if self.args.is_unsupported_extension(&e) => {
+                    // no need to continue if the server doesn't support the extension action
+                    crate::target::stats::mark_force_quit();
+                    return Err(BenchError::Fatal(anyhow!(
+                        "{:?} is not supported by the server, skipped",
+                        self.args.global.action
+                    )));
+                }
+                r => return r,
+            }
+        }
+    }
+
+    async fn run_request_once(
+        &mut self,
+        task_id: usize,
+        time_started: Instant,
+    ) -> Result<(), BenchError> {
+        if self.args.connect_only {
+            return self.run_connect_only(time_started).await;
+        }
+        if self.args.no_payload_reuse {
+            self.rebuild_request_messages().map_err(BenchError::Fatal)?;
+        }
+        if !self.multi_request_messages.is_empty() {
+            return self.run_multi(task_id, time_started).await;
+        }
+
+        if self.args.no_multiplex {
+            let mut connection = self
+                .fetch_simplex_connection()
+                .await
+                .map_err(BenchError::Fatal)?;
+            let peer = connection.peer_addr();
+            let tls_version = connection.tls_version();
+
+            let request_start = SystemTime::now();
+            let r =
+                Self::do_run_simplex(&self.args, &mut connection, &mut self.request_message).await;
+            self.add_trace_span("request", request_start);
+            Self::record_status(&self.runtime_stats, &r);
+            self.record_result(task_id, connection.local_addr(), request_start, &r);
+            match r {
+                Ok(rsp) => {
+                    let total_time = time_started.elapsed();
+                    if self.args.local_timing {
+                        self.run_local_action().map_err(BenchError::Task)?;
+                    }
+                    self.simplex = Some(connection);
+                    self.log_server_rsa_padding(task_id, &rsp);
+                    self.histogram_recorder.record_total_time(total_time);
+                    self.target_stats
+                        .record_passed(peer, tls_version, total_time);
+                    self.args
+                        .global
+                        .check_result_with_rsa_padding(task_id, rsp.rsa_padding(), rsp.into_vec())
+                        .map_err(BenchError::Task)
+                }
+                Err(e) => {
+                    self.target_stats.record_failed(peer, tls_version);
+                    Err(BenchError::Task(e))
+                }
+            }
+        } else {
+            let handle = self
+                .fetch_multiplex_handle()
+                .await
+                .map_err(BenchError::Fatal)?;
+            let peer = handle.peer_addr();
+            let tls_version = handle.tls_version();
+
+            let request_start = SystemTime::now();
+            let r = self
+                .do_run_multiplex(&handle, self.request_message.clone())
+                .await;
+            self.add_trace_span("request", request_start);
+            Self::record_status(&self.runtime_stats, &r);
+            self.record_result(task_id, handle.local_addr(), request_start, &r);
+            match r {
+                Ok(rsp) => {
+                    let total_time = time_started.elapsed();
+                    if self.args.local_timing {
+                        self.run_local_action().map_err(BenchError::Task)?;
+                    }
+                    self.histogram_recorder.record_total_time(total_time);
+                    self.target_stats
+                        .record_passed(peer, tls_version, total_time);
+                    self.log_server_rsa_padding(task_id, &rsp);
+                    self.args
+                        .global
+                        .check_result_with_rsa_padding(task_id, rsp.rsa_padding(), rsp.into_vec())
+                        .map_err(BenchError::Task)
+                }
+                Err(e) => {
+                    self.target_stats.record_failed(peer, tls_version);
+                    self.multiplex = None;
+                    Err(BenchError::Task(e))
+                }
+            }
+        }
+    }
+}
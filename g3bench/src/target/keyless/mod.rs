@@ -0,0 +1,80 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command, ValueHint};
+
+use super::{BenchRuntimeStats, BenchTarget, BenchTaskContext, ProcArgs};
+
+mod opts;
+use opts::{AppendKeylessArgs, KeylessGlobalArgs};
+
+mod cloudflare;
+
+mod openssl;
+
+pub const COMMAND: &str = "keyless";
+
+const ARG_LIST_ACTIONS: &str = "list-actions";
+const ARG_MERGE_ARTIFACTS: &str = "merge-artifacts";
+
+pub fn command() -> Command {
+    Command::new(COMMAND)
+        .subcommand_value_name("PROVIDER")
+        .subcommand(openssl::command())
+        .subcommand(cloudflare::command())
+        .arg(
+            Arg::new(ARG_LIST_ACTIONS)
+                .help("List all the supported actions, with the options and key types, then exit")
+                .long(ARG_LIST_ACTIONS)
+                .action(ArgAction::SetTrue)
+                .num_args(0)
+                .exclusive(true),
+        )
+        .arg(
+            Arg::new(ARG_MERGE_ARTIFACTS)
+                .help(
+                    "Merge the artifact directories saved by multiple instances \
+                    that run at the same time, and print the combined report, then exit",
+                )
+                .value_name("PATH")
+                .long(ARG_MERGE_ARTIFACTS)
+                .num_args(1..)
+                .value_parser(value_parser!(PathBuf))
+                .value_hint(ValueHint::DirPath)
+                .exclusive(true),
+        )
+}
+
+pub async fn run(proc_args: &Arc<ProcArgs>, cmd_args: &ArgMatches) -> anyhow::Result<()> {
+    if cmd_args.get_flag(ARG_LIST_ACTIONS) {
+        opts::print_actions();
+        return Ok(());
+    }
+    if let Some(dirs) = cmd_args.get_many::<PathBuf>(ARG_MERGE_ARTIFACTS) {
+        let dirs: Vec<PathBuf> = dirs.cloned().collect();
+        return cloudflare::merge_artifacts(&dirs);
+    }
+    match cmd_args.subcommand() {
+        Some((openssl::COMMAND, args)) => openssl::run(proc_args, args).await,
+        Some((cloudflare::COMMAND, args)) => cloudflare::run(proc_args, args).await,
+        Some((provider, _)) => Err(anyhow!("invalid provider {provider}")),
+        None => Err(anyhow!("no provider set")),
+    }
+}
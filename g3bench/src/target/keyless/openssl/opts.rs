@@ -0,0 +1,118 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{anyhow, Context};
+use clap::{value_parser, Arg, ArgMatches, Command, ValueHint};
+
+use crate::target::keyless::{AppendKeylessArgs, KeylessGlobalArgs};
+
+const ARG_LOCAL_VERIFY_BENCH: &str = "local-verify-bench";
+
+pub(super) struct KeylessOpensslArgs {
+    pub(super) global: KeylessGlobalArgs,
+    verify_corpus: Vec<Vec<u8>>,
+    next_verify_index: AtomicUsize,
+}
+
+impl KeylessOpensslArgs {
+    pub(super) fn new(global: KeylessGlobalArgs) -> Self {
+        KeylessOpensslArgs {
+            global,
+            verify_corpus: Vec::new(),
+            next_verify_index: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    pub(super) fn handle_action(&self) -> anyhow::Result<Vec<u8>> {
+        self.global.handle_local_action()
+    }
+
+    #[inline]
+    pub(super) fn local_verify_bench(&self) -> bool {
+        !self.verify_corpus.is_empty()
+    }
+
+    /// verify the next signature in the corpus, all signatures will be used in turn
+    pub(super) fn verify_next_signature(&self) -> anyhow::Result<bool> {
+        let i = self.next_verify_index.fetch_add(1, Ordering::Relaxed) % self.verify_corpus.len();
+        self.global.verify_signature(&self.verify_corpus[i])
+    }
+}
+
+/// load the signatures in hex, one for each line. Empty lines and comment lines are skipped
+fn load_verify_corpus(path: &Path) -> anyhow::Result<Vec<Vec<u8>>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("failed to read file {}: {e}", path.display()))?;
+    let mut corpus = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let sig = hex::decode(line)
+            .map_err(|e| anyhow!("invalid hex signature at line {}: {e}", i + 1))?;
+        corpus.push(sig);
+    }
+    if corpus.is_empty() {
+        return Err(anyhow!("no signature found in file {}", path.display()));
+    }
+    Ok(corpus)
+}
+
+pub(super) fn add_openssl_args(app: Command) -> Command {
+    app.append_keyless_args().arg(
+        Arg::new(ARG_LOCAL_VERIFY_BENCH)
+            .help(
+                "Verify the signatures in this file against the payload locally, \
+                instead of signing. The file should contain one hex signature per line",
+            )
+            .value_name("CORPUS FILE")
+            .long(ARG_LOCAL_VERIFY_BENCH)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::FilePath),
+    )
+}
+
+pub(super) fn parse_openssl_args(args: &ArgMatches) -> anyhow::Result<KeylessOpensslArgs> {
+    let mut global_args =
+        KeylessGlobalArgs::parse_args(args).context("failed to parse global keyless args")?;
+    global_args.setup_cross_check(args, false)?;
+
+    let mut openssl_args = KeylessOpensslArgs::new(global_args);
+
+    if let Some(path) = args.get_one::<PathBuf>(ARG_LOCAL_VERIFY_BENCH) {
+        if !openssl_args.global.multi_keys.is_empty() {
+            return Err(anyhow!(
+                "local verify bench can not be used with multiple keys"
+            ));
+        }
+        let corpus = load_verify_corpus(path)?;
+        // make sure the action is a sign one and the corpus matches the key and payload
+        if !openssl_args.global.verify_signature(&corpus[0])? {
+            return Err(anyhow!(
+                "the first signature in the corpus can not be verified"
+            ));
+        }
+        openssl_args.verify_corpus = corpus;
+    }
+
+    Ok(openssl_args)
+}
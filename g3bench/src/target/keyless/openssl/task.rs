@@ -0,0 +1,169 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::Arc;
+
+use tokio::time::Instant;
+
+#[cfg(feature = "openssl-async-job")]
+use super::KeylessOpensslAsyncJob;
+use super::{
+    BenchTaskContext, KeylessHistogramRecorder, KeylessOpensslArgs, KeylessRuntimeStats, ProcArgs,
+};
+use crate::target::keyless::opts::KeylessAction;
+use crate::target::BenchError;
+
+pub(super) struct KeylessOpensslTaskContext {
+    args: Arc<KeylessOpensslArgs>,
+    #[allow(unused)]
+    proc_args: Arc<ProcArgs>,
+
+    runtime_stats: Arc<KeylessRuntimeStats>,
+    histogram_recorder: KeylessHistogramRecorder,
+}
+
+impl KeylessOpensslTaskContext {
+    pub(super) fn new(
+        args: &Arc<KeylessOpensslArgs>,
+        proc_args: &Arc<ProcArgs>,
+        runtime_stats: &Arc<KeylessRuntimeStats>,
+        histogram_recorder: KeylessHistogramRecorder,
+    ) -> anyhow::Result<Self> {
+        Ok(KeylessOpensslTaskContext {
+            args: Arc::clone(args),
+            proc_args: Arc::clone(proc_args),
+            runtime_stats: Arc::clone(runtime_stats),
+            histogram_recorder,
+        })
+    }
+
+    #[cfg(feature = "openssl-async-job")]
+    async fn run_action(&self) -> anyhow::Result<Vec<u8>> {
+        if self.proc_args.use_unaided_worker && self.proc_args.openssl_async_job_size > 0 {
+            KeylessOpensslAsyncJob::new(self.args.clone()).run().await
+        } else {
+            self.args.handle_action()
+        }
+    }
+
+    #[cfg(not(feature = "openssl-async-job"))]
+    async fn run_action(&self) -> anyhow::Result<Vec<u8>> {
+        self.args.handle_action()
+    }
+}
+
+impl BenchTaskContext for KeylessOpensslTaskContext {
+    fn mark_task_start(&self) {
+        self.runtime_stats.add_task_total();
+        self.runtime_stats.inc_task_alive();
+    }
+
+    fn mark_task_passed(&self) {
+        self.runtime_stats.add_task_passed();
+        self.runtime_stats.dec_task_alive();
+    }
+
+    fn mark_task_failed(&self) {
+        self.runtime_stats.add_task_failed();
+        self.runtime_stats.dec_task_alive();
+    }
+
+    async fn run(&mut self, task_id: usize, time_started: Instant) -> Result<(), BenchError> {
+        if self.args.local_verify_bench() {
+            let valid = self
+                .args
+                .verify_next_signature()
+                .map_err(BenchError::Fatal)?;
+            let total_time = time_started.elapsed();
+            self.histogram_recorder.record_total_time(total_time);
+            self.args
+                .global
+                .check_verify_result(valid)
+                .map_err(BenchError::Task)?;
+            tokio::task::yield_now().await;
+            return Ok(());
+        }
+
+        if let KeylessAction::Verify(_, _) = self.args.global.action {
+            let valid = self.args.global.pkey_verify().map_err(BenchError::Fatal)?;
+            let total_time = time_started.elapsed();
+            self.histogram_recorder.record_total_time(total_time);
+            self.args
+                .global
+                .check_verify_result(valid)
+                .map_err(BenchError::Task)?;
+            tokio::task::yield_now().await;
+            return Ok(());
+        }
+
+        if !self.args.global.multi_keys.is_empty() {
+            let outputs = self.args.global.multi_sign().map_err(BenchError::Fatal)?;
+            let total_time = time_started.elapsed();
+            self.histogram_recorder.record_total_time(total_time);
+            self.args
+                .global
+                .check_multi_result(task_id, outputs)
+                .map_err(BenchError::Task)?;
+            tokio::task::yield_now().await;
+            return Ok(());
+        }
+
+        let output = self.run_action().await.map_err(BenchError::Fatal)?;
+        let total_time = time_started.elapsed();
+        self.histogram_recorder.record_total_time(total_time);
+        self.args
+            .global
+            .check_result(task_id, output)
+            .map_err(BenchError::Task)?;
+        tokio::task::yield_now().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::pkey::PKey;
+    use openssl::sign::Signer;
+
+    use super::super::KeylessHistogram;
+    use crate::target::keyless::opts::tests::local_args;
+    use crate::target::keyless::opts::{KeylessRsaPadding, KeylessSignDigest};
+
+    #[tokio::test]
+    async fn verify_failed() {
+        let private_key = PKey::generate_ed25519().unwrap();
+        let mut signer = Signer::new_without_digest(&private_key).unwrap();
+        let signature = signer.sign_oneshot_to_vec(b"foo").unwrap();
+
+        let action = KeylessAction::Verify(KeylessSignDigest::Sha256, KeylessRsaPadding::Pkcs1);
+        let mut global = local_args(&private_key, action, b"foo".to_vec());
+        global.signature = signature;
+        global.payload = b"bar".to_vec();
+        let args = Arc::new(KeylessOpensslArgs::new(global));
+
+        let proc_args = Arc::new(ProcArgs::default());
+        let runtime_stats = Arc::new(KeylessRuntimeStats::default());
+        let (_histogram, histogram_recorder) = KeylessHistogram::new();
+        let mut context =
+            KeylessOpensslTaskContext::new(&args, &proc_args, &runtime_stats, histogram_recorder)
+                .unwrap();
+
+        let r = context.run(0, Instant::now()).await;
+        assert!(matches!(r, Err(BenchError::Task(_))));
+        assert!(args.global.check_failed_tasks().is_err());
+    }
+}
@@ -0,0 +1,222 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+
+static GLOBAL_STATE: GlobalState = GlobalState::new(None, 0);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+pub(super) fn global_state() -> &'static GlobalState {
+    &GLOBAL_STATE
+}
+
+pub(super) fn mark_force_quit() {
+    GLOBAL_STATE.mark_force_quit();
+}
+
+pub(super) fn mark_interrupted() {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+    GLOBAL_STATE.mark_force_quit();
+}
+
+/// check if the process has been interrupted by SIGINT
+pub(super) fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// the sample rate of logged errors is kept in parts per million
+const LOG_ERROR_RATE_SCALE: u64 = 1_000_000;
+
+/// init the global state for a new run, the counters of the previous run will be reset.
+/// If the error sample rate is set without a log error count, all sampled errors will be logged
+pub(super) fn init_global_state(
+    requests: Option<usize>,
+    log_error_count: usize,
+    log_error_sample_rate: Option<f64>,
+) {
+    GLOBAL_STATE
+        .force_quit
+        .store(interrupted(), Ordering::Relaxed);
+    GLOBAL_STATE.total_passed.store(0, Ordering::Relaxed);
+    GLOBAL_STATE.total_failed.store(0, Ordering::Relaxed);
+    GLOBAL_STATE.request_id.store(0, Ordering::Relaxed);
+    GLOBAL_STATE
+        .check_total
+        .store(requests.is_some(), Ordering::Relaxed);
+    GLOBAL_STATE
+        .total_left
+        .store(requests.unwrap_or_default(), Ordering::Relaxed);
+    let log_error_count = match log_error_sample_rate {
+        Some(_) if log_error_count == 0 => usize::MAX,
+        _ => log_error_count,
+    };
+    GLOBAL_STATE
+        .log_error_left
+        .store(log_error_count, Ordering::Relaxed);
+    let rate = log_error_sample_rate
+        .map(|r| (r * LOG_ERROR_RATE_SCALE as f64) as u64)
+        .unwrap_or(LOG_ERROR_RATE_SCALE);
+    GLOBAL_STATE.log_error_rate.store(rate, Ordering::Relaxed);
+    GLOBAL_STATE.error_seen.store(0, Ordering::Relaxed);
+}
+
+pub(super) struct GlobalState {
+    check_total: AtomicBool,
+    force_quit: AtomicBool,
+    total_left: AtomicUsize,
+    total_passed: AtomicUsize,
+    total_failed: AtomicUsize,
+    log_error_left: AtomicUsize,
+    log_error_rate: AtomicU64,
+    error_seen: AtomicU64,
+    request_id: AtomicUsize,
+}
+
+impl Default for GlobalState {
+    fn default() -> Self {
+        GlobalState::new(None, 0)
+    }
+}
+
+impl GlobalState {
+    pub(super) const fn new(requests: Option<usize>, log_error_count: usize) -> Self {
+        let total_left = match requests {
+            Some(v) => v,
+            None => 0,
+        };
+        GlobalState {
+            check_total: AtomicBool::new(requests.is_some()),
+            force_quit: AtomicBool::new(false),
+            total_left: AtomicUsize::new(total_left),
+            total_passed: AtomicUsize::new(0),
+            total_failed: AtomicUsize::new(0),
+            log_error_left: AtomicUsize::new(log_error_count),
+            log_error_rate: AtomicU64::new(LOG_ERROR_RATE_SCALE),
+            error_seen: AtomicU64::new(0),
+            request_id: AtomicUsize::new(0),
+        }
+    }
+
+    fn mark_force_quit(&self) {
+        self.force_quit.store(true, Ordering::Relaxed);
+    }
+
+    pub(super) fn fetch_request(&self) -> Option<usize> {
+        if self.force_quit.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        if self.check_total.load(Ordering::Relaxed) {
+            let mut curr = self.total_left.load(Ordering::Acquire);
+            loop {
+                if curr == 0 {
+                    return None;
+                }
+
+                match self.total_left.compare_exchange(
+                    curr,
+                    curr - 1,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                ) {
+                    Ok(_) => break,
+                    Err(actual) => curr = actual,
+                }
+            }
+        }
+
+        Some(self.request_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// check if the error should be logged, the errors are sampled evenly by the sample rate
+    pub(super) fn check_log_error(&self) -> bool {
+        let rate = self.log_error_rate.load(Ordering::Relaxed);
+        if rate < LOG_ERROR_RATE_SCALE {
+            let n = self.error_seen.fetch_add(1, Ordering::Relaxed);
+            if (n + 1) * rate / LOG_ERROR_RATE_SCALE == n * rate / LOG_ERROR_RATE_SCALE {
+                return false;
+            }
+        }
+
+        let mut curr = self.log_error_left.load(Ordering::Acquire);
+        loop {
+            if curr == 0 {
+                return false;
+            }
+
+            match self.log_error_left.compare_exchange(
+                curr,
+                curr - 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => curr = actual,
+            }
+        }
+    }
+
+    pub(super) fn add_passed(&self) {
+        self.total_passed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn add_failed(&self) {
+        self.total_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn total_passed(&self) -> usize {
+        self.total_passed.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn total_failed(&self) -> usize {
+        self.total_failed.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn summary(&self, total_time: Duration, distribution: &Histogram<u64>) {
+        println!("Time taken for tests: {total_time:?}");
+
+        let passed = self.total_passed.load(Ordering::Relaxed);
+        println!("Complete requests:    {passed:<10}");
+
+        let failed = self.total_failed.load(Ordering::Relaxed);
+        if failed > 0 {
+            println!("Failed requests:      {failed}");
+        }
+
+        let left = self.total_left.load(Ordering::Relaxed);
+        if left > 0 {
+            println!("Left requests:        {left}");
+        }
+
+        println!(
+            "Requests per second:  {:.3} [#/sec] (mean)",
+            passed as f64 / total_time.as_secs_f64()
+        );
+
+        println!("Requests distribution:");
+        println!("  min   {}", distribution.min());
+        println!(
+            "  mean  {:.2}[+/- {:.2}]",
+            distribution.mean(),
+            distribution.stdev()
+        );
+        println!("  pct90 {}", distribution.value_at_percentile(90.0));
+        println!("  max   {}", distribution.max());
+    }
+}
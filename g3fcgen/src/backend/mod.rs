@@ -21,9 +21,13 @@ use std::time::Duration;
 use anyhow::anyhow;
 use flume::{Receiver, Sender};
 use log::{debug, error, warn};
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::verify::{X509VerifyFlags, X509VerifyParam};
+use openssl::x509::{X509StoreContext, X509};
 use tokio::runtime::Handle;
 
-use g3_tls_cert::builder::{ServerCertBuilder, TlsServerCertBuilder};
+use g3_tls_cert::builder::{IntermediateCertBuilder, ServerCertBuilder, TlsServerCertBuilder};
 use g3_types::net::Host;
 
 mod stats;
@@ -44,7 +48,17 @@ impl OpensslBackend {
         config: &Arc<OpensslBackendConfig>,
         stats: &Arc<BackendStats>,
     ) -> anyhow::Result<Self> {
-        let builder = TlsServerCertBuilder::new_ec256()?;
+        let mut builder = TlsServerCertBuilder::new_ec256()?;
+        let subject_builder = builder.subject_builder_mut();
+        if let Some(c) = &config.leaf_subject.country {
+            subject_builder.set_country(c.to_string());
+        }
+        if let Some(o) = &config.leaf_subject.organization {
+            subject_builder.set_organization(o.to_string());
+        }
+        if let Some(ou) = &config.leaf_subject.organization_unit {
+            subject_builder.set_organization_unit(ou.to_string());
+        }
         Ok(OpensslBackend {
             config: Arc::clone(config),
             builder,
@@ -64,14 +78,13 @@ impl OpensslBackend {
         self.stats.add_request_total();
         let host = Host::from_str(host)?;
         self.builder.refresh_serial()?;
-        let cert =
-            self.builder
-                .build_fake(&host, &self.config.ca_cert, &self.config.ca_key, None)?;
+        let (ca_cert, ca_key, ca_cert_pem) = self.config.active_ca();
+        let cert = self.builder.build_fake(&host, ca_cert, ca_key, None)?;
         let mut cert_pem = cert
             .to_pem()
             .map_err(|e| anyhow!("failed to encode cert: {e}"))?;
-        if !self.config.ca_cert_pem.is_empty() {
-            cert_pem.extend_from_slice(&self.config.ca_cert_pem);
+        if !ca_cert_pem.is_empty() {
+            cert_pem.extend_from_slice(ca_cert_pem);
         }
         let key_pem = self
             .builder
@@ -89,6 +102,96 @@ impl OpensslBackend {
         Ok(data)
     }
 
+    /// issue an intermediate ca cert with the active ca, the returned cert pem contains
+    /// the same ca chain as the leaf certs. The configured pathlen is only set here
+    pub(crate) fn issue_ca(&self, common_name: &str) -> anyhow::Result<ResponseData> {
+        let mut builder = IntermediateCertBuilder::new_ec256()?;
+        builder
+            .subject_builder_mut()
+            .set_common_name(common_name.to_string());
+        let (ca_cert, ca_key, ca_cert_pem) = self.config.active_ca();
+        let cert = builder.build(self.config.issued_ca_pathlen, ca_cert, ca_key, None)?;
+        let mut cert_pem = cert
+            .to_pem()
+            .map_err(|e| anyhow!("failed to encode cert: {e}"))?;
+        cert_pem.extend_from_slice(ca_cert_pem);
+        let key_pem = builder
+            .pkey()
+            .private_key_to_pem_pkcs8()
+            .map_err(|e| anyhow!("failed to encode pkey: {e}"))?;
+
+        Ok(ResponseData {
+            host: common_name.to_string(),
+            cert: unsafe { String::from_utf8_unchecked(cert_pem) },
+            key: unsafe { String::from_utf8_unchecked(key_pem) },
+            ttl: 0,
+        })
+    }
+
+    /// verify the generated certificate chain against the CA and the extra roots
+    pub(crate) fn verify(
+        &self,
+        host: &str,
+        data: &ResponseData,
+        extra_roots: &[X509],
+    ) -> anyhow::Result<()> {
+        let certs = X509::stack_from_pem(data.cert.as_bytes())
+            .map_err(|e| anyhow!("invalid generated certificate: {e}"))?;
+        let mut certs = certs.into_iter();
+        let leaf = certs
+            .next()
+            .ok_or_else(|| anyhow!("no certificate generated"))?;
+        let mut chain =
+            Stack::new().map_err(|e| anyhow!("failed to create certificate stack: {e}"))?;
+        for cert in certs {
+            chain
+                .push(cert)
+                .map_err(|e| anyhow!("failed to push certificate to stack: {e}"))?;
+        }
+
+        let mut param =
+            X509VerifyParam::new().map_err(|e| anyhow!("failed to create verify param: {e}"))?;
+        match Host::from_str(host)? {
+            Host::Ip(ip) => param.set_ip(ip),
+            Host::Domain(domain) => param.set_host(&domain),
+        }
+        .map_err(|e| anyhow!("failed to set verify host: {e}"))?;
+        // the configured CA may be an intermediate one
+        param
+            .set_flags(X509VerifyFlags::PARTIAL_CHAIN)
+            .map_err(|e| anyhow!("failed to set verify flags: {e}"))?;
+
+        let mut store_builder =
+            X509StoreBuilder::new().map_err(|e| anyhow!("failed to create store builder: {e}"))?;
+        let (ca_cert, _, _) = self.config.active_ca();
+        store_builder
+            .add_cert(ca_cert.clone())
+            .map_err(|e| anyhow!("failed to add ca certificate: {e}"))?;
+        for cert in extra_roots {
+            store_builder
+                .add_cert(cert.clone())
+                .map_err(|e| anyhow!("failed to add root certificate: {e}"))?;
+        }
+        store_builder
+            .set_param(&param)
+            .map_err(|e| anyhow!("failed to set verify param: {e}"))?;
+        let store = store_builder.build();
+
+        let mut ctx =
+            X509StoreContext::new().map_err(|e| anyhow!("failed to create store context: {e}"))?;
+        let (verified, reason) = ctx
+            .init(&store, &leaf, &chain, |c| {
+                let verified = c.verify_cert()?;
+                Ok((verified, c.error()))
+            })
+            .map_err(|e| anyhow!("failed to verify certificate: {e}"))?;
+        if verified {
+            Ok(())
+        } else {
+            Err(anyhow!("certificate verify failed: {reason}"))
+        }
+    }
+
     pub(crate) fn spawn(
         mut self,
         handle: &Handle,
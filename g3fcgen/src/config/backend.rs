@@ -14,12 +14,19 @@
  * limitations under the License.
  */
 
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::sync::OnceLock;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context};
-use openssl::pkey::{PKey, Private};
-use openssl::x509::X509;
+use log::{info, warn};
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{Id, PKey, Private};
+use openssl::sign::Signer;
+use openssl::x509::{X509VerifyResult, X509};
 use yaml_rust::Yaml;
 
 use g3_histogram::HistogramMetricsConfig;
@@ -30,38 +37,347 @@ pub(crate) fn get_config() -> Option<Arc<OpensslBackendConfig>> {
     BACKEND_CONFIG_LOCK.get().cloned()
 }
 
+#[derive(Clone, Default)]
+pub(crate) struct LeafSubjectConfig {
+    pub(crate) country: Option<String>,
+    pub(crate) organization: Option<String>,
+    pub(crate) organization_unit: Option<String>,
+}
+
+impl LeafSubjectConfig {
+    fn parse(value: &Yaml) -> anyhow::Result<Self> {
+        let Yaml::Hash(map) = value else {
+            return Err(anyhow!(
+                "yaml value type for the leaf subject config should be 'map'"
+            ));
+        };
+
+        let mut config = LeafSubjectConfig::default();
+        g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
+            "country" | "c" => {
+                let c = g3_yaml::value::as_string(v)?;
+                if c.len() != 2 || !c.chars().all(|c| c.is_ascii_alphabetic()) {
+                    return Err(anyhow!(
+                        "invalid country name {c}: should be 2 letter ISO 3166 code"
+                    ));
+                }
+                config.country = Some(c.to_ascii_uppercase());
+                Ok(())
+            }
+            "organization" | "o" => {
+                let o = g3_yaml::value::as_string(v)?;
+                check_dn_value(&o).context(format!("invalid value for key {k}"))?;
+                config.organization = Some(o);
+                Ok(())
+            }
+            "organization_unit" | "ou" => {
+                let ou = g3_yaml::value::as_string(v)?;
+                check_dn_value(&ou).context(format!("invalid value for key {k}"))?;
+                config.organization_unit = Some(ou);
+                Ok(())
+            }
+            _ => Err(anyhow!("invalid key {k}")),
+        })?;
+        Ok(config)
+    }
+}
+
+fn check_dn_value(s: &str) -> anyhow::Result<()> {
+    // the upper bound for O and OU in RFC 5280
+    const MAX_LENGTH: usize = 64;
+
+    if s.is_empty() {
+        return Err(anyhow!("empty value is not allowed"));
+    }
+    if s.chars().count() > MAX_LENGTH {
+        return Err(anyhow!("the value should be no longer than {MAX_LENGTH}"));
+    }
+    if s.chars().any(|c| c.is_control()) {
+        return Err(anyhow!("control characters are not allowed"));
+    }
+    Ok(())
+}
+
+#[cfg(not(any(feature = "vendored-aws-lc", feature = "vendored-boringssl")))]
+fn load_provider_key(provider: Option<&str>, uri: &str) -> anyhow::Result<PKey<Private>> {
+    if let Some(name) = provider {
+        // the default provider won't be loaded automatically if we load one explicitly
+        g3_tls_cert::ext::load_provider("default")?;
+        g3_tls_cert::ext::load_provider(name)?;
+    }
+    g3_tls_cert::ext::load_private_key_by_uri(uri)
+}
+
+#[cfg(any(feature = "vendored-aws-lc", feature = "vendored-boringssl"))]
+fn load_provider_key(_provider: Option<&str>, _uri: &str) -> anyhow::Result<PKey<Private>> {
+    Err(anyhow!(
+        "loading private key by uri is not supported by the openssl variant"
+    ))
+}
+
+/// build the ca cert chain to be appended after the issued leaf cert,
+/// the self-signed root certs will be skipped if `append_root` is false
+fn build_ca_chain_pem(certs: &[X509], append_root: bool) -> anyhow::Result<Vec<u8>> {
+    let mut chain_pem = Vec::new();
+    for (i, cert) in certs.iter().enumerate() {
+        if !append_root && cert.issued(cert) == X509VerifyResult::OK {
+            continue;
+        }
+        let pem = cert
+            .to_pem()
+            .map_err(|e| anyhow!("failed to convert cert {i} back to pem format: {e}"))?;
+        chain_pem.extend(pem);
+    }
+    Ok(chain_pem)
+}
+
+/// make sure the key is usable by doing a real sign
+fn probe_sign(key: &PKey<Private>) -> anyhow::Result<()> {
+    #[cfg(not(any(feature = "vendored-aws-lc", feature = "vendored-boringssl")))]
+    let no_digest = matches!(key.id(), Id::ED25519 | Id::ED448);
+    #[cfg(any(feature = "vendored-aws-lc", feature = "vendored-boringssl"))]
+    let no_digest = key.id() == Id::ED25519;
+
+    let mut signer = if no_digest {
+        Signer::new_without_digest(key)
+    } else {
+        Signer::new(MessageDigest::sha256(), key)
+    }
+    .map_err(|e| anyhow!("failed to create signer: {e}"))?;
+    signer
+        .sign_oneshot_to_vec(b"g3fcgen sign probe")
+        .map_err(|e| anyhow!("sign failed: {e}"))?;
+    Ok(())
+}
+
+/// log the remaining validity of the ca cert, and warn if it's less than the threshold,
+/// or return error if `deny` is set
+fn check_ca_expiry(cert: &X509, threshold: Duration, deny: bool) -> anyhow::Result<()> {
+    let now = Asn1Time::days_from_now(0).map_err(|e| anyhow!("failed to get now time: {e}"))?;
+    let diff = now
+        .diff(cert.not_after())
+        .map_err(|e| anyhow!("failed to get the remaining validity: {e}"))?;
+    let remaining = diff.days as i64 * 86400 + diff.secs as i64;
+    let subject = subject_string(cert);
+
+    let msg = if remaining <= 0 {
+        format!(
+            "ca certificate {subject} has expired for {}",
+            format_validity(-remaining)
+        )
+    } else if (remaining as u64) < threshold.as_secs() {
+        format!(
+            "ca certificate {subject} will expire in {}",
+            format_validity(remaining)
+        )
+    } else {
+        info!(
+            "ca certificate {subject} will expire in {}",
+            format_validity(remaining)
+        );
+        return Ok(());
+    };
+    if deny {
+        Err(anyhow!(msg))
+    } else {
+        warn!("{msg}, the issued certs will not be trusted after that");
+        Ok(())
+    }
+}
+
+/// the basicConstraints pathlen to set in the issued intermediate ca certs
+fn parse_ca_pathlen(v: &Yaml) -> anyhow::Result<u32> {
+    let len = g3_yaml::value::as_i64(v)?;
+    if len < 0 {
+        return Err(anyhow!("pathlen should be non-negative"));
+    }
+    u32::try_from(len).map_err(|_| anyhow!("pathlen {len} is too large"))
+}
+
+/// make sure the pathlen of the issued intermediate ca certs is allowed by the ca cert
+fn check_ca_pathlen(cert: &X509, issued_pathlen: u32) -> anyhow::Result<()> {
+    match cert.pathlen() {
+        Some(0) => Err(anyhow!(
+            "ca certificate {} doesn't allow issuing intermediate ca certs",
+            subject_string(cert)
+        )),
+        Some(len) if issued_pathlen >= len => Err(anyhow!(
+            "the issued ca pathlen should be less than {len}, the pathlen of ca certificate {}",
+            subject_string(cert)
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn format_validity(secs: i64) -> String {
+    format!("{} days {} hours", secs / 86400, (secs % 86400) / 3600)
+}
+
+/// format the subject name of the cert like "CN=..., O=..."
+pub(crate) fn subject_string(cert: &X509) -> String {
+    let mut parts = Vec::new();
+    for entry in cert.subject_name().entries() {
+        let name = entry.object().nid().short_name().unwrap_or("?");
+        match entry.data().as_utf8() {
+            Ok(v) => parts.push(format!("{name}={v}")),
+            Err(_) => parts.push(format!("{name}=<invalid>")),
+        }
+    }
+    parts.join(", ")
+}
+
+pub(crate) struct RolloverCaConfig {
+    pub(crate) ca_cert: X509,
+    pub(crate) ca_key: PKey<Private>,
+    pub(crate) ca_cert_pem: Vec<u8>,
+}
+
 pub(crate) struct OpensslBackendConfig {
     pub(crate) ca_cert: X509,
     pub(crate) ca_key: PKey<Private>,
     pub(crate) ca_cert_pem: Vec<u8>,
+    pub(crate) rollover_ca: Option<RolloverCaConfig>,
+    use_rollover_ca: AtomicBool,
+    pub(crate) leaf_subject: LeafSubjectConfig,
+    pub(crate) issued_ca_pathlen: Option<u32>,
     pub(crate) duration_stats: HistogramMetricsConfig,
+    pub(crate) startup_check: bool,
+    pub(crate) deny_startup_check_failure: bool,
+}
+
+impl OpensslBackendConfig {
+    fn with_ca(&self, ca_cert: &X509, ca_key: &PKey<Private>, ca_cert_pem: &[u8]) -> Self {
+        OpensslBackendConfig {
+            ca_cert: ca_cert.clone(),
+            ca_key: ca_key.clone(),
+            ca_cert_pem: ca_cert_pem.to_vec(),
+            rollover_ca: None,
+            use_rollover_ca: AtomicBool::new(false),
+            leaf_subject: self.leaf_subject.clone(),
+            issued_ca_pathlen: self.issued_ca_pathlen,
+            duration_stats: self.duration_stats.clone(),
+            startup_check: false,
+            deny_startup_check_failure: false,
+        }
+    }
+
+    /// get a standalone config for each of the configured ca, so they can be checked separately
+    pub(crate) fn each_ca(&self) -> Vec<(&'static str, Arc<OpensslBackendConfig>)> {
+        let mut configs = vec![(
+            "primary",
+            Arc::new(self.with_ca(&self.ca_cert, &self.ca_key, &self.ca_cert_pem)),
+        )];
+        if let Some(ca) = &self.rollover_ca {
+            configs.push((
+                "rollover",
+                Arc::new(self.with_ca(&ca.ca_cert, &ca.ca_key, &ca.ca_cert_pem)),
+            ));
+        }
+        configs
+    }
+
+    /// get the cert, key and the chain pem to append of the active ca
+    pub(crate) fn active_ca(&self) -> (&X509, &PKey<Private>, &[u8]) {
+        match &self.rollover_ca {
+            Some(ca) if self.use_rollover_ca.load(Ordering::Acquire) => {
+                (&ca.ca_cert, &ca.ca_key, &ca.ca_cert_pem)
+            }
+            _ => (&self.ca_cert, &self.ca_key, &self.ca_cert_pem),
+        }
+    }
+
+    /// switch the active ca between the primary and the rollover one, and return the now
+    /// active ca cert. The issuing that already started will complete with the previous one
+    pub(crate) fn rotate_ca(&self) -> anyhow::Result<&X509> {
+        let Some(ca) = &self.rollover_ca else {
+            return Err(anyhow!("no rollover ca configured"));
+        };
+        if self.use_rollover_ca.fetch_xor(true, Ordering::AcqRel) {
+            Ok(&self.ca_cert)
+        } else {
+            Ok(&ca.ca_cert)
+        }
+    }
+}
+
+/// build a standalone backend config with the candidate ca, which will not affect the live one.
+/// The leaf subject is copied from the live config so the probe issues the same leaf certs
+pub(crate) fn load_candidate(
+    cert_file: &Path,
+    key_file: &Path,
+    live: Option<&OpensslBackendConfig>,
+) -> anyhow::Result<Arc<OpensslBackendConfig>> {
+    let contents = std::fs::read(cert_file)
+        .map_err(|e| anyhow!("failed to read file {}: {e}", cert_file.display()))?;
+    let ca_certs = X509::stack_from_pem(&contents)
+        .map_err(|e| anyhow!("invalid certificate file {}: {e}", cert_file.display()))?;
+    let contents = std::fs::read(key_file)
+        .map_err(|e| anyhow!("failed to read file {}: {e}", key_file.display()))?;
+    let ca_key = PKey::private_key_from_pem(&contents)
+        .map_err(|e| anyhow!("invalid private key file {}: {e}", key_file.display()))?;
+    let leaf_subject = live.map(|c| c.leaf_subject.clone()).unwrap_or_default();
+    build_candidate(ca_certs, ca_key, leaf_subject)
+}
+
+fn build_candidate(
+    ca_certs: Vec<X509>,
+    ca_key: PKey<Private>,
+    leaf_subject: LeafSubjectConfig,
+) -> anyhow::Result<Arc<OpensslBackendConfig>> {
+    let Some(ca_cert) = ca_certs.first().cloned() else {
+        return Err(anyhow!("no ca certificate found"));
+    };
+    // only deny the already expired one, the threshold is a matter of the live config
+    check_ca_expiry(&ca_cert, Duration::ZERO, true)?;
+    let cert_key = ca_cert
+        .public_key()
+        .map_err(|e| anyhow!("failed to get ca public key: {e}"))?;
+    if !cert_key.public_eq(&ca_key) {
+        return Err(anyhow!("the ca private key does not match the certificate"));
+    }
+    probe_sign(&ca_key).context("the ca private key is not usable")?;
+    let ca_cert_pem = build_ca_chain_pem(&ca_certs, true)?;
+    Ok(Arc::new(OpensslBackendConfig {
+        ca_cert,
+        ca_key,
+        ca_cert_pem,
+        rollover_ca: None,
+        use_rollover_ca: AtomicBool::new(false),
+        leaf_subject,
+        issued_ca_pathlen: None,
+        duration_stats: HistogramMetricsConfig::default(),
+        startup_check: false,
+        deny_startup_check_failure: false,
+    }))
 }
 
 pub(super) fn load_config(value: &Yaml) -> anyhow::Result<()> {
     if let Yaml::Hash(map) = value {
         let mut no_append_ca_cert = false;
-        let mut ca_cert_pem = Vec::new();
-        let mut ca_cert: Option<X509> = None;
+        let mut no_append_root_ca_cert = false;
+        let mut ca_certs: Vec<X509> = Vec::new();
         let mut ca_key: Option<PKey<Private>> = None;
+        let mut ca_key_provider: Option<String> = None;
+        let mut ca_key_uri: Option<String> = None;
+        let mut rollover_ca_certs: Vec<X509> = Vec::new();
+        let mut rollover_ca_key: Option<PKey<Private>> = None;
+        let mut leaf_subject = LeafSubjectConfig::default();
+        let mut issued_ca_pathlen: Option<u32> = None;
         let mut duration_stats = HistogramMetricsConfig::default();
+        let mut ca_expire_threshold = Duration::from_secs(30 * 86400);
+        let mut deny_expiring_ca = false;
+        let mut startup_check = false;
+        let mut deny_startup_check_failure = false;
         let lookup_dir = g3_daemon::config::get_lookup_dir(None)?;
 
         g3_yaml::foreach_kv(map, |k, v| match g3_yaml::key::normalize(k).as_str() {
             "ca_certificate" => {
                 let certs = g3_yaml::value::as_openssl_certificates(v, Some(lookup_dir))
                     .context(format!("invalid openssl certificate value for key {k}"))?;
-                for (i, cert) in certs.iter().enumerate() {
-                    let pem = cert.to_pem().map_err(|e| {
-                        anyhow!("failed to convert cert {i} back to pem format: {e}")
-                    })?;
-                    ca_cert_pem.extend(pem);
+                if certs.is_empty() {
+                    return Err(anyhow!("no valid openssl certificate key found"));
                 }
-
-                let cert = certs
-                    .into_iter()
-                    .next()
-                    .ok_or_else(|| anyhow!("no valid openssl certificate key found"))?;
-                ca_cert = Some(cert);
+                ca_certs = certs;
                 Ok(())
             }
             "ca_private_key" => {
@@ -70,10 +386,65 @@ pub(super) fn load_config(value: &Yaml) -> anyhow::Result<()> {
                 ca_key = Some(key);
                 Ok(())
             }
+            "rollover_ca_certificate" => {
+                let certs = g3_yaml::value::as_openssl_certificates(v, Some(lookup_dir))
+                    .context(format!("invalid openssl certificate value for key {k}"))?;
+                if certs.is_empty() {
+                    return Err(anyhow!("no valid openssl certificate key found"));
+                }
+                rollover_ca_certs = certs;
+                Ok(())
+            }
+            "rollover_ca_private_key" => {
+                let key = g3_yaml::value::as_openssl_private_key(v, Some(lookup_dir))
+                    .context(format!("invalid openssl private key value for key {k}"))?;
+                rollover_ca_key = Some(key);
+                Ok(())
+            }
+            "ca_private_key_provider" => {
+                ca_key_provider = Some(g3_yaml::value::as_string(v)?);
+                Ok(())
+            }
+            "ca_private_key_uri" => {
+                ca_key_uri = Some(g3_yaml::value::as_string(v)?);
+                Ok(())
+            }
+            "ca_expire_threshold" => {
+                ca_expire_threshold = g3_yaml::humanize::as_duration(v)
+                    .context(format!("invalid humanize duration value for key {k}"))?;
+                Ok(())
+            }
+            "deny_expiring_ca" => {
+                deny_expiring_ca = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
+            "startup_check" => {
+                startup_check = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
+            "deny_startup_check_failure" => {
+                deny_startup_check_failure = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
             "no_append_ca_cert" => {
                 no_append_ca_cert = g3_yaml::value::as_bool(v)?;
                 Ok(())
             }
+            "no_append_root_ca_cert" => {
+                no_append_root_ca_cert = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
+            "leaf_subject" => {
+                leaf_subject = LeafSubjectConfig::parse(v)
+                    .context(format!("invalid leaf subject config value for key {k}"))?;
+                Ok(())
+            }
+            "issued_ca_pathlen" => {
+                let len =
+                    parse_ca_pathlen(v).context(format!("invalid pathlen value for key {k}"))?;
+                issued_ca_pathlen = Some(len);
+                Ok(())
+            }
             "duration_stats" | "duration_metrics" => {
                 duration_stats = g3_yaml::value::as_histogram_metrics_config(v).context(
                     format!("invalid histogram metrics config value for key {k}"),
@@ -83,22 +454,83 @@ pub(super) fn load_config(value: &Yaml) -> anyhow::Result<()> {
             _ => Err(anyhow!("invalid key {k}")),
         })?;
 
-        let Some(ca_cert) = ca_cert else {
+        let Some(ca_cert) = ca_certs.first().cloned() else {
             return Err(anyhow!("no ca certificate set"));
         };
+        check_ca_expiry(&ca_cert, ca_expire_threshold, deny_expiring_ca)?;
+        if let Some(len) = issued_ca_pathlen {
+            check_ca_pathlen(&ca_cert, len)?;
+        }
+        if let Some(uri) = &ca_key_uri {
+            if ca_key.is_some() {
+                return Err(anyhow!(
+                    "ca_private_key and ca_private_key_uri should not be set at the same time"
+                ));
+            }
+            let key = load_provider_key(ca_key_provider.as_deref(), uri)
+                .context(format!("failed to load ca private key from uri {uri}"))?;
+            probe_sign(&key).context("the ca private key loaded from uri is not usable")?;
+            ca_key = Some(key);
+        } else if ca_key_provider.is_some() {
+            return Err(anyhow!(
+                "ca_private_key_uri should be set if ca_private_key_provider is set"
+            ));
+        }
         let Some(ca_key) = ca_key else {
             return Err(anyhow!("no ca private key set"));
         };
 
-        if no_append_ca_cert {
-            ca_cert_pem.clear();
-        }
+        let ca_cert_pem = if no_append_ca_cert {
+            Vec::new()
+        } else {
+            build_ca_chain_pem(&ca_certs, !no_append_root_ca_cert)?
+        };
+
+        let rollover_ca = match (rollover_ca_certs.first(), rollover_ca_key) {
+            (Some(cert), Some(key)) => {
+                check_ca_expiry(cert, ca_expire_threshold, deny_expiring_ca)
+                    .context("invalid rollover ca certificate")?;
+                if let Some(len) = issued_ca_pathlen {
+                    check_ca_pathlen(cert, len).context("invalid rollover ca certificate")?;
+                }
+                let cert_key = cert
+                    .public_key()
+                    .map_err(|e| anyhow!("failed to get rollover ca public key: {e}"))?;
+                if !cert_key.public_eq(&key) {
+                    return Err(anyhow!(
+                        "the rollover ca private key does not match the certificate"
+                    ));
+                }
+                let ca_cert_pem = if no_append_ca_cert {
+                    Vec::new()
+                } else {
+                    build_ca_chain_pem(&rollover_ca_certs, !no_append_root_ca_cert)?
+                };
+                Some(RolloverCaConfig {
+                    ca_cert: cert.clone(),
+                    ca_key: key,
+                    ca_cert_pem,
+                })
+            }
+            (None, None) => None,
+            _ => {
+                return Err(anyhow!(
+                    "rollover_ca_certificate and rollover_ca_private_key should be set together"
+                ))
+            }
+        };
         BACKEND_CONFIG_LOCK
             .set(Arc::new(OpensslBackendConfig {
                 ca_cert,
                 ca_key,
                 ca_cert_pem,
+                rollover_ca,
+                use_rollover_ca: AtomicBool::new(false),
+                leaf_subject,
+                issued_ca_pathlen,
                 duration_stats,
+                startup_check,
+                deny_startup_check_failure,
             }))
             .map_err(|_| anyhow!("duplicate backend config"))?;
         Ok(())
@@ -108,3 +540,176 @@ pub(super) fn load_config(value: &Yaml) -> anyhow::Result<()> {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use g3_tls_cert::builder::{IntermediateCertBuilder, RootCertBuilder};
+
+    use crate::backend::{BackendStats, OpensslBackend};
+
+    fn generate_chain(append_root: bool) -> Vec<X509> {
+        let mut root_builder = RootCertBuilder::new_ec256().unwrap();
+        root_builder
+            .subject_builder_mut()
+            .set_common_name("test root".to_string());
+        let root_cert = root_builder.build(None).unwrap();
+
+        let mut intermediate_builder = IntermediateCertBuilder::new_ec256().unwrap();
+        intermediate_builder
+            .subject_builder_mut()
+            .set_common_name("test intermediate".to_string());
+        let intermediate_cert = intermediate_builder
+            .build(None, &root_cert, root_builder.pkey(), None)
+            .unwrap();
+
+        let ca_certs = vec![intermediate_cert.clone(), root_cert];
+        let config = Arc::new(OpensslBackendConfig {
+            ca_cert: intermediate_cert,
+            ca_key: intermediate_builder.pkey().clone(),
+            ca_cert_pem: build_ca_chain_pem(&ca_certs, append_root).unwrap(),
+            rollover_ca: None,
+            use_rollover_ca: AtomicBool::new(false),
+            leaf_subject: LeafSubjectConfig::default(),
+            issued_ca_pathlen: None,
+            duration_stats: HistogramMetricsConfig::default(),
+            startup_check: false,
+            deny_startup_check_failure: false,
+        });
+        let stats = Arc::new(BackendStats::default());
+        let mut backend = OpensslBackend::new(&config, &stats).unwrap();
+        let data = backend.generate("www.example.net").unwrap();
+        X509::stack_from_pem(data.cert.as_bytes()).unwrap()
+    }
+
+    fn common_name(cert: &X509) -> String {
+        let entry = cert
+            .subject_name()
+            .entries_by_nid(openssl::nid::Nid::COMMONNAME)
+            .next()
+            .unwrap();
+        entry.data().as_utf8().unwrap().to_string()
+    }
+
+    #[test]
+    fn chain_order() {
+        let chain = generate_chain(true);
+        assert_eq!(chain.len(), 3);
+        assert_eq!(common_name(&chain[0]), "www.example.net");
+        assert_eq!(common_name(&chain[1]), "test intermediate");
+        assert_eq!(common_name(&chain[2]), "test root");
+
+        let chain = generate_chain(false);
+        assert_eq!(chain.len(), 2);
+        assert_eq!(common_name(&chain[0]), "www.example.net");
+        assert_eq!(common_name(&chain[1]), "test intermediate");
+    }
+
+    #[test]
+    fn rotate_ca() {
+        let new_ca = |name: &str| {
+            let mut builder = RootCertBuilder::new_ec256().unwrap();
+            builder
+                .subject_builder_mut()
+                .set_common_name(name.to_string());
+            let cert = builder.build(None).unwrap();
+            (cert, builder.pkey().clone())
+        };
+        let (primary_cert, primary_key) = new_ca("primary");
+        let (rollover_cert, rollover_key) = new_ca("rollover");
+
+        let config = OpensslBackendConfig {
+            ca_cert: primary_cert,
+            ca_key: primary_key,
+            ca_cert_pem: Vec::new(),
+            rollover_ca: Some(RolloverCaConfig {
+                ca_cert: rollover_cert,
+                ca_key: rollover_key,
+                ca_cert_pem: Vec::new(),
+            }),
+            use_rollover_ca: AtomicBool::new(false),
+            leaf_subject: LeafSubjectConfig::default(),
+            issued_ca_pathlen: None,
+            duration_stats: HistogramMetricsConfig::default(),
+            startup_check: false,
+            deny_startup_check_failure: false,
+        };
+        let each_ca = config.each_ca();
+        assert_eq!(each_ca.len(), 2);
+        assert_eq!(common_name(&each_ca[1].1.ca_cert), "rollover");
+        assert_eq!(common_name(config.active_ca().0), "primary");
+        assert_eq!(subject_string(config.rotate_ca().unwrap()), "CN=rollover");
+        assert_eq!(common_name(config.active_ca().0), "rollover");
+        assert_eq!(common_name(config.rotate_ca().unwrap()), "primary");
+        assert_eq!(common_name(config.active_ca().0), "primary");
+    }
+
+    #[test]
+    fn candidate_ca() {
+        let new_ca = || {
+            let builder = RootCertBuilder::new_ec256().unwrap();
+            let cert = builder.build(None).unwrap();
+            (cert, builder.pkey().clone())
+        };
+        let (cert, key) = new_ca();
+        let (_, other_key) = new_ca();
+
+        assert!(build_candidate(vec![cert.clone()], other_key, Default::default()).is_err());
+        assert!(build_candidate(Vec::new(), key.clone(), Default::default()).is_err());
+
+        let config = build_candidate(vec![cert], key, Default::default()).unwrap();
+        let stats = Arc::new(BackendStats::default());
+        let mut backend = OpensslBackend::new(&config, &stats).unwrap();
+        let data = backend.generate("www.example.net").unwrap();
+        backend.verify("www.example.net", &data, &[]).unwrap();
+    }
+
+    #[test]
+    fn issued_ca_pathlen() {
+        assert_eq!(parse_ca_pathlen(&Yaml::Integer(1)).unwrap(), 1);
+        assert!(parse_ca_pathlen(&Yaml::Integer(-1)).is_err());
+
+        let root_builder = RootCertBuilder::new_ec256().unwrap();
+        let root_cert = root_builder.build(None).unwrap();
+        assert!(check_ca_pathlen(&root_cert, 1).is_ok());
+        let sub_cert = IntermediateCertBuilder::new_ec256()
+            .unwrap()
+            .build(Some(0), &root_cert, root_builder.pkey(), None)
+            .unwrap();
+        assert!(check_ca_pathlen(&sub_cert, 0).is_err());
+
+        let config = Arc::new(OpensslBackendConfig {
+            ca_cert: root_cert,
+            ca_key: root_builder.pkey().clone(),
+            ca_cert_pem: Vec::new(),
+            rollover_ca: None,
+            use_rollover_ca: AtomicBool::new(false),
+            leaf_subject: LeafSubjectConfig::default(),
+            issued_ca_pathlen: Some(1),
+            duration_stats: HistogramMetricsConfig::default(),
+            startup_check: false,
+            deny_startup_check_failure: false,
+        });
+        let stats = Arc::new(BackendStats::default());
+        let mut backend = OpensslBackend::new(&config, &stats).unwrap();
+
+        let data = backend.issue_ca("test intermediate").unwrap();
+        let ca_cert = X509::from_pem(data.cert.as_bytes()).unwrap();
+        assert_eq!(ca_cert.pathlen(), Some(1));
+
+        let data = backend.generate("www.example.net").unwrap();
+        let leaf_cert = X509::from_pem(data.cert.as_bytes()).unwrap();
+        assert_eq!(leaf_cert.pathlen(), None);
+    }
+
+    #[test]
+    fn ca_expiry() {
+        let builder = RootCertBuilder::new_ec256().unwrap();
+        let cert = builder.build(None).unwrap();
+
+        // the test root cert is valid for 3650 days
+        assert!(check_ca_expiry(&cert, Duration::from_secs(30 * 86400), true).is_ok());
+        assert!(check_ca_expiry(&cert, Duration::from_secs(3651 * 86400), true).is_err());
+        assert!(check_ca_expiry(&cert, Duration::from_secs(3651 * 86400), false).is_ok());
+    }
+}
@@ -20,7 +20,10 @@ use anyhow::anyhow;
 use yaml_rust::{yaml, Yaml};
 
 mod backend;
-pub(crate) use backend::{get_config as get_backend_config, OpensslBackendConfig};
+pub(crate) use backend::{
+    get_config as get_backend_config, load_candidate as load_candidate_backend_config,
+    subject_string, OpensslBackendConfig,
+};
 
 pub fn load() -> anyhow::Result<&'static Path> {
     let config_file =
@@ -17,20 +17,24 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
 
-use ::log::warn;
+use ::log::{info, warn};
 use anyhow::{anyhow, Context};
+use openssl::x509::X509;
 use tokio::runtime::Handle;
 use tokio::time::Instant;
 
 use g3_types::ext::DurationExt;
 
 pub mod config;
+use config::OpensslBackendConfig;
 
 mod build;
 
 pub mod opts;
 use opts::ProcArgs;
 
+pub mod signal;
+
 mod stat;
 
 mod backend;
@@ -67,6 +71,124 @@ impl BackendResponse {
     }
 }
 
+fn load_self_test_roots(proc_args: &ProcArgs) -> anyhow::Result<Vec<X509>> {
+    let mut extra_roots = Vec::new();
+    for file in &proc_args.self_test_roots {
+        let contents = std::fs::read(file)
+            .map_err(|e| anyhow!("failed to read file {}: {e}", file.display()))?;
+        let certs = X509::stack_from_pem(&contents)
+            .map_err(|e| anyhow!("invalid certificate file {}: {e}", file.display()))?;
+        extra_roots.extend(certs);
+    }
+    Ok(extra_roots)
+}
+
+fn probe_backend(
+    backend_config: &Arc<OpensslBackendConfig>,
+    host: &str,
+    extra_roots: &[X509],
+) -> anyhow::Result<()> {
+    let backend_stats = Arc::new(BackendStats::default());
+    let mut backend = OpensslBackend::new(backend_config, &backend_stats)?;
+    backend
+        .generate(host)
+        .and_then(|data| backend.verify(host, &data, extra_roots))
+}
+
+pub fn self_test(proc_args: &ProcArgs) -> anyhow::Result<()> {
+    let Some(host) = &proc_args.self_test_host else {
+        return Err(anyhow!("no self test host set"));
+    };
+
+    let extra_roots = load_self_test_roots(proc_args)?;
+    let backend_config =
+        config::get_backend_config().ok_or_else(|| anyhow!("no backend config available"))?;
+
+    match probe_backend(&backend_config, host, &extra_roots) {
+        Ok(_) => {
+            println!("PASS");
+            Ok(())
+        }
+        Err(e) => {
+            println!("FAIL: {e:?}");
+            Err(anyhow!("self test failed for host {host}"))
+        }
+    }
+}
+
+/// validate the candidate ca and issue a test cert with it, the live backend is not touched
+pub fn check_ca(proc_args: &ProcArgs) -> anyhow::Result<()> {
+    let Some((cert_file, key_file)) = &proc_args.check_ca else {
+        return Err(anyhow!("no candidate ca set"));
+    };
+    let host = proc_args
+        .self_test_host
+        .as_deref()
+        .unwrap_or("www.example.net");
+
+    let extra_roots = load_self_test_roots(proc_args)?;
+    let live_config = config::get_backend_config();
+    match config::load_candidate_backend_config(cert_file, key_file, live_config.as_deref())
+        .and_then(|candidate| probe_backend(&candidate, host, &extra_roots))
+    {
+        Ok(_) => {
+            println!("PASS");
+            Ok(())
+        }
+        Err(e) => {
+            println!("FAIL: {e:?}");
+            Err(anyhow!(
+                "candidate ca check failed for {}",
+                cert_file.display()
+            ))
+        }
+    }
+}
+
+/// issue an intermediate ca cert with the active ca and print it to stdout
+pub fn issue_ca(proc_args: &ProcArgs) -> anyhow::Result<()> {
+    let Some(name) = &proc_args.issue_ca_name else {
+        return Err(anyhow!("no common name set for the intermediate ca"));
+    };
+
+    let backend_config =
+        config::get_backend_config().ok_or_else(|| anyhow!("no backend config available"))?;
+    let backend_stats = Arc::new(BackendStats::default());
+    let backend = OpensslBackend::new(&backend_config, &backend_stats)?;
+    let data = backend
+        .issue_ca(name)
+        .context(format!("failed to issue intermediate ca {name}"))?;
+    print!("{}{}", data.cert, data.key);
+    Ok(())
+}
+
+/// issue and verify a test cert with each of the configured ca before serving
+pub fn startup_check() -> anyhow::Result<()> {
+    const CHECK_HOST: &str = "www.example.net";
+
+    let backend_config =
+        config::get_backend_config().ok_or_else(|| anyhow!("no backend config available"))?;
+    if !backend_config.startup_check {
+        return Ok(());
+    }
+
+    for (name, ca_config) in backend_config.each_ca() {
+        let subject = config::subject_string(&ca_config.ca_cert);
+        let start = Instant::now();
+        match probe_backend(&ca_config, CHECK_HOST, &[]) {
+            Ok(_) => info!(
+                "startup check passed for {name} ca {subject}, cost {:?}",
+                start.elapsed()
+            ),
+            Err(e) if backend_config.deny_startup_check_failure => {
+                return Err(e.context(format!("startup check failed for {name} ca {subject}")));
+            }
+            Err(e) => warn!("startup check failed for {name} ca {subject}: {e:?}"),
+        }
+    }
+    Ok(())
+}
+
 pub async fn run(proc_args: &ProcArgs) -> anyhow::Result<()> {
     let (req_sender, req_receiver) = flume::bounded::<BackendRequest>(1024);
     let (rsp_sender, rsp_receiver) = flume::bounded::<BackendResponse>(1024);
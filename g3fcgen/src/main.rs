@@ -49,6 +49,18 @@ fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
+    if proc_args.check_ca() {
+        return g3fcgen::check_ca(&proc_args);
+    }
+    if proc_args.issue_ca() {
+        return g3fcgen::issue_ca(&proc_args);
+    }
+    if proc_args.self_test() {
+        return g3fcgen::self_test(&proc_args);
+    }
+
+    g3fcgen::startup_check()?;
+
     // enter daemon mode after config loaded
     g3_daemon::daemonize::check_enter(&proc_args.daemon_config)?;
 
@@ -68,7 +80,8 @@ fn tokio_run(args: &ProcArgs) -> anyhow::Result<()> {
         .start()
         .context("failed to start runtime")?;
     rt.block_on(async {
-        // TODO setup signal handler
+        // TODO setup quit signal handler
+        g3fcgen::signal::setup_and_spawn().context("failed to setup signal handler")?;
 
         let _workers_guard = g3_daemon::runtime::worker::spawn_workers()
             .await
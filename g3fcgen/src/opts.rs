@@ -28,6 +28,11 @@ use g3_daemon::opts::{DaemonArgs, DaemonArgsExt};
 const GLOBAL_ARG_VERSION: &str = "version";
 const GLOBAL_ARG_GROUP_NAME: &str = "group-name";
 const GLOBAL_ARG_CONFIG_FILE: &str = "config-file";
+const GLOBAL_ARG_SELF_TEST: &str = "self-test";
+const GLOBAL_ARG_SELF_TEST_ROOT: &str = "self-test-root";
+const GLOBAL_ARG_CHECK_CA: &str = "check-ca";
+const GLOBAL_ARG_CHECK_CA_KEY: &str = "check-ca-key";
+const GLOBAL_ARG_ISSUE_CA: &str = "issue-ca";
 
 static DAEMON_GROUP: OnceLock<String> = OnceLock::new();
 
@@ -35,6 +40,10 @@ static DAEMON_GROUP: OnceLock<String> = OnceLock::new();
 pub struct ProcArgs {
     pub daemon_config: DaemonArgs,
     udp_addr: Option<SocketAddr>,
+    pub(crate) self_test_host: Option<String>,
+    pub(crate) self_test_roots: Vec<PathBuf>,
+    pub(crate) check_ca: Option<(PathBuf, PathBuf)>,
+    pub(crate) issue_ca_name: Option<String>,
 }
 
 impl Default for ProcArgs {
@@ -42,6 +51,10 @@ impl Default for ProcArgs {
         ProcArgs {
             daemon_config: DaemonArgs::new(crate::build::PKG_NAME),
             udp_addr: None,
+            self_test_host: None,
+            self_test_roots: Vec::new(),
+            check_ca: None,
+            issue_ca_name: None,
         }
     }
 }
@@ -51,6 +64,21 @@ impl ProcArgs {
         self.udp_addr
             .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2999))
     }
+
+    #[inline]
+    pub fn self_test(&self) -> bool {
+        self.self_test_host.is_some()
+    }
+
+    #[inline]
+    pub fn check_ca(&self) -> bool {
+        self.check_ca.is_some()
+    }
+
+    #[inline]
+    pub fn issue_ca(&self) -> bool {
+        self.issue_ca_name.is_some()
+    }
 }
 
 fn build_cli_args() -> Command {
@@ -84,6 +112,55 @@ fn build_cli_args() -> Command {
                 .short('c')
                 .long("config-file"),
         )
+        .arg(
+            Arg::new(GLOBAL_ARG_SELF_TEST)
+                .help("Generate a certificate for the host and verify it against the CA, then exit")
+                .num_args(1)
+                .value_name("HOSTNAME")
+                .long(GLOBAL_ARG_SELF_TEST),
+        )
+        .arg(
+            Arg::new(GLOBAL_ARG_SELF_TEST_ROOT)
+                .help("Extra root certificate file to use in self test")
+                .num_args(1)
+                .value_name("CERT FILE")
+                .value_hint(ValueHint::FilePath)
+                .value_parser(value_parser!(PathBuf))
+                .action(ArgAction::Append)
+                .requires(GLOBAL_ARG_SELF_TEST)
+                .long(GLOBAL_ARG_SELF_TEST_ROOT),
+        )
+        .arg(
+            Arg::new(GLOBAL_ARG_CHECK_CA)
+                .help("Validate the candidate CA certificate and do a self test with it, then exit")
+                .num_args(1)
+                .value_name("CERT FILE")
+                .value_hint(ValueHint::FilePath)
+                .value_parser(value_parser!(PathBuf))
+                .requires(GLOBAL_ARG_CHECK_CA_KEY)
+                .long(GLOBAL_ARG_CHECK_CA),
+        )
+        .arg(
+            Arg::new(GLOBAL_ARG_CHECK_CA_KEY)
+                .help("Private key file of the candidate CA certificate")
+                .num_args(1)
+                .value_name("KEY FILE")
+                .value_hint(ValueHint::FilePath)
+                .value_parser(value_parser!(PathBuf))
+                .requires(GLOBAL_ARG_CHECK_CA)
+                .long(GLOBAL_ARG_CHECK_CA_KEY),
+        )
+        .arg(
+            Arg::new(GLOBAL_ARG_ISSUE_CA)
+                .help(
+                    "Issue an intermediate CA certificate with the active CA and print it \
+                    with the private key in PEM format, then exit",
+                )
+                .num_args(1)
+                .value_name("COMMON NAME")
+                .conflicts_with_all([GLOBAL_ARG_SELF_TEST, GLOBAL_ARG_CHECK_CA])
+                .long(GLOBAL_ARG_ISSUE_CA),
+        )
 }
 
 pub fn parse_clap() -> anyhow::Result<Option<ProcArgs>> {
@@ -108,6 +185,22 @@ pub fn parse_clap() -> anyhow::Result<Option<ProcArgs>> {
         return Err(anyhow!("no config file given"));
     }
 
+    if let Some(host) = args.get_one::<String>(GLOBAL_ARG_SELF_TEST) {
+        proc_args.self_test_host = Some(host.to_string());
+        if let Some(roots) = args.get_many::<PathBuf>(GLOBAL_ARG_SELF_TEST_ROOT) {
+            proc_args.self_test_roots = roots.cloned().collect();
+        }
+    }
+    if let (Some(cert), Some(key)) = (
+        args.get_one::<PathBuf>(GLOBAL_ARG_CHECK_CA),
+        args.get_one::<PathBuf>(GLOBAL_ARG_CHECK_CA_KEY),
+    ) {
+        proc_args.check_ca = Some((cert.clone(), key.clone()));
+    }
+    if let Some(name) = args.get_one::<String>(GLOBAL_ARG_ISSUE_CA) {
+        proc_args.issue_ca_name = Some(name.to_string());
+    }
+
     if let Some(group_name) = args.get_one::<String>(GLOBAL_ARG_GROUP_NAME) {
         DAEMON_GROUP
             .set(group_name.to_string())
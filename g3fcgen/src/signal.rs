@@ -0,0 +1,44 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use log::{info, warn};
+use tokio::signal::unix::SignalKind;
+
+use g3_signal::{ActionSignal, SigResult};
+
+fn call_rotate_ca(_: u32) -> SigResult {
+    info!("got rotate ca signal");
+    let Some(config) = crate::config::get_backend_config() else {
+        warn!("no backend config available");
+        return SigResult::Continue;
+    };
+    match config.rotate_ca() {
+        Ok(cert) => info!(
+            "now using ca with subject {}",
+            crate::config::subject_string(cert)
+        ),
+        Err(e) => warn!("failed to rotate ca: {e}"),
+    }
+    SigResult::Continue
+}
+
+pub fn setup_and_spawn() -> anyhow::Result<()> {
+    tokio::spawn(ActionSignal::new(
+        SignalKind::user_defined1(),
+        &call_rotate_ca,
+    )?);
+    Ok(())
+}
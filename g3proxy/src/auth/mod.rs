@@ -15,6 +15,7 @@
  */
 
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use ahash::AHashMap;
@@ -24,6 +25,7 @@ use chrono::Utc;
 use futures_util::future::AbortHandle;
 use log::{info, warn};
 
+use g3_types::auth::UserAuthError;
 use g3_types::metrics::MetricsName;
 
 use crate::config::auth::UserGroupConfig;
@@ -74,6 +76,7 @@ pub(crate) struct UserGroup {
     /// the dynamic job is for both dynamic fetch and expire check
     dynamic_job_handler: Option<AbortHandle>,
     anonymous_user: Option<Arc<User>>,
+    source_failure: Arc<AtomicBool>,
 }
 
 impl Drop for UserGroup {
@@ -92,6 +95,7 @@ impl UserGroup {
             dynamic_users: Arc::new(ArcSwap::from_pointee(AHashMap::new())),
             dynamic_job_handler: None,
             anonymous_user: None,
+            source_failure: Arc::new(AtomicBool::new(false)),
         }
     }
 
@@ -140,6 +144,7 @@ impl UserGroup {
             &group.config,
             &group.static_users,
             &group.dynamic_users,
+            &group.source_failure,
         ));
 
         Ok(Arc::new(group))
@@ -185,6 +190,7 @@ impl UserGroup {
             &group.config,
             &group.static_users,
             &group.dynamic_users,
+            &group.source_failure,
         ));
 
         Ok(Arc::new(group))
@@ -243,6 +249,15 @@ impl UserGroup {
         }
     }
 
+    #[inline]
+    pub(crate) fn static_user_count(&self) -> usize {
+        self.static_users.len()
+    }
+
+    pub(crate) fn dynamic_user_count(&self) -> usize {
+        self.dynamic_users.load().len()
+    }
+
     pub(crate) fn all_static_users(&self) -> Vec<&str> {
         self.static_users.keys().map(|k| k.as_str()).collect()
     }
@@ -252,6 +267,117 @@ impl UserGroup {
         dynamic_users.keys().map(|k| k.to_string()).collect()
     }
 
+    /// simulate an auth check for the user, the returned message contains the policy applied
+    pub(crate) fn check_user_auth(&self, username: &str, password: &str) -> anyhow::Result<String> {
+        let Some((user, user_type)) = self.get_user(username) else {
+            return Err(anyhow!("{}", UserAuthError::NoSuchUser));
+        };
+        match user.verify_password(password) {
+            Ok(_) => Ok(format!(
+                "authenticated as {} user, policies: {}",
+                user_type.as_str(),
+                user.auth_policies().join(", ")
+            )),
+            Err(UserAuthError::BlockedUser(duration)) if user.is_config_blocked() => Err(anyhow!(
+                "{} user has been blocked by config, with delay {duration:?}",
+                user_type.as_str()
+            )),
+            Err(UserAuthError::BlockedUser(_)) => Err(anyhow!(
+                "{} user has been blocked by ctl",
+                user_type.as_str()
+            )),
+            Err(e) => Err(anyhow!("{} user: {e}", user_type.as_str())),
+        }
+    }
+
+    /// block or unblock the static or dynamic user at runtime,
+    /// the returned message contains the resulting state
+    pub(crate) fn set_user_blocked(
+        &self,
+        username: &str,
+        block: bool,
+        terminate: bool,
+    ) -> anyhow::Result<String> {
+        let (user, user_type) = if let Some(user) = self.static_users.get(username) {
+            (Arc::clone(user), UserType::Static)
+        } else if let Some(user) = self.dynamic_users.load().get(username) {
+            (Arc::clone(user), UserType::Dynamic)
+        } else {
+            return Err(anyhow!("{}", UserAuthError::NoSuchUser));
+        };
+        user.set_ctl_block(block, terminate);
+        let state = if block {
+            if terminate {
+                "blocked, and existing sessions will be terminated"
+            } else {
+                "blocked for new auths"
+            }
+        } else if user.is_effectively_blocked() {
+            "unblocked, but still blocked by config"
+        } else {
+            "unblocked"
+        };
+        Ok(format!("{} user {state}", user_type.as_str()))
+    }
+
+    /// simulate the failure of the dynamic user source, for fault injection tests only.
+    /// The returned message contains how the group behaves during the failure
+    pub(crate) fn simulate_source_failure(&self, enable: bool) -> anyhow::Result<String> {
+        if !self.config.allow_fault_injection {
+            return Err(anyhow!(
+                "fault injection is not allowed for user-group {}",
+                self.config.name()
+            ));
+        }
+        if self.config.dynamic_source.is_none() {
+            return Err(anyhow!(
+                "no dynamic source set for user-group {}",
+                self.config.name()
+            ));
+        }
+
+        if enable {
+            self.source_failure.store(true, Ordering::Relaxed);
+            warn!(
+                "source failure simulation enabled for user-group {}",
+                self.config.name()
+            );
+            Ok(format!(
+                "dynamic source fetch will fail, the existing {} dynamic users will be kept \
+                with only expire check, until restored or reloaded",
+                self.dynamic_user_count()
+            ))
+        } else {
+            self.source_failure.store(false, Ordering::Relaxed);
+            info!(
+                "source failure simulation disabled for user-group {}",
+                self.config.name()
+            );
+            Ok("dynamic source fetch restored, will take effect at next refresh".to_string())
+        }
+    }
+
+    /// describe the effective config of this group, no user secrets will be included
+    pub(crate) fn describe(&self) -> serde_json::Value {
+        let mut static_users = self.all_static_users();
+        static_users.sort_unstable();
+        let dynamic_cache = if self.config.dynamic_cache.as_os_str().is_empty() {
+            None
+        } else {
+            Some(self.config.dynamic_cache.display().to_string())
+        };
+        serde_json::json!({
+            "name": self.config.name().as_str(),
+            "static_user_count": static_users.len(),
+            "static_users": static_users,
+            "dynamic_source": self.config.dynamic_source.as_ref().map(|s| s.describe()),
+            "dynamic_user_count": self.dynamic_user_count(),
+            "dynamic_cache": dynamic_cache,
+            "refresh_interval": format!("{:?}", self.config.refresh_interval),
+            "allow_anonymous": self.allow_anonymous(),
+        })
+    }
+
     pub(crate) async fn publish_dynamic_users(&self, contents: &str) -> anyhow::Result<()> {
         let doc = serde_json::Value::from_str(contents)
             .map_err(|e| anyhow!("the published contents is not valid json: {e}",))?;
@@ -273,4 +399,74 @@ impl UserGroup {
         source::publish_dynamic_users(self.config.as_ref(), user_config, &self.dynamic_users);
         Ok(())
     }
+
+    /// merge the published users into the existing dynamic users,
+    /// the returned value is the count of added and updated users
+    pub(crate) async fn merge_dynamic_users(
+        &self,
+        contents: &str,
+    ) -> anyhow::Result<(usize, usize)> {
+        let doc = serde_json::Value::from_str(contents)
+            .map_err(|e| anyhow!("the published contents is not valid json: {e}",))?;
+        let user_config = crate::config::auth::source::cache::parse_json(&doc)?;
+
+        if !self.config.dynamic_cache.as_os_str().is_empty() {
+            self.merge_dynamic_cache(doc).await;
+        }
+
+        Ok(source::merge_dynamic_users(
+            self.config.as_ref(),
+            user_config,
+            &self.dynamic_users,
+        ))
+    }
+
+    async fn merge_dynamic_cache(&self, doc: serde_json::Value) {
+        use serde_json::Value;
+
+        let cache_file = &self.config.dynamic_cache;
+        let mut records = match tokio::fs::read_to_string(cache_file).await {
+            Ok(s) => match Value::from_str(&s) {
+                Ok(Value::Array(records)) => records,
+                _ => {
+                    warn!(
+                        "invalid dynamic user cache file {}, it will not be updated",
+                        cache_file.display()
+                    );
+                    return;
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                warn!(
+                    "failed to read dynamic user cache file {} ({e:?}), it will not be updated",
+                    cache_file.display()
+                );
+                return;
+            }
+        };
+        let Value::Array(new_records) = doc else {
+            return;
+        };
+        for record in new_records {
+            let name = record.get("name");
+            if let Some(r) = records.iter_mut().find(|r| r.get("name") == name) {
+                *r = record;
+            } else {
+                records.push(record);
+            }
+        }
+
+        let contents = Value::Array(records).to_string();
+        // we should avoid corrupt write at process exit
+        if let Some(Err(e)) =
+            crate::control::run_protected_io(tokio::fs::write(cache_file, contents)).await
+        {
+            warn!(
+                "failed to cache dynamic users to file {} ({e:?}), \
+                this may lead to auth error during restart",
+                cache_file.display()
+            );
+        }
+    }
 }
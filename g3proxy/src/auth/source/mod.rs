@@ -14,6 +14,7 @@
  * limitations under the License.
  */
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use ahash::AHashMap;
@@ -67,17 +68,25 @@ pub(super) fn new_job(
     group_config: &Arc<UserGroupConfig>,
     static_users: &Arc<AHashMap<String, Arc<User>>>,
     dynamic_users_container: &Arc<ArcSwap<AHashMap<String, Arc<User>>>>,
+    source_failure: &Arc<AtomicBool>,
 ) -> AbortHandle {
     let group_config = Arc::clone(group_config);
     let static_users = Arc::clone(static_users);
     let dynamic_users_container = Arc::clone(dynamic_users_container);
+    let source_failure = Arc::clone(source_failure);
 
     let f = async move {
         let mut interval = tokio::time::interval(group_config.refresh_interval);
         interval.tick().await; // will tick immediately
         loop {
             let new_dynamic_config: Option<Vec<UserConfig>> =
-                if let Some(source) = &group_config.dynamic_source {
+                if source_failure.load(Ordering::Relaxed) {
+                    warn!(
+                        "skip fetching dynamic user for group {} as source failure is simulated",
+                        group_config.name(),
+                    );
+                    None
+                } else if let Some(source) = &group_config.dynamic_source {
                     let r = match source {
                         UserDynamicSource::File(config) => config.fetch_records().await,
                         #[cfg(feature = "lua")]
@@ -144,6 +153,38 @@ pub(super) fn publish_dynamic_users(
     );
 }
 
+/// merge the users into the existing dynamic users, the others will be kept.
+/// Return the count of added and updated users
+pub(super) fn merge_dynamic_users(
+    group_config: &UserGroupConfig,
+    dynamic_config: Vec<UserConfig>,
+    dynamic_users_container: &Arc<ArcSwap<AHashMap<String, Arc<User>>>>,
+) -> (usize, usize) {
+    let datetime_now = Utc::now();
+    let dynamic_config: Vec<Arc<UserConfig>> = dynamic_config.into_iter().map(Arc::new).collect();
+    let mut added = 0;
+    let mut updated = 0;
+    // the closure will be called again if the users are changed by others in the meantime
+    dynamic_users_container.rcu(|old_dynamic_users| {
+        let mut new_dynamic_users = AHashMap::clone(old_dynamic_users);
+        added = 0;
+        updated = 0;
+        for user_config in &dynamic_config {
+            let username = user_config.name();
+            let user = if let Some(old_user) = old_dynamic_users.get(username) {
+                updated += 1;
+                old_user.new_for_reload(user_config, &datetime_now)
+            } else {
+                added += 1;
+                User::new(group_config.name(), user_config, &datetime_now)
+            };
+            new_dynamic_users.insert(username.to_string(), Arc::new(user));
+        }
+        new_dynamic_users
+    });
+    (added, updated)
+}
+
 fn update_dynamic_users(
     group_config: &UserGroupConfig,
     datetime_now: &DateTime<Utc>,
@@ -184,3 +225,62 @@ fn check_static_users(
         user.check_expired(datetime_now);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use g3_types::metrics::MetricsName;
+
+    fn user_config(name: &str) -> UserConfig {
+        let value = serde_json::json!({"name": name, "token": null});
+        UserConfig::parse_json(value.as_object().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn merge_users() {
+        let group_config = UserGroupConfig::empty(&MetricsName::default());
+        let container = Arc::new(ArcSwap::new(Arc::new(AHashMap::new())));
+
+        let r = merge_dynamic_users(
+            &group_config,
+            vec![user_config("a"), user_config("b")],
+            &container,
+        );
+        assert_eq!(r, (2, 0));
+        assert_eq!(container.load().len(), 2);
+
+        let r = merge_dynamic_users(
+            &group_config,
+            vec![user_config("b"), user_config("c")],
+            &container,
+        );
+        assert_eq!(r, (1, 1));
+        let users = container.load();
+        assert_eq!(users.len(), 3);
+        assert!(users.contains_key("a"));
+        assert!(users.contains_key("c"));
+    }
+
+    #[test]
+    fn merge_users_concurrently() {
+        let group_config = Arc::new(UserGroupConfig::empty(&MetricsName::default()));
+        let container = Arc::new(ArcSwap::new(Arc::new(AHashMap::new())));
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let group_config = Arc::clone(&group_config);
+                let container = Arc::clone(&container);
+                std::thread::spawn(move || {
+                    let users = (0..50)
+                        .map(|j| user_config(&format!("user-{i}-{j}")))
+                        .collect();
+                    merge_dynamic_users(&group_config, users, &container)
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), (50, 0));
+        }
+        assert_eq!(container.load().len(), 400);
+    }
+}
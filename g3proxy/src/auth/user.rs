@@ -15,7 +15,7 @@
  */
 
 use std::net::SocketAddr;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -39,12 +39,17 @@ use super::{
 };
 use crate::config::auth::{UserAuditConfig, UserConfig};
 
+const CTL_BLOCK_NONE: u8 = 0;
+const CTL_BLOCK_AUTH: u8 = 1;
+const CTL_BLOCK_TERMINATE: u8 = 2;
+
 pub(crate) struct User {
     config: Arc<UserConfig>,
     group: MetricsName,
     started: Instant,
     is_expired: AtomicBool,
     is_blocked: Arc<AtomicBool>,
+    ctl_block: Arc<AtomicU8>,
     request_rate_limit: Option<Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>,
     tcp_conn_rate_limit: Option<Arc<RateLimiter<NotKeyed, InMemoryState, DefaultClock>>>,
     ingress_net_filter: Option<Arc<AclNetworkRule>>,
@@ -118,6 +123,7 @@ impl User {
             started: Instant::now(),
             is_expired,
             is_blocked,
+            ctl_block: Arc::new(AtomicU8::new(CTL_BLOCK_NONE)),
             request_rate_limit,
             tcp_conn_rate_limit,
             ingress_net_filter: None,
@@ -222,6 +228,7 @@ impl User {
             started: self.started,
             is_expired,
             is_blocked,
+            ctl_block: Arc::clone(&self.ctl_block),
             request_rate_limit,
             tcp_conn_rate_limit,
             ingress_net_filter: None,
@@ -256,10 +263,34 @@ impl User {
     /// for user blocked check in idle checking
     pub(crate) fn is_blocked(&self) -> bool {
         self.is_blocked.load(Ordering::Relaxed)
+            || self.ctl_block.load(Ordering::Relaxed) == CTL_BLOCK_TERMINATE
     }
 
+    /// check if the user is blocked by the config, ignoring the runtime block state
     #[inline]
-    fn is_expired(&self) -> bool {
+    pub(super) fn is_config_blocked(&self) -> bool {
+        self.config.block_and_delay.is_some()
+    }
+
+    /// check if new auths of this user will be rejected, either by the config or at runtime.
+    /// this is what the ctl should report as the block state of the user
+    pub(crate) fn is_effectively_blocked(&self) -> bool {
+        self.is_config_blocked() || self.ctl_block.load(Ordering::Relaxed) != CTL_BLOCK_NONE
+    }
+
+    /// block the user for new auths at runtime, and also existing sessions if `terminate` is set.
+    /// the block state will be kept across reloads, until unblocked
+    pub(super) fn set_ctl_block(&self, block: bool, terminate: bool) {
+        let state = match (block, terminate) {
+            (false, _) => CTL_BLOCK_NONE,
+            (true, false) => CTL_BLOCK_AUTH,
+            (true, true) => CTL_BLOCK_TERMINATE,
+        };
+        self.ctl_block.store(state, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn is_expired(&self) -> bool {
         self.is_expired.load(Ordering::Relaxed)
     }
 
@@ -279,21 +310,70 @@ impl User {
         password: &str,
         forbid_stats: &Arc<UserForbiddenStats>,
     ) -> Result<(), UserAuthError> {
+        self.verify_password(password).map_err(|e| {
+            match e {
+                UserAuthError::TokenNotMatch => forbid_stats.add_auth_failed(),
+                UserAuthError::ExpiredUser => forbid_stats.add_user_expired(),
+                UserAuthError::BlockedUser(_) => forbid_stats.add_user_blocked(),
+                _ => {}
+            }
+            e
+        })
+    }
+
+    /// check the password without updating any stats
+    pub(super) fn verify_password(&self, password: &str) -> Result<(), UserAuthError> {
         if !self.config.check_password(password) {
-            forbid_stats.add_auth_failed();
             return Err(UserAuthError::TokenNotMatch);
         }
         if self.is_expired() {
-            forbid_stats.add_user_expired();
             return Err(UserAuthError::ExpiredUser);
         }
         if let Some(duration) = self.config.block_and_delay {
-            forbid_stats.add_user_blocked();
             return Err(UserAuthError::BlockedUser(duration));
         }
+        if self.is_effectively_blocked() {
+            return Err(UserAuthError::BlockedUser(Duration::ZERO));
+        }
         Ok(())
     }
 
+    /// the auth related policies of this user, used to describe the simulated auth result
+    pub(super) fn auth_policies(&self) -> Vec<String> {
+        let mut policies = vec![format!("{} password", self.config.password_type())];
+        if let Some(dt) = self.config.expire_datetime() {
+            policies.push(format!("expire at {}", dt.to_rfc3339()));
+        }
+        if self.config.request_rate_limit.is_some() {
+            policies.push("request rate limit".to_string());
+        }
+        if self.config.tcp_conn_rate_limit.is_some() {
+            policies.push("tcp conn rate limit".to_string());
+        }
+        if self.config.ingress_net_filter.is_some() {
+            policies.push("ingress network filter".to_string());
+        }
+        if self.config.proxy_request_filter.is_some() {
+            policies.push("proxy request filter".to_string());
+        }
+        if self.config.dst_host_filter.is_some() {
+            policies.push("dst host filter".to_string());
+        }
+        if self.config.dst_port_filter.is_some() {
+            policies.push("dst port filter".to_string());
+        }
+        if self.config.http_user_agent_filter.is_some() {
+            policies.push("http user agent filter".to_string());
+        }
+        if !self.config.explicit_sites.is_empty() {
+            policies.push(format!(
+                "{} explicit sites",
+                self.config.explicit_sites.len()
+            ));
+        }
+        policies
+    }
+
     fn fetch_forbidden_stats(
         &self,
         user_type: UserType,
@@ -38,6 +38,7 @@ pub(crate) struct UserGroupConfig {
     pub(crate) dynamic_cache: PathBuf,
     pub(crate) refresh_interval: Duration,
     pub(crate) anonymous_user: Option<Arc<UserConfig>>,
+    pub(crate) allow_fault_injection: bool,
 }
 
 impl UserGroupConfig {
@@ -58,6 +59,7 @@ impl UserGroupConfig {
             dynamic_cache: PathBuf::default(),
             refresh_interval: DEFAULT_REFRESH_INTERVAL,
             anonymous_user: None,
+            allow_fault_injection: false,
         }
     }
 
@@ -70,6 +72,7 @@ impl UserGroupConfig {
             dynamic_cache: PathBuf::default(),
             refresh_interval: DEFAULT_REFRESH_INTERVAL,
             anonymous_user: None,
+            allow_fault_injection: false,
         }
     }
 
@@ -144,6 +147,10 @@ impl UserGroupConfig {
                     Err(anyhow!("invalid hash value for key {k}"))
                 }
             }
+            "allow_fault_injection" => {
+                self.allow_fault_injection = g3_yaml::value::as_bool(v)?;
+                Ok(())
+            }
             _ => Err(anyhow!("invalid key {k}")),
         }
     }
@@ -42,6 +42,33 @@ pub(crate) enum UserDynamicSource {
 }
 
 impl UserDynamicSource {
+    pub(crate) fn describe(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        match self {
+            UserDynamicSource::File(s) => json!({
+                "type": "file",
+                "path": s.path.display().to_string(),
+                "format": format!("{:?}", s.format).to_lowercase(),
+            }),
+            #[cfg(feature = "lua")]
+            UserDynamicSource::Lua(s) => json!({
+                "type": "lua",
+                "fetch_script": s.fetch_script.display().to_string(),
+                "fetch_timeout": format!("{:?}", s.fetch_timeout),
+                "report_script": s.report_script.as_ref().map(|p| p.display().to_string()),
+                "report_timeout": format!("{:?}", s.report_timeout),
+            }),
+            #[cfg(feature = "python")]
+            UserDynamicSource::Python(s) => json!({
+                "type": "python",
+                "script_file": s.script_file.display().to_string(),
+                "fetch_timeout": format!("{:?}", s.fetch_timeout),
+                "report_timeout": format!("{:?}", s.report_timeout),
+            }),
+        }
+    }
+
     pub(super) fn parse_config(v: &Yaml, lookup_dir: &Path) -> anyhow::Result<Self> {
         match v {
             Yaml::Hash(map) => {
@@ -123,6 +123,20 @@ impl UserConfig {
         }
     }
 
+    #[inline]
+    pub(crate) fn expire_datetime(&self) -> Option<&DateTime<Utc>> {
+        self.expire_datetime.as_ref()
+    }
+
+    pub(crate) fn password_type(&self) -> &'static str {
+        match &self.password_token {
+            PasswordToken::Forbidden => "forbidden",
+            PasswordToken::SkipVerify => "skip-verify",
+            PasswordToken::FastHash(_) => "fast-hash",
+            PasswordToken::XCrypt(_) => "xcrypt",
+        }
+    }
+
     pub(crate) fn check_password(&self, password: &str) -> bool {
         match &self.password_token {
             PasswordToken::Forbidden => false,
@@ -21,7 +21,7 @@ use capnp_rpc::pry;
 
 use g3_types::metrics::MetricsName;
 
-use g3proxy_proto::user_group_capnp::user_group_control;
+use g3proxy_proto::user_group_capnp::{user_group_control, UserState};
 
 use super::set_operation_result;
 use crate::auth::UserGroup;
@@ -41,10 +41,27 @@ impl UserGroupControlImpl {
 impl user_group_control::Server for UserGroupControlImpl {
     fn list_static_user(
         &mut self,
-        _params: user_group_control::ListStaticUserParams,
+        params: user_group_control::ListStaticUserParams,
         mut results: user_group_control::ListStaticUserResults,
     ) -> Promise<(), capnp::Error> {
-        let v = self.user_group.all_static_users();
+        let params = pry!(params.get());
+        let prefix = pry!(pry!(params.get_prefix()).to_str());
+        let state = pry!(params.get_state());
+        let mut v = Vec::new();
+        self.user_group.foreach_static_user(|name, user| {
+            if !name.starts_with(prefix) {
+                return;
+            }
+            let matched = match state {
+                UserState::Any => true,
+                UserState::Active => !user.is_effectively_blocked() && !user.is_expired(),
+                UserState::Blocked => user.is_effectively_blocked(),
+                UserState::Expired => user.is_expired(),
+            };
+            if matched {
+                v.push(name.to_string());
+            }
+        });
         let mut builder = results.get().init_result(v.len() as u32);
         for (i, name) in v.into_iter().enumerate() {
             builder.set(i as u32, name);
@@ -65,6 +82,17 @@ impl user_group_control::Server for UserGroupControlImpl {
         Promise::ok(())
     }
 
+    fn count_user(
+        &mut self,
+        _params: user_group_control::CountUserParams,
+        mut results: user_group_control::CountUserResults,
+    ) -> Promise<(), capnp::Error> {
+        let mut builder = results.get();
+        builder.set_static_count(self.user_group.static_user_count() as u64);
+        builder.set_dynamic_count(self.user_group.dynamic_user_count() as u64);
+        Promise::ok(())
+    }
+
     fn publish_dynamic_user(
         &mut self,
         params: user_group_control::PublishDynamicUserParams,
@@ -78,4 +106,94 @@ impl user_group_control::Server for UserGroupControlImpl {
             Ok(())
         })
     }
+
+    fn merge_dynamic_user(
+        &mut self,
+        params: user_group_control::MergeDynamicUserParams,
+        mut results: user_group_control::MergeDynamicUserResults,
+    ) -> Promise<(), capnp::Error> {
+        let user_group = self.user_group.clone();
+        let contents = pry!(pry!(pry!(params.get()).get_contents()).to_string());
+        Promise::from_future(async move {
+            let mut builder = results.get().init_result();
+            match user_group.merge_dynamic_users(&contents).await {
+                Ok((added, updated)) => {
+                    builder.set_ok(format!("added {added}, updated {updated}").as_str())
+                }
+                Err(e) => set_operation_result(builder, Err(e)),
+            }
+            Ok(())
+        })
+    }
+
+    fn check_user_auth(
+        &mut self,
+        params: user_group_control::CheckUserAuthParams,
+        mut results: user_group_control::CheckUserAuthResults,
+    ) -> Promise<(), capnp::Error> {
+        let params = pry!(params.get());
+        let user = pry!(pry!(params.get_user()).to_str());
+        let token = pry!(pry!(params.get_token()).to_str());
+        let mut builder = results.get().init_result();
+        match self.user_group.check_user_auth(user, token) {
+            Ok(msg) => builder.set_ok(msg.as_str()),
+            Err(e) => set_operation_result(builder, Err(e)),
+        }
+        Promise::ok(())
+    }
+
+    fn block_user(
+        &mut self,
+        params: user_group_control::BlockUserParams,
+        mut results: user_group_control::BlockUserResults,
+    ) -> Promise<(), capnp::Error> {
+        let params = pry!(params.get());
+        let user = pry!(pry!(params.get_user()).to_str());
+        let terminate = params.get_terminate();
+        let r = self.user_group.set_user_blocked(user, true, terminate);
+        set_operation_result(results.get().init_result(), r);
+        Promise::ok(())
+    }
+
+    fn unblock_user(
+        &mut self,
+        params: user_group_control::UnblockUserParams,
+        mut results: user_group_control::UnblockUserResults,
+    ) -> Promise<(), capnp::Error> {
+        let user = pry!(pry!(pry!(params.get()).get_user()).to_str());
+        let r = self.user_group.set_user_blocked(user, false, false);
+        set_operation_result(results.get().init_result(), r);
+        Promise::ok(())
+    }
+
+    fn simulate_source_failure(
+        &mut self,
+        params: user_group_control::SimulateSourceFailureParams,
+        mut results: user_group_control::SimulateSourceFailureResults,
+    ) -> Promise<(), capnp::Error> {
+        let enable = pry!(params.get()).get_enable();
+        let mut builder = results.get().init_result();
+        match self.user_group.simulate_source_failure(enable) {
+            Ok(msg) => builder.set_ok(msg.as_str()),
+            Err(e) => set_operation_result(builder, Err(e)),
+        }
+        Promise::ok(())
+    }
+
+    fn describe(
+        &mut self,
+        _params: user_group_control::DescribeParams,
+        mut results: user_group_control::DescribeResults,
+    ) -> Promise<(), capnp::Error> {
+        let v = self.user_group.describe();
+        match serde_json::to_string_pretty(&v) {
+            Ok(s) => results.get().set_result(s.as_str()),
+            Err(e) => {
+                return Promise::err(capnp::Error::failed(format!(
+                    "failed to encode the description: {e}"
+                )))
+            }
+        }
+        Promise::ok(())
+    }
 }
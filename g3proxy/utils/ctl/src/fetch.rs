@@ -0,0 +1,276 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::time::Duration;
+
+use anyhow::anyhow;
+use http::{Method, StatusCode};
+use openssl::ssl::{Ssl, SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::X509;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use url::{Host, Position, Url};
+
+use g3_http::client::HttpForwardRemoteResponse;
+use g3_http::HttpBodyReader;
+
+const MAX_HEADER_SIZE: usize = 4096;
+pub(crate) const MAX_BODY_SIZE: u64 = 64 * 1024 * 1024; // 64MB
+
+pub(crate) struct HttpFetchConfig {
+    pub(crate) bearer_token: Option<String>,
+    pub(crate) timeout: Duration,
+    pub(crate) no_verify: bool,
+    pub(crate) ca_certs: Vec<X509>,
+    pub(crate) max_body_size: u64,
+}
+
+impl HttpFetchConfig {
+    /// fetch the body of the url with a GET request, only 200 response is accepted
+    pub(crate) async fn fetch(&self, url: &Url) -> anyhow::Result<String> {
+        match tokio::time::timeout(self.timeout, self.do_fetch(url)).await {
+            Ok(r) => r,
+            Err(_) => Err(anyhow!("timed out to fetch {url}")),
+        }
+    }
+
+    async fn do_fetch(&self, url: &Url) -> anyhow::Result<String> {
+        let host = match url.host() {
+            Some(Host::Domain(domain)) => domain.to_string(),
+            Some(Host::Ipv4(ip)) => ip.to_string(),
+            Some(Host::Ipv6(ip)) => ip.to_string(),
+            None => return Err(anyhow!("no host found in url {url}")),
+        };
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| anyhow!("no port found in url {url}"))?;
+
+        let stream = TcpStream::connect((host.as_str(), port))
+            .await
+            .map_err(|e| anyhow!("failed to connect to {host}:{port}: {e:?}"))?;
+        match url.scheme() {
+            "http" => self.http_get(stream, url).await,
+            "https" => {
+                let ssl = self.build_ssl(&host)?;
+                let connector = g3_openssl::SslConnector::new(ssl, stream)
+                    .map_err(|e| anyhow!("tls connector create failed: {e}"))?;
+                let stream = connector
+                    .connect()
+                    .await
+                    .map_err(|e| anyhow!("tls handshake with {host} failed: {e}"))?;
+                self.http_get(stream, url).await
+            }
+            s => Err(anyhow!("unsupported url scheme {s}")),
+        }
+    }
+
+    fn build_ssl(&self, host: &str) -> anyhow::Result<Ssl> {
+        let mut builder = SslConnector::builder(SslMethod::tls_client())
+            .map_err(|e| anyhow!("failed to create tls builder: {e}"))?;
+        if self.no_verify {
+            builder.set_verify(SslVerifyMode::NONE);
+        }
+        for cert in &self.ca_certs {
+            builder
+                .cert_store_mut()
+                .add_cert(cert.clone())
+                .map_err(|e| anyhow!("failed to add ca certificate: {e}"))?;
+        }
+        let mut config = builder
+            .build()
+            .configure()
+            .map_err(|e| anyhow!("failed to configure tls context: {e}"))?;
+        if self.no_verify {
+            config.set_verify_hostname(false);
+        }
+        config
+            .into_ssl(host)
+            .map_err(|e| anyhow!("failed to build tls ssl: {e}"))
+    }
+
+    async fn http_get<S>(&self, stream: S, url: &Url) -> anyhow::Result<String>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut stream = BufReader::new(stream);
+
+        let host = url.host_str().unwrap_or_default();
+        let host = match url.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        };
+        let mut req = format!(
+            "GET {} HTTP/1.1\r\n\
+             Host: {host}\r\n\
+             Accept: application/json\r\n\
+             Connection: close\r\n",
+            &url[Position::BeforePath..Position::AfterQuery]
+        );
+        if let Some(token) = &self.bearer_token {
+            req.push_str(&format!("Authorization: Bearer {token}\r\n"));
+        }
+        req.push_str("\r\n");
+        stream
+            .write_all(req.as_bytes())
+            .await
+            .map_err(|e| anyhow!("failed to write request: {e:?}"))?;
+        stream
+            .flush()
+            .await
+            .map_err(|e| anyhow!("failed to write request: {e:?}"))?;
+
+        let rsp =
+            HttpForwardRemoteResponse::parse(&mut stream, &Method::GET, false, MAX_HEADER_SIZE)
+                .await
+                .map_err(|e| anyhow!("failed to recv response: {e}"))?;
+        if rsp.code != StatusCode::OK {
+            return Err(anyhow!("unexpected response: {} {}", rsp.code, rsp.reason));
+        }
+        let Some(body_type) = rsp.body_type(&Method::GET) else {
+            return Err(anyhow!("no body found in response"));
+        };
+
+        let mut body_reader = HttpBodyReader::new(&mut stream, body_type, 1024);
+        let mut body = Vec::new();
+        (&mut body_reader)
+            .take(self.max_body_size + 1)
+            .read_to_end(&mut body)
+            .await
+            .map_err(|e| anyhow!("failed to read response body: {e:?}"))?;
+        if body.len() as u64 > self.max_body_size {
+            return Err(anyhow!(
+                "response body exceeds {} bytes",
+                self.max_body_size
+            ));
+        }
+        String::from_utf8(body).map_err(|e| anyhow!("response body is not valid utf-8: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncBufReadExt;
+    use tokio::net::TcpListener;
+
+    fn fetch_config(max_body_size: u64) -> HttpFetchConfig {
+        HttpFetchConfig {
+            bearer_token: Some("secret".to_string()),
+            timeout: Duration::from_secs(5),
+            no_verify: false,
+            ca_certs: Vec::new(),
+            max_body_size,
+        }
+    }
+
+    /// read the request header and send back the response, return the request header
+    async fn serve<S>(stream: S, response: &'static str) -> String
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut stream = BufReader::new(stream);
+        let mut header = String::new();
+        loop {
+            let mut line = String::new();
+            let len = stream.read_line(&mut line).await.unwrap();
+            if len == 0 || line == "\r\n" {
+                break;
+            }
+            header.push_str(&line);
+        }
+        stream.write_all(response.as_bytes()).await.unwrap();
+        stream.shutdown().await.unwrap();
+        header
+    }
+
+    async fn fetch_local(
+        config: &HttpFetchConfig,
+        response: &'static str,
+    ) -> (anyhow::Result<String>, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(stream, response).await
+        });
+        let url = Url::parse(&format!("http://{addr}/users?group=a")).unwrap();
+        let r = config.fetch(&url).await;
+        (r, server.await.unwrap())
+    }
+
+    async fn fetch_duplex(url: &str) -> String {
+        let (client, server) = tokio::io::duplex(4096);
+        let server = tokio::spawn(serve(
+            server,
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n[]",
+        ));
+        let url = Url::parse(url).unwrap();
+        let body = fetch_config(MAX_BODY_SIZE)
+            .http_get(client, &url)
+            .await
+            .unwrap();
+        assert_eq!(body, "[]");
+        server.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn fetch_ok() {
+        let config = fetch_config(MAX_BODY_SIZE);
+        let (r, header) =
+            fetch_local(&config, "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello").await;
+        assert_eq!(r.unwrap(), "hello");
+        assert!(header.starts_with("GET /users?group=a HTTP/1.1\r\n"));
+        assert!(header.contains("\r\nAuthorization: Bearer secret\r\n"));
+    }
+
+    #[tokio::test]
+    async fn fetch_not_ok() {
+        let config = fetch_config(MAX_BODY_SIZE);
+        let (r, _) = fetch_local(
+            &config,
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 5\r\n\r\nerror",
+        )
+        .await;
+        assert!(r.is_err());
+    }
+
+    #[tokio::test]
+    async fn fetch_body_size() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        let config = fetch_config(5);
+        let (r, _) = fetch_local(&config, response).await;
+        assert_eq!(r.unwrap(), "hello");
+
+        let config = fetch_config(4);
+        let (r, _) = fetch_local(&config, response).await;
+        assert!(r.is_err());
+    }
+
+    #[tokio::test]
+    async fn host_header() {
+        let header = fetch_duplex("http://example.net/users").await;
+        assert!(header.contains("\r\nHost: example.net\r\n"));
+
+        let header = fetch_duplex("http://example.net:80/users").await;
+        assert!(header.contains("\r\nHost: example.net\r\n"));
+
+        let header = fetch_duplex("https://example.net:8443/users").await;
+        assert!(header.contains("\r\nHost: example.net:8443\r\n"));
+
+        let header = fetch_duplex("http://[::1]:8080/users").await;
+        assert!(header.contains("\r\nHost: [::1]:8080\r\n"));
+    }
+}
@@ -29,6 +29,7 @@ use tokio::net::UnixStream;
 use g3proxy_proto::proc_capnp::proc_control;
 
 mod common;
+mod fetch;
 mod proc;
 
 mod escaper;
@@ -14,46 +14,198 @@
  * limitations under the License.
  */
 
+use std::io::IsTerminal;
 use std::path::PathBuf;
 use std::str::FromStr;
 
 use anyhow::anyhow;
-use clap::{value_parser, Arg, ArgMatches, Command, ValueHint};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command, ValueHint};
+use openssl::x509::X509;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use url::Url;
 
 use g3_ctl::{CommandError, CommandResult};
 
 use g3proxy_proto::proc_capnp::proc_control;
-use g3proxy_proto::user_group_capnp::user_group_control;
+use g3proxy_proto::user_group_capnp::{user_group_control, UserState};
 
 use super::common::parse_operation_result;
+use super::fetch::{HttpFetchConfig, MAX_BODY_SIZE};
 
 pub const COMMAND: &str = "user-group";
 
 const COMMAND_ARG_NAME: &str = "name";
 const COMMAND_ARG_FILE: &str = "file";
+const COMMAND_ARG_EXPAND_ENV: &str = "expand-env";
+const COMMAND_ARG_ALLOW_UNDEFINED: &str = "allow-undefined";
+const COMMAND_ARG_USER: &str = "user";
+const COMMAND_ARG_SECRET_FILE: &str = "secret-file";
+const COMMAND_ARG_PREFIX: &str = "prefix";
+const COMMAND_ARG_STATE: &str = "state";
+const COMMAND_ARG_TERMINATE: &str = "terminate";
+const COMMAND_ARG_BEARER_TOKEN_FILE: &str = "bearer-token-file";
+const COMMAND_ARG_TIMEOUT: &str = "timeout";
+const COMMAND_ARG_NO_VERIFY: &str = "no-verify";
+const COMMAND_ARG_CA_CERT: &str = "ca-cert";
+const COMMAND_ARG_RESTORE: &str = "restore";
 
 const SUBCOMMAND_LIST_STATIC_USER: &str = "list-static-user";
 const SUBCOMMAND_LIST_DYNAMIC_USER: &str = "list-dynamic-user";
 const SUBCOMMAND_PUBLISH_USER: &str = "publish-user";
+const SUBCOMMAND_MERGE_USER: &str = "merge-user";
+const SUBCOMMAND_CHECK_AUTH: &str = "check-auth";
+const SUBCOMMAND_DESCRIBE: &str = "describe";
+const SUBCOMMAND_COUNT: &str = "count";
+const SUBCOMMAND_BLOCK_USER: &str = "block-user";
+const SUBCOMMAND_UNBLOCK_USER: &str = "unblock-user";
+const SUBCOMMAND_SIMULATE_SOURCE_FAILURE: &str = "simulate-source-failure";
 
 pub fn command() -> Command {
     Command::new(COMMAND)
         .arg(Arg::new(COMMAND_ARG_NAME).required(true).num_args(1))
         .subcommand_required(true)
-        .subcommand(Command::new(SUBCOMMAND_LIST_STATIC_USER).about("List static users"))
+        .subcommand(
+            Command::new(SUBCOMMAND_LIST_STATIC_USER)
+                .about("List static users")
+                .arg(
+                    Arg::new(COMMAND_ARG_PREFIX)
+                        .help("Only list users with this name prefix")
+                        .long(COMMAND_ARG_PREFIX)
+                        .num_args(1),
+                )
+                .arg(
+                    Arg::new(COMMAND_ARG_STATE)
+                        .help("Only list users in this state")
+                        .long(COMMAND_ARG_STATE)
+                        .num_args(1)
+                        .value_parser(["active", "blocked", "expired"]),
+                ),
+        )
         .subcommand(Command::new(SUBCOMMAND_LIST_DYNAMIC_USER).about("List dynamic users"))
         .subcommand(
+            Command::new(SUBCOMMAND_COUNT).about("Show the count of static and dynamic users"),
+        )
+        .subcommand(add_user_source_args(
             Command::new(SUBCOMMAND_PUBLISH_USER)
                 .about("Publish dynamic users")
-                .visible_aliases(["publish", "publish-dynamic-user"])
+                .visible_aliases(["publish", "publish-dynamic-user"]),
+        ))
+        .subcommand(add_user_source_args(
+            Command::new(SUBCOMMAND_MERGE_USER)
+                .about("Merge users into the existing dynamic users, without removing others")
+                .visible_aliases(["merge", "merge-dynamic-user"]),
+        ))
+        .subcommand(
+            Command::new(SUBCOMMAND_CHECK_AUTH)
+                .about(
+                    "Check if the user credentials would pass the auth. \
+                    The secret will be read from stdin if no secret file is set",
+                )
+                .arg(Arg::new(COMMAND_ARG_USER).required(true).num_args(1))
                 .arg(
-                    Arg::new(COMMAND_ARG_FILE)
-                        .required(true)
+                    Arg::new(COMMAND_ARG_SECRET_FILE)
+                        .help("Read the secret from the first line of this file")
+                        .long(COMMAND_ARG_SECRET_FILE)
                         .num_args(1)
                         .value_parser(value_parser!(PathBuf))
                         .value_hint(ValueHint::FilePath),
                 ),
         )
+        .subcommand(
+            Command::new(SUBCOMMAND_BLOCK_USER)
+                .about("Block the user for new auths, until unblocked")
+                .arg(Arg::new(COMMAND_ARG_USER).required(true).num_args(1))
+                .arg(
+                    Arg::new(COMMAND_ARG_TERMINATE)
+                        .help("Also terminate the existing sessions of this user")
+                        .long(COMMAND_ARG_TERMINATE)
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(SUBCOMMAND_UNBLOCK_USER)
+                .about("Unblock the user blocked by the block-user command")
+                .arg(Arg::new(COMMAND_ARG_USER).required(true).num_args(1)),
+        )
+        .subcommand(
+            Command::new(SUBCOMMAND_SIMULATE_SOURCE_FAILURE)
+                .about(
+                    "Simulate the failure of the dynamic user source, for testing only. \
+                    Only allowed if allow_fault_injection is enabled in the group config",
+                )
+                .arg(
+                    Arg::new(COMMAND_ARG_RESTORE)
+                        .help("Stop the simulation and restore the dynamic user source")
+                        .long(COMMAND_ARG_RESTORE)
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(SUBCOMMAND_DESCRIBE)
+                .about("Show the effective config of this user group, with secrets excluded"),
+        )
+}
+
+fn add_user_source_args(cmd: Command) -> Command {
+    cmd.arg(
+        Arg::new(COMMAND_ARG_FILE)
+            .help("File path, '-' for stdin, or http(s) url to fetch the users")
+            .value_name("SOURCE")
+            .required(true)
+            .num_args(1)
+            .value_hint(ValueHint::AnyPath),
+    )
+    .arg(
+        Arg::new(COMMAND_ARG_BEARER_TOKEN_FILE)
+            .help(
+                "Read the bearer token to use when fetching from url \
+                from the first line of this file",
+            )
+            .value_name("FILE")
+            .long(COMMAND_ARG_BEARER_TOKEN_FILE)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::FilePath),
+    )
+    .arg(
+        Arg::new(COMMAND_ARG_TIMEOUT)
+            .help("Timeout for fetching from url")
+            .value_name("DURATION")
+            .long(COMMAND_ARG_TIMEOUT)
+            .num_args(1)
+            .default_value("30s"),
+    )
+    .arg(
+        Arg::new(COMMAND_ARG_NO_VERIFY)
+            .help("Skip tls verification when fetching from https url")
+            .long(COMMAND_ARG_NO_VERIFY)
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new(COMMAND_ARG_CA_CERT)
+            .help("Extra CA certificates to verify the https server")
+            .value_name("FILE")
+            .long(COMMAND_ARG_CA_CERT)
+            .num_args(1)
+            .value_parser(value_parser!(PathBuf))
+            .value_hint(ValueHint::FilePath)
+            .conflicts_with(COMMAND_ARG_NO_VERIFY),
+    )
+    .arg(
+        Arg::new(COMMAND_ARG_EXPAND_ENV)
+            .help(
+                "Expand ${VAR} environment variables in the file contents, use $${ for literal ${",
+            )
+            .long(COMMAND_ARG_EXPAND_ENV)
+            .action(ArgAction::SetTrue),
+    )
+    .arg(
+        Arg::new(COMMAND_ARG_ALLOW_UNDEFINED)
+            .help("Expand undefined environment variables to empty string")
+            .long(COMMAND_ARG_ALLOW_UNDEFINED)
+            .action(ArgAction::SetTrue)
+            .requires(COMMAND_ARG_EXPAND_ENV),
+    )
 }
 
 pub async fn run(client: &proc_control::Client, args: &ArgMatches) -> CommandResult<()> {
@@ -63,15 +215,37 @@ pub async fn run(client: &proc_control::Client, args: &ArgMatches) -> CommandRes
 
     let (subcommand, args) = args.subcommand().unwrap();
     match subcommand {
-        SUBCOMMAND_LIST_STATIC_USER => list_static_user(&user_group).await,
+        SUBCOMMAND_LIST_STATIC_USER => list_static_user(&user_group, args).await,
         SUBCOMMAND_LIST_DYNAMIC_USER => list_dynamic_user(&user_group).await,
+        SUBCOMMAND_COUNT => count_user(&user_group).await,
         SUBCOMMAND_PUBLISH_USER => publish_dynamic_user(&user_group, args).await,
+        SUBCOMMAND_MERGE_USER => merge_dynamic_user(&user_group, args).await,
+        SUBCOMMAND_CHECK_AUTH => check_user_auth(&user_group, args).await,
+        SUBCOMMAND_BLOCK_USER => block_user(&user_group, args).await,
+        SUBCOMMAND_UNBLOCK_USER => unblock_user(&user_group, args).await,
+        SUBCOMMAND_SIMULATE_SOURCE_FAILURE => simulate_source_failure(&user_group, args).await,
+        SUBCOMMAND_DESCRIBE => describe(&user_group).await,
         _ => unreachable!(),
     }
 }
 
-async fn list_static_user(client: &user_group_control::Client) -> CommandResult<()> {
-    let req = client.list_static_user_request();
+async fn list_static_user(
+    client: &user_group_control::Client,
+    args: &ArgMatches,
+) -> CommandResult<()> {
+    let mut req = client.list_static_user_request();
+    if let Some(prefix) = args.get_one::<String>(COMMAND_ARG_PREFIX) {
+        req.get().set_prefix(prefix.as_str());
+    }
+    if let Some(state) = args.get_one::<String>(COMMAND_ARG_STATE) {
+        let state = match state.as_str() {
+            "active" => UserState::Active,
+            "blocked" => UserState::Blocked,
+            "expired" => UserState::Expired,
+            _ => unreachable!(),
+        };
+        req.get().set_state(state);
+    }
     let rsp = req.send().promise.await?;
     g3_ctl::print_result_list(rsp.get()?.get_result()?)
 }
@@ -82,19 +256,81 @@ async fn list_dynamic_user(client: &user_group_control::Client) -> CommandResult
     g3_ctl::print_result_list(rsp.get()?.get_result()?)
 }
 
+async fn count_user(client: &user_group_control::Client) -> CommandResult<()> {
+    let req = client.count_user_request();
+    let rsp = req.send().promise.await?;
+    let rsp = rsp.get()?;
+    let static_count = rsp.get_static_count();
+    let dynamic_count = rsp.get_dynamic_count();
+    println!("static: {static_count}");
+    println!("dynamic: {dynamic_count}");
+    println!("total: {}", static_count + dynamic_count);
+    Ok(())
+}
+
+async fn simulate_source_failure(
+    client: &user_group_control::Client,
+    args: &ArgMatches,
+) -> CommandResult<()> {
+    let mut req = client.simulate_source_failure_request();
+    req.get().set_enable(!args.get_flag(COMMAND_ARG_RESTORE));
+    let rsp = req.send().promise.await?;
+    parse_operation_result(rsp.get()?.get_result()?)
+}
+
+async fn describe(client: &user_group_control::Client) -> CommandResult<()> {
+    let req = client.describe_request();
+    let rsp = req.send().promise.await?;
+    g3_ctl::print_text("result", rsp.get()?.get_result()?)
+}
+
 async fn publish_dynamic_user(
     client: &user_group_control::Client,
     args: &ArgMatches,
 ) -> CommandResult<()> {
-    let data = if let Some(file) = args.get_one::<PathBuf>(COMMAND_ARG_FILE) {
-        tokio::fs::read_to_string(file).await.map_err(|e| {
-            CommandError::Cli(anyhow!(
-                "failed to read contents of file {}: {e:?}",
-                file.display()
-            ))
+    let data = load_user_data(args).await?;
+    let mut req = client.publish_dynamic_user_request();
+    req.get().set_contents(data.as_str());
+    let rsp = req.send().promise.await?;
+    parse_operation_result(rsp.get()?.get_result()?)
+}
+
+async fn merge_dynamic_user(
+    client: &user_group_control::Client,
+    args: &ArgMatches,
+) -> CommandResult<()> {
+    let data = load_user_data(args).await?;
+    let mut req = client.merge_dynamic_user_request();
+    req.get().set_contents(data.as_str());
+    let rsp = req.send().promise.await?;
+    parse_operation_result(rsp.get()?.get_result()?)
+}
+
+async fn load_user_data(args: &ArgMatches) -> CommandResult<String> {
+    let source = args.get_one::<String>(COMMAND_ARG_FILE).unwrap();
+    let data = if source == "-" {
+        let mut data = String::new();
+        tokio::io::stdin()
+            .read_to_string(&mut data)
+            .await
+            .map_err(|e| CommandError::Cli(anyhow!("failed to read from stdin: {e:?}")))?;
+        data
+    } else if source.starts_with("http://") || source.starts_with("https://") {
+        let url = Url::parse(source)
+            .map_err(|e| CommandError::Cli(anyhow!("invalid url {source}: {e}")))?;
+        let config = build_fetch_config(args).map_err(CommandError::Cli)?;
+        config.fetch(&url).await.map_err(CommandError::Cli)?
+    } else {
+        tokio::fs::read_to_string(source).await.map_err(|e| {
+            CommandError::Cli(anyhow!("failed to read contents of file {source}: {e:?}"))
         })?
+    };
+
+    let data = if args.get_flag(COMMAND_ARG_EXPAND_ENV) {
+        expand_env_vars(&data, args.get_flag(COMMAND_ARG_ALLOW_UNDEFINED))
+            .map_err(CommandError::Cli)?
     } else {
-        unreachable!()
+        data
     };
 
     if let Err(e) = serde_json::Value::from_str(&data) {
@@ -102,9 +338,167 @@ async fn publish_dynamic_user(
             "the data to publish is not valid json: {e:?}"
         )));
     }
+    Ok(data)
+}
 
-    let mut req = client.publish_dynamic_user_request();
-    req.get().set_contents(data.as_str());
+fn build_fetch_config(args: &ArgMatches) -> anyhow::Result<HttpFetchConfig> {
+    let timeout = g3_clap::humanize::get_duration(args, COMMAND_ARG_TIMEOUT)?.unwrap();
+    let ca_certs = if let Some(file) = args.get_one::<PathBuf>(COMMAND_ARG_CA_CERT) {
+        let contents = std::fs::read(file)
+            .map_err(|e| anyhow!("failed to read file {}: {e:?}", file.display()))?;
+        let certs = X509::stack_from_pem(&contents)
+            .map_err(|e| anyhow!("invalid certificate file {}: {e}", file.display()))?;
+        if certs.is_empty() {
+            return Err(anyhow!(
+                "no valid certificate found in file {}",
+                file.display()
+            ));
+        }
+        certs
+    } else {
+        Vec::new()
+    };
+    let bearer_token = if let Some(file) = args.get_one::<PathBuf>(COMMAND_ARG_BEARER_TOKEN_FILE) {
+        let contents = std::fs::read_to_string(file)
+            .map_err(|e| anyhow!("failed to read file {}: {e:?}", file.display()))?;
+        let token = contents.lines().next().unwrap_or_default();
+        if token.is_empty() {
+            return Err(anyhow!("no bearer token found in file {}", file.display()));
+        }
+        Some(token.to_string())
+    } else {
+        None
+    };
+    Ok(HttpFetchConfig {
+        bearer_token,
+        timeout,
+        no_verify: args.get_flag(COMMAND_ARG_NO_VERIFY),
+        ca_certs,
+        max_body_size: MAX_BODY_SIZE,
+    })
+}
+
+fn expand_env_vars(data: &str, allow_undefined: bool) -> anyhow::Result<String> {
+    let mut output = String::with_capacity(data.len());
+    let mut left = data;
+    while let Some(p) = left.find("${") {
+        if left[..p].ends_with('$') {
+            // escaped as $${
+            output.push_str(&left[..p - 1]);
+            output.push_str("${");
+            left = &left[p + 2..];
+            continue;
+        }
+        output.push_str(&left[..p]);
+        let var_start = &left[p + 2..];
+        let Some(end) = var_start.find('}') else {
+            return Err(anyhow!("unclosed variable reference at {}", &left[p..]));
+        };
+        let name = &var_start[..end];
+        match std::env::var(name) {
+            Ok(v) => output.push_str(&v),
+            Err(std::env::VarError::NotPresent) if allow_undefined => {}
+            Err(e) => return Err(anyhow!("failed to get env var {name}: {e}")),
+        }
+        left = &var_start[end + 1..];
+    }
+    output.push_str(left);
+    Ok(output)
+}
+
+/// read the secret from the first line of the file or stdin,
+/// so it won't be exposed in the process list or the shell history
+async fn read_secret(file: Option<&PathBuf>) -> CommandResult<String> {
+    let data = if let Some(path) = file {
+        tokio::fs::read_to_string(path).await.map_err(|e| {
+            CommandError::Cli(anyhow!(
+                "failed to read secret file {}: {e:?}",
+                path.display()
+            ))
+        })?
+    } else {
+        if std::io::stdin().is_terminal() {
+            eprint!("Secret: ");
+        }
+        let mut data = String::new();
+        BufReader::new(tokio::io::stdin())
+            .read_line(&mut data)
+            .await
+            .map_err(|e| CommandError::Cli(anyhow!("failed to read from stdin: {e:?}")))?;
+        data
+    };
+    let secret = data.lines().next().unwrap_or_default();
+    if secret.is_empty() {
+        return Err(CommandError::Cli(anyhow!("no secret found")));
+    }
+    Ok(secret.to_string())
+}
+
+async fn check_user_auth(
+    client: &user_group_control::Client,
+    args: &ArgMatches,
+) -> CommandResult<()> {
+    let user = args.get_one::<String>(COMMAND_ARG_USER).unwrap();
+    let secret = read_secret(args.get_one::<PathBuf>(COMMAND_ARG_SECRET_FILE)).await?;
+
+    let mut req = client.check_user_auth_request();
+    req.get().set_user(user.as_str());
+    req.get().set_token(secret.as_str());
+    let rsp = req.send().promise.await?;
+    parse_operation_result(rsp.get()?.get_result()?)
+}
+
+async fn block_user(client: &user_group_control::Client, args: &ArgMatches) -> CommandResult<()> {
+    let user = args.get_one::<String>(COMMAND_ARG_USER).unwrap();
+
+    let mut req = client.block_user_request();
+    req.get().set_user(user.as_str());
+    req.get()
+        .set_terminate(args.get_flag(COMMAND_ARG_TERMINATE));
+    let rsp = req.send().promise.await?;
+    parse_operation_result(rsp.get()?.get_result()?)
+}
+
+async fn unblock_user(client: &user_group_control::Client, args: &ArgMatches) -> CommandResult<()> {
+    let user = args.get_one::<String>(COMMAND_ARG_USER).unwrap();
+
+    let mut req = client.unblock_user_request();
+    req.get().set_user(user.as_str());
     let rsp = req.send().promise.await?;
     parse_operation_result(rsp.get()?.get_result()?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_defined_var() {
+        std::env::set_var("G3PROXY_CTL_TEST_DEFINED", "bar");
+        assert_eq!(
+            expand_env_vars("foo-${G3PROXY_CTL_TEST_DEFINED}-baz", false).unwrap(),
+            "foo-bar-baz"
+        );
+    }
+
+    #[test]
+    fn expand_undefined_var() {
+        let data = "foo-${G3PROXY_CTL_TEST_UNDEFINED}-baz";
+        assert!(expand_env_vars(data, false).is_err());
+        assert_eq!(expand_env_vars(data, true).unwrap(), "foo--baz");
+    }
+
+    #[test]
+    fn expand_literal_dollar() {
+        assert_eq!(expand_env_vars("a$b$$c$", false).unwrap(), "a$b$$c$");
+        assert_eq!(
+            expand_env_vars("$${G3PROXY_CTL_TEST_UNDEFINED}", false).unwrap(),
+            "${G3PROXY_CTL_TEST_UNDEFINED}"
+        );
+    }
+
+    #[test]
+    fn expand_unclosed_var() {
+        assert!(expand_env_vars("foo-${BAR", true).is_err());
+    }
+}
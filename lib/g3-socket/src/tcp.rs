@@ -51,18 +51,46 @@ pub fn new_std_socket_to(
     keepalive: &TcpKeepAliveConfig,
     misc_opts: &TcpMiscSockOpts,
     default_set_nodelay: bool,
+) -> io::Result<std::net::TcpStream> {
+    let bind_addr = bind_ip.map(|ip| SocketAddr::new(ip, 0));
+    new_std_socket_bind_to(
+        peer_ip,
+        bind_addr,
+        false,
+        keepalive,
+        misc_opts,
+        default_set_nodelay,
+    )
+}
+
+/// create a new socket to `peer_ip`, with an optional bind address.
+/// The local port will be assigned at connect time if the port of the bind address is 0,
+/// and SO_REUSEADDR can be set to allow to reuse ports that are still in TIME_WAIT state
+pub fn new_std_socket_bind_to(
+    peer_ip: IpAddr,
+    bind_addr: Option<SocketAddr>,
+    reuse_addr: bool,
+    keepalive: &TcpKeepAliveConfig,
+    misc_opts: &TcpMiscSockOpts,
+    default_set_nodelay: bool,
 ) -> io::Result<std::net::TcpStream> {
     let peer_family = AddressFamily::from(&peer_ip);
     let socket = new_tcp_socket(peer_family)?;
-    if let Some(ip) = bind_ip {
+    if reuse_addr {
+        socket.set_reuse_address(true)?;
+    }
+    if let Some(addr) = bind_addr {
+        let ip = addr.ip();
         if AddressFamily::from(&ip) != peer_family {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 format!("peer_ip {peer_ip} and bind_ip {ip} should be of the same family",),
             ));
         }
-        set_bind_address_no_port(socket.as_raw_fd(), true)?;
-        let addr: SockAddr = SocketAddr::new(ip, 0).into();
+        if addr.port() == 0 {
+            set_bind_address_no_port(socket.as_raw_fd(), true)?;
+        }
+        let addr: SockAddr = addr.into();
         socket.bind(&addr)?;
     }
     if keepalive.is_enabled() {
@@ -161,3 +189,22 @@ pub fn new_socket_to(
     let socket = new_std_socket_to(peer_ip, bind_ip, keepalive, misc_opts, default_set_nodelay)?;
     Ok(TcpSocket::from_std_stream(socket))
 }
+
+pub fn new_socket_bind_to(
+    peer_ip: IpAddr,
+    bind_addr: Option<SocketAddr>,
+    reuse_addr: bool,
+    keepalive: &TcpKeepAliveConfig,
+    misc_opts: &TcpMiscSockOpts,
+    default_set_nodelay: bool,
+) -> io::Result<TcpSocket> {
+    let socket = new_std_socket_bind_to(
+        peer_ip,
+        bind_addr,
+        reuse_addr,
+        keepalive,
+        misc_opts,
+        default_set_nodelay,
+    )?;
+    Ok(TcpSocket::from_std_stream(socket))
+}
@@ -211,3 +211,35 @@ impl IntermediateCertBuilder {
         Ok(builder.build())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{RootCertBuilder, TlsServerCertBuilder};
+    use g3_types::net::Host;
+
+    #[test]
+    fn pathlen_only_for_ca() {
+        let root_builder = RootCertBuilder::new_ec256().unwrap();
+        let root_cert = root_builder.build(None).unwrap();
+
+        let builder = IntermediateCertBuilder::new_ec256().unwrap();
+        let ca_cert = builder
+            .build(Some(2), &root_cert, root_builder.pkey(), None)
+            .unwrap();
+        assert_eq!(ca_cert.pathlen(), Some(2));
+
+        let sub_builder = IntermediateCertBuilder::new_ec256().unwrap();
+        let sub_cert = sub_builder
+            .build(Some(1), &ca_cert, builder.pkey(), None)
+            .unwrap();
+        assert_eq!(sub_cert.pathlen(), Some(1));
+
+        let leaf_builder = TlsServerCertBuilder::new_ec256().unwrap();
+        let host = Host::Domain("www.example.net".to_string());
+        let leaf_cert = leaf_builder
+            .build_fake(&host, &ca_cert, builder.pkey(), None)
+            .unwrap();
+        assert_eq!(leaf_cert.pathlen(), None);
+    }
+}
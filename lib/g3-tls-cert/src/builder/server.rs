@@ -390,3 +390,50 @@ impl ServerCertBuilder {
         Ok(builder.build())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::RootCertBuilder;
+    use openssl::nid::Nid;
+
+    fn subject_entry(cert: &X509, nid: Nid) -> Option<String> {
+        cert.subject_name()
+            .entries_by_nid(nid)
+            .next()
+            .map(|e| e.data().as_utf8().unwrap().to_string())
+    }
+
+    #[test]
+    fn build_fake_with_subject() {
+        let mut root_builder = RootCertBuilder::new_ec256().unwrap();
+        root_builder
+            .subject_builder_mut()
+            .set_common_name("Test Root CA".to_string());
+        let root_cert = root_builder.build(None).unwrap();
+
+        let mut builder = TlsServerCertBuilder::new_ec256().unwrap();
+        let subject_builder = builder.subject_builder_mut();
+        subject_builder.set_country("CN".to_string());
+        subject_builder.set_organization("G3 Test".to_string());
+        subject_builder.set_organization_unit("Fake Cert".to_string());
+
+        let host = Host::Domain("www.example.net".to_string());
+        let cert = builder
+            .build_fake(&host, &root_cert, root_builder.pkey(), None)
+            .unwrap();
+        assert_eq!(subject_entry(&cert, Nid::COUNTRYNAME).unwrap(), "CN");
+        assert_eq!(
+            subject_entry(&cert, Nid::ORGANIZATIONNAME).unwrap(),
+            "G3 Test"
+        );
+        assert_eq!(
+            subject_entry(&cert, Nid::ORGANIZATIONALUNITNAME).unwrap(),
+            "Fake Cert"
+        );
+        assert_eq!(
+            subject_entry(&cert, Nid::COMMONNAME).unwrap(),
+            "www.example.net"
+        );
+    }
+}
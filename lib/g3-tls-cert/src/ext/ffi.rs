@@ -17,6 +17,9 @@
 use libc::{c_int, c_uchar, c_uint};
 use openssl_sys::RSA;
 
+#[cfg(ossl300)]
+pub use store::*;
+
 extern "C" {
 
     pub fn RSA_sign_ASN1_OCTET_STRING(
@@ -28,3 +31,87 @@ extern "C" {
         rsa: *mut RSA,
     ) -> c_int;
 }
+
+#[cfg(ossl300)]
+#[allow(non_camel_case_types)]
+mod store {
+    use libc::{c_char, c_int, c_uint, c_void, size_t};
+    use openssl_sys::{EVP_MD_CTX, EVP_PKEY, EVP_PKEY_CTX};
+
+    pub enum OSSL_PROVIDER {}
+    pub enum OSSL_STORE_CTX {}
+    pub enum OSSL_STORE_INFO {}
+
+    pub const OSSL_STORE_INFO_PKEY: c_int = 4;
+
+    #[repr(C)]
+    pub struct OSSL_PARAM {
+        key: *const c_char,
+        data_type: c_uint,
+        data: *mut c_void,
+        data_size: size_t,
+        return_size: size_t,
+    }
+
+    extern "C" {
+        pub fn OSSL_PROVIDER_load(libctx: *mut c_void, name: *const c_char) -> *mut OSSL_PROVIDER;
+
+        pub fn OSSL_STORE_open(
+            uri: *const c_char,
+            ui_method: *const c_void,
+            ui_data: *mut c_void,
+            post_process: *const c_void,
+            post_process_data: *mut c_void,
+        ) -> *mut OSSL_STORE_CTX;
+
+        pub fn OSSL_STORE_expect(ctx: *mut OSSL_STORE_CTX, expected_type: c_int) -> c_int;
+
+        pub fn OSSL_STORE_load(ctx: *mut OSSL_STORE_CTX) -> *mut OSSL_STORE_INFO;
+
+        pub fn OSSL_STORE_eof(ctx: *mut OSSL_STORE_CTX) -> c_int;
+
+        pub fn OSSL_STORE_error(ctx: *mut OSSL_STORE_CTX) -> c_int;
+
+        pub fn OSSL_STORE_close(ctx: *mut OSSL_STORE_CTX) -> c_int;
+
+        pub fn OSSL_STORE_INFO_get_type(info: *const OSSL_STORE_INFO) -> c_int;
+
+        pub fn OSSL_STORE_INFO_get1_PKEY(info: *const OSSL_STORE_INFO) -> *mut EVP_PKEY;
+
+        pub fn OSSL_STORE_INFO_free(info: *mut OSSL_STORE_INFO);
+
+        pub fn OSSL_PARAM_construct_utf8_string(
+            key: *const c_char,
+            buf: *mut c_char,
+            bsize: size_t,
+        ) -> OSSL_PARAM;
+
+        pub fn OSSL_PARAM_construct_octet_string(
+            key: *const c_char,
+            buf: *mut c_void,
+            bsize: size_t,
+        ) -> OSSL_PARAM;
+
+        pub fn OSSL_PARAM_construct_end() -> OSSL_PARAM;
+
+        pub fn EVP_DigestSignInit_ex(
+            ctx: *mut EVP_MD_CTX,
+            pctx: *mut *mut EVP_PKEY_CTX,
+            mdname: *const c_char,
+            libctx: *mut c_void,
+            props: *const c_char,
+            pkey: *mut EVP_PKEY,
+            params: *const OSSL_PARAM,
+        ) -> c_int;
+
+        pub fn EVP_DigestVerifyInit_ex(
+            ctx: *mut EVP_MD_CTX,
+            pctx: *mut *mut EVP_PKEY_CTX,
+            mdname: *const c_char,
+            libctx: *mut c_void,
+            props: *const c_char,
+            pkey: *mut EVP_PKEY,
+            params: *const OSSL_PARAM,
+        ) -> c_int;
+    }
+}
@@ -24,3 +24,20 @@ pub use rsa::RsaExt;
 
 mod pkey;
 pub use pkey::PublicKeyExt;
+
+#[cfg(ossl300)]
+mod store;
+#[cfg(ossl300)]
+pub use store::{load_private_key_by_uri, load_provider};
+
+#[cfg(ossl300)]
+mod sign;
+#[cfg(ossl300)]
+pub use sign::{sign_ed25519ctx, sign_sm2, verify_sm2};
+
+#[cfg(not(ossl300))]
+mod unsupported;
+#[cfg(not(ossl300))]
+pub use unsupported::{
+    load_private_key_by_uri, load_provider, sign_ed25519ctx, sign_sm2, verify_sm2,
+};
@@ -0,0 +1,209 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::ptr;
+
+use anyhow::anyhow;
+use libc::{c_char, c_void};
+use openssl::error::ErrorStack;
+use openssl::foreign_types::{ForeignType, ForeignTypeRef};
+use openssl::pkey::{HasPublic, PKey, PKeyRef, Private};
+use openssl_sys::EVP_MD_CTX;
+
+use super::ffi;
+
+/// sign the data with Ed25519ctx as defined in RFC 8032, the context should be 1 - 255 bytes.
+/// This requires OpenSSL 3.2 or later
+#[cfg(ossl320)]
+pub fn sign_ed25519ctx(
+    pkey: &PKey<Private>,
+    context: &[u8],
+    data: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    if context.is_empty() || context.len() > 255 {
+        return Err(anyhow!(
+            "the Ed25519ctx context length should be in range 1 - 255"
+        ));
+    }
+
+    let mut instance = *b"Ed25519ctx\0";
+    unsafe {
+        let params = [
+            ffi::OSSL_PARAM_construct_utf8_string(
+                b"instance\0".as_ptr() as *const c_char,
+                instance.as_mut_ptr() as *mut c_char,
+                0,
+            ),
+            ffi::OSSL_PARAM_construct_octet_string(
+                b"context-string\0".as_ptr() as *const c_char,
+                context.as_ptr() as *mut c_void,
+                context.len(),
+            ),
+            ffi::OSSL_PARAM_construct_end(),
+        ];
+
+        let md_ctx = openssl_sys::EVP_MD_CTX_new();
+        if md_ctx.is_null() {
+            return Err(anyhow!(
+                "failed to create EVP_MD_CTX: {}",
+                ErrorStack::get()
+            ));
+        }
+        let r = if ffi::EVP_DigestSignInit_ex(
+            md_ctx,
+            ptr::null_mut(),
+            ptr::null(),
+            ptr::null_mut(),
+            ptr::null(),
+            pkey.as_ptr(),
+            params.as_ptr(),
+        ) != 1
+        {
+            Err(anyhow!("sign init failed: {}", ErrorStack::get()))
+        } else {
+            digest_sign(md_ctx, data)
+        };
+        openssl_sys::EVP_MD_CTX_free(md_ctx);
+        r
+    }
+}
+
+#[cfg(not(ossl320))]
+pub fn sign_ed25519ctx(
+    _pkey: &PKey<Private>,
+    _context: &[u8],
+    _data: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    Err(anyhow!("ed25519ctx requires OpenSSL 3.2 or later"))
+}
+
+/// sign the data with SM2 and SM3, the distinguishing identifier is used to compute Z
+pub fn sign_sm2(pkey: &PKey<Private>, id: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    unsafe {
+        let params = sm2_params(id);
+
+        let md_ctx = openssl_sys::EVP_MD_CTX_new();
+        if md_ctx.is_null() {
+            return Err(anyhow!(
+                "failed to create EVP_MD_CTX: {}",
+                ErrorStack::get()
+            ));
+        }
+        let r = if ffi::EVP_DigestSignInit_ex(
+            md_ctx,
+            ptr::null_mut(),
+            b"SM3\0".as_ptr() as *const c_char,
+            ptr::null_mut(),
+            ptr::null(),
+            pkey.as_ptr(),
+            params.as_ptr(),
+        ) != 1
+        {
+            Err(anyhow!("sign init failed: {}", ErrorStack::get()))
+        } else {
+            digest_sign(md_ctx, data)
+        };
+        openssl_sys::EVP_MD_CTX_free(md_ctx);
+        r
+    }
+}
+
+/// verify the SM2 signature of the data, which should be signed with the same
+/// distinguishing identifier
+pub fn verify_sm2<T: HasPublic>(
+    pkey: &PKeyRef<T>,
+    id: &[u8],
+    data: &[u8],
+    sig: &[u8],
+) -> anyhow::Result<bool> {
+    unsafe {
+        let params = sm2_params(id);
+
+        let md_ctx = openssl_sys::EVP_MD_CTX_new();
+        if md_ctx.is_null() {
+            return Err(anyhow!(
+                "failed to create EVP_MD_CTX: {}",
+                ErrorStack::get()
+            ));
+        }
+        let r = if ffi::EVP_DigestVerifyInit_ex(
+            md_ctx,
+            ptr::null_mut(),
+            b"SM3\0".as_ptr() as *const c_char,
+            ptr::null_mut(),
+            ptr::null(),
+            pkey.as_ptr(),
+            params.as_ptr(),
+        ) != 1
+        {
+            Err(anyhow!("verify init failed: {}", ErrorStack::get()))
+        } else {
+            let r = openssl_sys::EVP_DigestVerify(
+                md_ctx,
+                sig.as_ptr(),
+                sig.len(),
+                data.as_ptr(),
+                data.len(),
+            );
+            // clear the error stack for the invalid signatures
+            let _ = ErrorStack::get();
+            Ok(r == 1)
+        };
+        openssl_sys::EVP_MD_CTX_free(md_ctx);
+        r
+    }
+}
+
+unsafe fn sm2_params(id: &[u8]) -> [ffi::OSSL_PARAM; 2] {
+    [
+        ffi::OSSL_PARAM_construct_octet_string(
+            b"distid\0".as_ptr() as *const c_char,
+            id.as_ptr() as *mut c_void,
+            id.len(),
+        ),
+        ffi::OSSL_PARAM_construct_end(),
+    ]
+}
+
+unsafe fn digest_sign(md_ctx: *mut EVP_MD_CTX, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut sig_len = 0;
+    if openssl_sys::EVP_DigestSign(
+        md_ctx,
+        ptr::null_mut(),
+        &mut sig_len,
+        data.as_ptr(),
+        data.len(),
+    ) != 1
+    {
+        return Err(anyhow!(
+            "failed to get signature length: {}",
+            ErrorStack::get()
+        ));
+    }
+    let mut sig = vec![0u8; sig_len];
+    if openssl_sys::EVP_DigestSign(
+        md_ctx,
+        sig.as_mut_ptr(),
+        &mut sig_len,
+        data.as_ptr(),
+        data.len(),
+    ) != 1
+    {
+        return Err(anyhow!("sign failed: {}", ErrorStack::get()));
+    }
+    sig.truncate(sig_len);
+    Ok(sig)
+}
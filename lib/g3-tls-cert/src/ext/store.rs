@@ -0,0 +1,85 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::ffi::CString;
+use std::ptr;
+
+use anyhow::anyhow;
+use openssl::error::ErrorStack;
+use openssl::foreign_types::ForeignType;
+use openssl::pkey::{PKey, Private};
+
+use super::ffi;
+
+/// load an OpenSSL 3 provider, it will be kept loaded until the process exit
+pub fn load_provider(name: &str) -> anyhow::Result<()> {
+    let c_name = CString::new(name).map_err(|e| anyhow!("invalid provider name {name}: {e}"))?;
+    let provider = unsafe { ffi::OSSL_PROVIDER_load(ptr::null_mut(), c_name.as_ptr()) };
+    if provider.is_null() {
+        Err(anyhow!(
+            "failed to load provider {name}: {}",
+            ErrorStack::get()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// load the first private key found at the uri through the OpenSSL store API
+pub fn load_private_key_by_uri(uri: &str) -> anyhow::Result<PKey<Private>> {
+    let c_uri = CString::new(uri).map_err(|e| anyhow!("invalid key uri {uri}: {e}"))?;
+    unsafe {
+        let ctx = ffi::OSSL_STORE_open(
+            c_uri.as_ptr(),
+            ptr::null(),
+            ptr::null_mut(),
+            ptr::null(),
+            ptr::null_mut(),
+        );
+        if ctx.is_null() {
+            return Err(anyhow!(
+                "failed to open store for uri {uri}: {}",
+                ErrorStack::get()
+            ));
+        }
+        // it's ok to fail as not all store loaders support this
+        let _ = ffi::OSSL_STORE_expect(ctx, ffi::OSSL_STORE_INFO_PKEY);
+
+        let mut pkey = None;
+        while ffi::OSSL_STORE_eof(ctx) == 0 {
+            let info = ffi::OSSL_STORE_load(ctx);
+            if info.is_null() {
+                if ffi::OSSL_STORE_error(ctx) != 0 {
+                    break;
+                }
+                continue;
+            }
+            if ffi::OSSL_STORE_INFO_get_type(info) == ffi::OSSL_STORE_INFO_PKEY {
+                let p = ffi::OSSL_STORE_INFO_get1_PKEY(info);
+                if !p.is_null() {
+                    pkey = Some(PKey::from_ptr(p));
+                }
+            }
+            ffi::OSSL_STORE_INFO_free(info);
+            if pkey.is_some() {
+                break;
+            }
+        }
+        ffi::OSSL_STORE_close(ctx);
+
+        pkey.ok_or_else(|| anyhow!("no private key found at uri {uri}: {}", ErrorStack::get()))
+    }
+}
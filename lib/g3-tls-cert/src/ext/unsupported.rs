@@ -0,0 +1,55 @@
+/*
+ * Copyright 2023 ByteDance and/or its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use anyhow::anyhow;
+use openssl::pkey::{HasPublic, PKey, PKeyRef, Private};
+
+// the functions below depend on the provider, store and params API added in OpenSSL 3.0
+
+pub fn load_provider(name: &str) -> anyhow::Result<()> {
+    Err(anyhow!(
+        "loading provider {name} is not supported by the current ssl library"
+    ))
+}
+
+pub fn load_private_key_by_uri(_uri: &str) -> anyhow::Result<PKey<Private>> {
+    Err(anyhow!(
+        "loading private key by uri is not supported by the current ssl library"
+    ))
+}
+
+pub fn sign_ed25519ctx(
+    _pkey: &PKey<Private>,
+    _context: &[u8],
+    _data: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    Err(anyhow!(
+        "ed25519ctx is not supported by the current ssl library"
+    ))
+}
+
+pub fn sign_sm2(_pkey: &PKey<Private>, _id: &[u8], _data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    Err(anyhow!("sm2 is not supported by the current ssl library"))
+}
+
+pub fn verify_sm2<T: HasPublic>(
+    _pkey: &PKeyRef<T>,
+    _id: &[u8],
+    _data: &[u8],
+    _sig: &[u8],
+) -> anyhow::Result<bool> {
+    Err(anyhow!("sm2 is not supported by the current ssl library"))
+}
@@ -20,7 +20,8 @@ use anyhow::anyhow;
 #[cfg(any(feature = "aws-lc", feature = "boringssl", feature = "tongsuo"))]
 use openssl::ssl::CertCompressionAlgorithm;
 use openssl::ssl::{
-    Ssl, SslConnector, SslConnectorBuilder, SslContext, SslMethod, SslVerifyMode, SslVersion,
+    Ssl, SslConnector, SslConnectorBuilder, SslContext, SslMethod, SslOptions, SslSession,
+    SslVerifyMode, SslVersion,
 };
 #[cfg(not(any(feature = "aws-lc", feature = "boringssl")))]
 use openssl::ssl::{SslCtValidationMode, StatusType};
@@ -77,6 +78,22 @@ impl OpensslClientConfig {
         }
         Ok(ssl)
     }
+
+    /// get the sessions in the client session cache, so they can be saved and resumed later
+    pub fn export_sessions(&self) -> Vec<SslSession> {
+        match &self.session_cache {
+            Some(cache) => cache.export(&self.ssl_context),
+            None => Vec::new(),
+        }
+    }
+
+    /// add previous saved sessions to the client session cache
+    pub fn import_sessions(&self, sessions: Vec<SslSession>) -> anyhow::Result<()> {
+        let Some(cache) = &self.session_cache else {
+            return Err(anyhow!("no client session cache enabled"));
+        };
+        cache.import(&self.ssl_context, sessions)
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -378,11 +395,47 @@ impl OpensslClientConfigBuilder {
         Ok(ctx_builder)
     }
 
+    fn new_dtls_builder(&self) -> anyhow::Result<SslConnectorBuilder> {
+        let mut ctx_builder = SslConnector::builder(SslMethod::dtls_client())
+            .map_err(|e| anyhow!("failed to create dtls context builder: {e}"))?;
+        ctx_builder.set_verify(SslVerifyMode::PEER);
+        // the socket is not known to openssl, so the mtu should be set for each ssl
+        ctx_builder.set_options(SslOptions::NO_QUERY_MTU);
+
+        let version = match self.protocol {
+            Some(OpensslProtocol::Tls11) => Some(SslVersion::DTLS1),
+            Some(OpensslProtocol::Tls12) => Some(SslVersion::DTLS1_2),
+            Some(p) => return Err(anyhow!("protocol {p:?} is not supported for dtls")),
+            None => None,
+        };
+        if let Some(version) = version {
+            ctx_builder
+                .set_min_proto_version(Some(version))
+                .map_err(|e| anyhow!("failed to set min protocol version: {e}"))?;
+            ctx_builder
+                .set_max_proto_version(Some(version))
+                .map_err(|e| anyhow!("failed to set max protocol version: {e}"))?;
+        }
+
+        if !self.ciphers.is_empty() {
+            let cipher_list = self.ciphers.join(":");
+            ctx_builder
+                .set_cipher_list(&cipher_list)
+                .map_err(|e| anyhow!("failed to set cipher list: {e}"))?;
+        }
+
+        if let Some(cert_pair) = &self.client_cert_pair {
+            cert_pair.add_to_client_ssl_context(&mut ctx_builder)?;
+        }
+
+        Ok(ctx_builder)
+    }
+
     pub fn build_with_alpn_protocols(
         &self,
         alpn_protocols: Option<Vec<AlpnProtocol>>,
     ) -> anyhow::Result<OpensslClientConfig> {
-        let mut ctx_builder = match self.protocol {
+        let ctx_builder = match self.protocol {
             Some(OpensslProtocol::Ssl3) => self.new_versioned_builder(SslVersion::SSL3)?,
             Some(OpensslProtocol::Tls1) => self.new_versioned_builder(SslVersion::TLS1)?,
             Some(OpensslProtocol::Tls11) => self.new_versioned_builder(SslVersion::TLS1_1)?,
@@ -392,7 +445,24 @@ impl OpensslClientConfigBuilder {
             Some(OpensslProtocol::Tlcp11) => self.new_tlcp_builder()?,
             None => self.new_default_builder()?,
         };
+        self.build_with_ctx_builder(ctx_builder, alpn_protocols)
+    }
 
+    /// build a dtls client config, the protocol should be tls1.1 (for DTLS 1.0) or tls1.2
+    /// (for DTLS 1.2) if set
+    pub fn build_dtls_with_alpn_protocols(
+        &self,
+        alpn_protocols: Option<Vec<AlpnProtocol>>,
+    ) -> anyhow::Result<OpensslClientConfig> {
+        let ctx_builder = self.new_dtls_builder()?;
+        self.build_with_ctx_builder(ctx_builder, alpn_protocols)
+    }
+
+    fn build_with_ctx_builder(
+        &self,
+        mut ctx_builder: SslConnectorBuilder,
+        alpn_protocols: Option<Vec<AlpnProtocol>>,
+    ) -> anyhow::Result<OpensslClientConfig> {
         if !self.supported_groups.is_empty() {
             ctx_builder
                 .set_groups_list(&self.supported_groups)
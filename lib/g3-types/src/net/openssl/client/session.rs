@@ -221,6 +221,44 @@ impl OpensslClientSessionCache {
         ctx_builder.set_ex_data(session_cache.session_cache_index, caches);
     }
 
+    /// get all the cached sessions, the newer ones come first for each site
+    pub(in crate::net::openssl) fn export(&self, ctx: &SslContext) -> Vec<SslSession> {
+        let Some(caches) = ctx.ex_data(self.session_cache_index) else {
+            return Vec::new();
+        };
+        match caches {
+            SessionCaches::One(m) => m.lock().unwrap().queue.iter().cloned().collect(),
+            SessionCaches::Many(m) => m
+                .lock()
+                .unwrap()
+                .lru
+                .iter()
+                .flat_map(|(_, c)| c.queue.iter().cloned())
+                .collect(),
+        }
+    }
+
+    /// add the sessions in the same order as exported, only supported for the one site cache
+    pub(in crate::net::openssl) fn import(
+        &self,
+        ctx: &SslContext,
+        sessions: Vec<SslSession>,
+    ) -> anyhow::Result<()> {
+        match ctx.ex_data(self.session_cache_index) {
+            Some(SessionCaches::One(m)) => {
+                let mut o = m.lock().unwrap();
+                for s in sessions.into_iter().rev() {
+                    o.push(s);
+                }
+                Ok(())
+            }
+            Some(SessionCaches::Many(_)) => Err(anyhow!(
+                "importing sessions is not supported by the multiple sites cache"
+            )),
+            None => Err(anyhow!("no session cache found in ssl context")),
+        }
+    }
+
     pub(in crate::net::openssl) fn find_and_set_cache(
         &self,
         ssl: &mut Ssl,